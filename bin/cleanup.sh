@@ -38,3 +38,10 @@ for param_with_hyphen in ${PARAMS_WITH_HYPHENS[@]}; do
     replacement=$(echo "$param_with_hyphen" | sed "s/-/_/g")
     sed -i "s/$param_with_hyphen/$replacement/g" "$DEFAULT_API_FILE"
 done
+
+# Keep the Retry-After header of error responses, so that rate limited requests are retried when
+# Shortcut allows it
+APIS_MOD_FILE="$SHORTCUT_CLIENT_SRC_DIR/apis/mod.rs"
+sed -i 's/^\(    pub entity: Option<T>,\)$/\1\n    pub retry_after: Option<String>,/' "$APIS_MOD_FILE"
+sed -i 's/^\( *\)let local_var_status = local_var_resp.status();$/&\n\1let local_var_retry_after = local_var_resp.headers().get(reqwest::header::RETRY_AFTER).and_then(|value| value.to_str().ok()).map(str::to_owned);/' "$DEFAULT_API_FILE"
+sed -i 's/entity: local_var_entity }/entity: local_var_entity, retry_after: local_var_retry_after }/' "$DEFAULT_API_FILE"
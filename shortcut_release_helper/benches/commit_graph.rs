@@ -0,0 +1,160 @@
+//! Benchmark of the history walk of the backends on a large synthetic repository, with and
+//! without a commit-graph file
+//!
+//! Run it with `cargo bench --bench commit_graph`, adding `--features gix` to benchmark the gix
+//! backend too. The number of commits defaults to 100 000 and can be set with the `BENCH_COMMITS`
+//! environment variable. The `git` command is required to write the commit-graph.
+use std::{
+    env, fs,
+    path::Path,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use git2::{Oid, Repository, Signature, Time};
+
+// The tool is a binary crate, so its backends are included directly
+#[path = "../src/git/backend.rs"]
+#[allow(dead_code, unused_imports)]
+mod backend;
+#[cfg(feature = "gix")]
+#[path = "../src/git/gix_backend.rs"]
+mod gix_backend;
+
+use backend::HistoryBackend;
+
+const DEFAULT_COMMIT_COUNT: usize = 100_000;
+const RUNS: usize = 5;
+
+fn main() {
+    let commit_count = env::var("BENCH_COMMITS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(DEFAULT_COMMIT_COUNT);
+    let path = env::temp_dir().join(format!("commit_graph_bench_{}", std::process::id()));
+    println!("Creating {commit_count} commits in {path:?}");
+    let (release, next) = create_history(&path, commit_count);
+
+    let without_commit_graph = walk_times(&path, release, next);
+
+    let status = Command::new("git")
+        .arg("--git-dir")
+        .arg(&path)
+        .args(["commit-graph", "write", "--reachable"])
+        .status()
+        .expect("Could not run git commit-graph");
+    assert!(status.success(), "Could not write the commit-graph");
+    let with_commit_graph = walk_times(&path, release, next);
+    for ((name, without_commit_graph), (_, with_commit_graph)) in
+        without_commit_graph.iter().zip(&with_commit_graph)
+    {
+        println!(
+            "{name}: {without_commit_graph:?} without commit-graph, {with_commit_graph:?} with \
+            commit-graph, {:.1}x speedup",
+            without_commit_graph.as_secs_f64() / with_commit_graph.as_secs_f64()
+        );
+    }
+
+    fs::remove_dir_all(&path).expect("Could not remove the benchmark repository");
+}
+
+/// Create a bare repository with a linear history of commits, where every tenth commit merges a
+/// side commit. Returns the commits 10% and 100% of the way through the history, acting as the
+/// release and next branches.
+fn create_history(path: &Path, commit_count: usize) -> (Oid, Oid) {
+    let repository = Repository::init_bare(path).expect("Could not create the repository");
+    let tree_id = repository
+        .treebuilder(None)
+        .and_then(|builder| builder.write())
+        .expect("Could not write the empty tree");
+    let tree = repository.find_tree(tree_id).expect("Missing empty tree");
+    let mut head: Option<Oid> = None;
+    let mut release = None;
+    for index in 0..commit_count {
+        let signature = Signature::new("Bench", "bench@example.com", &Time::new(index as i64, 0))
+            .expect("Invalid signature");
+        let mut parents = head
+            .map(|head| repository.find_commit(head))
+            .transpose()
+            .expect("Missing parent")
+            .into_iter()
+            .collect::<Vec<_>>();
+        if let (true, Some(first_parent)) = (index % 10 == 9, parents.first()) {
+            let side_commit = repository
+                .commit(
+                    None,
+                    &signature,
+                    &signature,
+                    &format!("Side commit {index}"),
+                    &tree,
+                    &[first_parent],
+                )
+                .and_then(|id| repository.find_commit(id))
+                .expect("Could not create side commit");
+            parents.push(side_commit);
+        }
+        let commit_id = repository
+            .commit(
+                None,
+                &signature,
+                &signature,
+                &format!("Commit {index}"),
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )
+            .expect("Could not create commit");
+        head = Some(commit_id);
+        if index == commit_count / 10 {
+            release = Some(commit_id);
+        }
+    }
+    let next = head.expect("The history should not be empty");
+    repository
+        .reference("refs/heads/next", next, true, "benchmark")
+        .expect("Could not create the next branch");
+    (release.unwrap_or(next), next)
+}
+
+/// Median time of walking the history with each backend
+fn walk_times(path: &Path, release: Oid, next: Oid) -> Vec<(&'static str, Duration)> {
+    #[cfg_attr(not(feature = "gix"), allow(unused_mut))]
+    let mut walk_times = vec![(
+        "libgit2",
+        median_walk_time(release, next, || {
+            Box::new(Repository::open_bare(path).expect("Could not open the repository"))
+        }),
+    )];
+    #[cfg(feature = "gix")]
+    walk_times.push((
+        "gix",
+        median_walk_time(release, next, || {
+            Box::new(gix_backend::GixBackend::open(path).expect("Could not open the repository"))
+        }),
+    ));
+    walk_times
+}
+
+/// Median time of walking the commits reachable from `next` but not from `release` with
+/// [`HistoryBackend::commits_between`], reopening the repository on each run so that the
+/// commit-graph is picked up
+fn median_walk_time(
+    release: Oid,
+    next: Oid,
+    open: impl Fn() -> Box<dyn HistoryBackend>,
+) -> Duration {
+    let mut times = (0..RUNS)
+        .map(|_| {
+            let backend = open();
+            let now = Instant::now();
+            let count = backend
+                .commits_between(release, next, false)
+                .expect("Could not walk")
+                .len();
+            let elapsed = now.elapsed();
+            assert!(count > 0);
+            elapsed
+        })
+        .collect::<Vec<_>>();
+    times.sort();
+    times[RUNS / 2]
+}
@@ -0,0 +1,165 @@
+//! This module maps commits to their owners, as defined by the `CODEOWNERS` file of the
+//! repository
+//!
+//! The file follows the
+//! [GitHub syntax](https://docs.github.com/en/repositories/managing-your-repositorys-settings-and-features/customizing-your-repository/about-code-owners):
+//! each line is a gitignore-like pattern followed by its owners, and the last matching pattern
+//! takes precedence.
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::types::RepoToCommits;
+
+/// Locations of the `CODEOWNERS` file, relative to the repository root, in the order GitHub
+/// looks them up
+pub const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Key grouping the commits touching only files without owners
+pub const UNOWNED: &str = "unowned";
+
+#[derive(Debug, Default)]
+pub struct CodeOwners {
+    /// Patterns and their owners, in the order of the file
+    rules: Vec<(Regex, Vec<String>)>,
+}
+
+impl CodeOwners {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+            let owners = fields
+                .take_while(|field| !field.starts_with('#'))
+                .map(str::to_owned)
+                .collect();
+            let pattern_re = pattern_regex(pattern)
+                .with_context(|| format!("Invalid pattern on line {} of CODEOWNERS", index + 1))?;
+            rules.push((pattern_re, owners));
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Owners of the file at `path`, relative to the repository root. A matching pattern without
+    /// owners removes the owners of the previous patterns.
+    pub fn owners(&self, path: &Path) -> &[String] {
+        let path = path.to_string_lossy();
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern_re, _)| pattern_re.is_match(&path))
+            .map_or(&[], |(_, owners)| owners.as_slice())
+    }
+}
+
+/// Translate a gitignore-like pattern to a regex matching the paths of the files it covers,
+/// including the files under a matching directory
+fn pattern_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    // Patterns are relative to the root when they contain a slash other than a trailing one
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let pattern = pattern.trim_start_matches('/').trim_end_matches('/');
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push_str("(?:/.*)?$");
+    Regex::new(&regex)
+}
+
+/// Group the commits by owner, then by repository. Commits with several owners are listed under
+/// each of them, and commits without owners under [`UNOWNED`].
+pub fn group_by_owner(commits: &RepoToCommits) -> HashMap<String, RepoToCommits> {
+    let mut commits_by_owner: HashMap<String, RepoToCommits> = HashMap::new();
+    for (repo_name, commits) in commits {
+        for commit in commits {
+            let owners = if commit.owners.is_empty() {
+                vec![UNOWNED.to_owned()]
+            } else {
+                commit.owners.clone()
+            };
+            for owner in owners {
+                commits_by_owner
+                    .entry(owner)
+                    .or_default()
+                    .entry(repo_name.clone())
+                    .or_default()
+                    .push(commit.clone());
+            }
+        }
+    }
+    commits_by_owner
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owners<'a>(code_owners: &'a CodeOwners, path: &str) -> &'a [String] {
+        code_owners.owners(Path::new(path))
+    }
+
+    #[test]
+    fn parse_empty() {
+        let code_owners = CodeOwners::parse("\n# Only comments\n  \n").unwrap();
+        assert!(code_owners.is_empty());
+        assert!(owners(&code_owners, "src/main.rs").is_empty());
+    }
+
+    #[test]
+    fn last_pattern_wins() {
+        let code_owners = CodeOwners::parse(
+            "* @default\n\
+            *.rs @rust @backend # Inline comment\n\
+            /docs/ @writers\n\
+            src/generated/\n\
+            **/fixtures/** @qa\n\
+            file?.txt @files\n",
+        )
+        .unwrap();
+        assert_eq!(owners(&code_owners, "README.md"), ["@default"]);
+        assert_eq!(owners(&code_owners, "src/main.rs"), ["@rust", "@backend"]);
+        // Anchored to the root
+        assert_eq!(owners(&code_owners, "docs/guide.md"), ["@writers"]);
+        assert_eq!(owners(&code_owners, "src/docs/guide.md"), ["@default"]);
+        // A pattern without owners removes the previous ones
+        assert!(owners(&code_owners, "src/generated/api.rs").is_empty());
+        assert_eq!(owners(&code_owners, "a/b/fixtures/c/d.json"), ["@qa"]);
+        assert_eq!(owners(&code_owners, "fixtures/d.json"), ["@qa"]);
+        // `?` matches a single character, not a slash
+        assert_eq!(owners(&code_owners, "dir/file1.txt"), ["@files"]);
+        assert_eq!(owners(&code_owners, "file12.txt"), ["@default"]);
+    }
+
+    #[test]
+    fn patterns_are_escaped() {
+        let code_owners = CodeOwners::parse("a+b.(c) @owner").unwrap();
+        assert_eq!(owners(&code_owners, "a+b.(c)"), ["@owner"]);
+        assert!(owners(&code_owners, "aab.(c)").is_empty());
+    }
+}
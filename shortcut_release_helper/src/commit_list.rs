@@ -0,0 +1,132 @@
+//! This module reads a pre-computed list of unreleased commits, instead of finding them in the
+//! repositories
+//!
+//! Each line of the list is a commit, as `<repository>\t<sha>\t<message>`, eg as produced by
+//! `git log --format="dev%x09%H%x09%s" master..next`. Empty lines are ignored.
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use git2::Oid as GitOid;
+
+use crate::{
+    conventional_commits::ConventionalCommit,
+    types::{CommitAuthor, RepoToCommits, RepositoryName, UnreleasedCommit},
+};
+
+/// Read the commit list from the file at `source`, or from the standard input when it's `-`
+pub fn read_commits(source: &Path) -> Result<RepoToCommits> {
+    let content = if source == Path::new("-") {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        fs::read_to_string(source)
+            .with_context(|| format!("Could not read the commit list {source:?}"))?
+    };
+    parse_commits(&content)
+}
+
+/// Parse the commit list, keeping the commits of each repository in order
+fn parse_commits(content: &str) -> Result<RepoToCommits> {
+    // The dates aren't part of the list, so all commits get the time of the run and keep their
+    // order once sorted
+    let now = Local::now().fixed_offset();
+    let mut commits = RepoToCommits::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let (Some(repository), Some(sha), Some(message)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(anyhow!(
+                "Expected <repository>\\t<sha>\\t<message> on line {}, got {line:?}",
+                index + 1
+            ));
+        };
+        let id = GitOid::from_str(sha)
+            .with_context(|| format!("Invalid commit sha {sha:?} on line {}", index + 1))?;
+        let commit = UnreleasedCommit {
+            id,
+            message: Some(message.to_string()),
+            author: CommitAuthor {
+                name: None,
+                email: None,
+            },
+            co_authors: Vec::new(),
+            authored_at: now,
+            committed_at: now,
+            diff_stats: None,
+            signature: None,
+            conventional: ConventionalCommit::parse(message),
+            pull_request: None,
+            merged_branch: None,
+            owners: Vec::new(),
+            commit_url: None,
+            suggested_stories: Vec::new(),
+        };
+        commits
+            .entry(RepositoryName::new(repository.to_string()))
+            .or_default()
+            .push(commit);
+    }
+    Ok(commits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA_1: &str = "1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d";
+    const SHA_2: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn parse_empty() {
+        assert!(parse_commits("").unwrap().is_empty());
+        assert!(parse_commits("\n  \n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_in_order() {
+        let content = format!(
+            "dev\t{SHA_1}\tfeat(api): add an endpoint\n\nlegacy\t{SHA_2}\tFix\tthe build\n\
+            dev\t{SHA_2}\t\n"
+        );
+        let commits = parse_commits(&content).unwrap();
+        assert_eq!(commits.len(), 2);
+        let dev_commits = &commits[&RepositoryName::new("dev".to_string())];
+        assert_eq!(
+            dev_commits
+                .iter()
+                .map(|commit| commit.id.to_string())
+                .collect::<Vec<_>>(),
+            [SHA_1, SHA_2]
+        );
+        assert_eq!(
+            dev_commits[0]
+                .conventional
+                .as_ref()
+                .map(|conventional| conventional.commit_type.as_str()),
+            Some("feat")
+        );
+        assert_eq!(dev_commits[1].message.as_deref(), Some(""));
+        // Only the first two tabs separate the fields
+        let legacy_commits = &commits[&RepositoryName::new("legacy".to_string())];
+        assert_eq!(legacy_commits[0].message.as_deref(), Some("Fix\tthe build"));
+        assert_eq!(legacy_commits[0].conventional, None);
+    }
+
+    #[test]
+    fn parse_malformed() {
+        let err = parse_commits(&format!("dev\t{SHA_1}\tOk\ndev {SHA_2} Spaces")).unwrap_err();
+        assert!(err.to_string().contains("on line 2"), "{err}");
+        let err = parse_commits("dev\tnot-a-sha\tMessage").unwrap_err();
+        assert!(err.to_string().contains("Invalid commit sha"), "{err}");
+    }
+}
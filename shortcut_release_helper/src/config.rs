@@ -1,26 +1,225 @@
 use std::{
     collections::HashMap,
-    fs::File,
+    fmt,
+    fs::{self, File},
     io::Read,
+    num::{NonZeroU32, NonZeroUsize},
     path::{Path, PathBuf},
 };
 
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{anyhow, Context, Result};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
 
-use crate::types::{RepositoryConfiguration, RepositoryName};
+use crate::types::{
+    ArchivedPolicy, RepositoryAuth, RepositoryConfiguration, RepositoryName, SectionConfiguration,
+    TokenSource, WorkspaceConfiguration, WorkspaceName,
+};
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub struct AppConfig {
     pub template_file: PathBuf,
     pub repositories: HashMap<RepositoryName, RepositoryConfiguration>,
+    /// Regex matching the story id, as its first capture group, in the names of merged branches
+    #[serde(default = "default_branch_story_id_pattern")]
+    pub branch_story_id_pattern: String,
+    /// Keys of the git trailers referencing stories, like `Shortcut-Story: 1234`
+    #[serde(default = "default_story_trailers")]
+    pub story_trailers: Vec<String>,
+    /// Default regexes of the commit authors to exclude, for repositories which don't define
+    /// their own
+    #[serde(default)]
+    pub exclude_authors: Vec<String>,
+    /// Regexes of the messages of commits to exclude
+    #[serde(default)]
+    pub exclude_commit_patterns: Vec<String>,
+    /// Names of the environments commits are promoted through, from the most to the least
+    /// released one, eg `["production", "staging", "next"]`
+    #[serde(default)]
+    pub promotion_pipeline: Vec<String>,
+    /// Maximum number of concurrent requests to the Shortcut API
+    #[serde(default = "default_shortcut_concurrency")]
+    pub shortcut_concurrency: NonZeroUsize,
+    /// Maximum number of attempts of each request to the Shortcut API, retrying connection and
+    /// server errors
+    #[serde(default = "default_shortcut_max_attempts")]
+    pub shortcut_max_attempts: NonZeroU32,
+    /// Maximum number of requests per minute to the Shortcut API
+    #[serde(default = "default_shortcut_requests_per_minute")]
+    pub shortcut_requests_per_minute: NonZeroU32,
+    /// Number of minutes the stories and epics retrieved from Shortcut are cached for
+    #[serde(default = "default_shortcut_cache_ttl_minutes")]
+    pub shortcut_cache_ttl_minutes: u64,
+    /// Base URL of the Shortcut API, eg of a mock server, overridden by the `SHORTCUT_API_URL`
+    /// environment variable
+    pub shortcut_api_url: Option<String>,
+    /// URL of the proxy for the requests to the Shortcut API, used instead of the one of the
+    /// `HTTPS_PROXY` environment variable
+    pub proxy: Option<String>,
+    /// Shortcut workspaces the repositories are assigned to, by name
+    #[serde(default)]
+    pub workspaces: HashMap<WorkspaceName, WorkspaceConfiguration>,
+    /// Names of the workflow state the stories are moved to once released, the first one found
+    /// in the workflow of each story being used, as workflows may name it differently
+    #[serde(default)]
+    pub released_workflow_states: Vec<String>,
+    /// Name of the custom field set to the version of the release on the stories, eg
+    /// `Released in`
+    pub version_custom_field: Option<String>,
+    /// Whether the archived stories are part of the release
+    #[serde(default)]
+    pub archived: ArchivedPolicy,
+    /// Label of the stories which are breaking changes, on top of the ones with breaking commits
+    #[serde(default = "default_breaking_label")]
+    pub breaking_label: String,
+    /// Label of the stories fixing security issues, which get their own section
+    #[serde(default = "default_security_label")]
+    pub security_label: String,
+    /// Sections of the release notes, as the heading of the stories of each label, in order
+    #[serde(default, deserialize_with = "deserialize_sections")]
+    pub sections: Vec<SectionConfiguration>,
+}
+
+/// Deserialize the `label = heading` entries of the sections table, in the order of the file
+fn deserialize_sections<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<SectionConfiguration>, D::Error> {
+    struct SectionsVisitor;
+
+    impl<'de> Visitor<'de> for SectionsVisitor {
+        type Value = Vec<SectionConfiguration>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a table of the section headings by label")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            let mut sections = Vec::new();
+            while let Some((label, heading)) = map.next_entry()? {
+                sections.push(SectionConfiguration { label, heading });
+            }
+            Ok(sections)
+        }
+    }
+
+    deserializer.deserialize_map(SectionsVisitor)
+}
+
+fn default_breaking_label() -> String {
+    "breaking-change".to_string()
+}
+
+fn default_security_label() -> String {
+    "security".to_string()
+}
+
+fn default_shortcut_cache_ttl_minutes() -> u64 {
+    24 * 60
+}
+
+fn default_shortcut_requests_per_minute() -> NonZeroU32 {
+    NonZeroU32::new(200).expect("Should be non-zero")
+}
+
+fn default_shortcut_max_attempts() -> NonZeroU32 {
+    NonZeroU32::new(4).expect("Should be non-zero")
+}
+
+fn default_shortcut_concurrency() -> NonZeroUsize {
+    NonZeroUsize::new(8).expect("Should be non-zero")
+}
+
+fn default_story_trailers() -> Vec<String> {
+    vec!["Shortcut-Story".to_string()]
+}
+
+fn default_branch_story_id_pattern() -> String {
+    r"(?:^|/)(?:sc-|ch)(\d+)".to_string()
 }
 
 impl AppConfig {
+    /// Parse the configuration file, resolving its relative paths against its directory
     pub fn parse(config_file_path: &Path) -> Result<Self> {
-        let mut file = File::open(config_file_path)?;
+        let mut file = File::open(config_file_path)
+            .with_context(|| format!("Could not open the configuration {config_file_path:?}"))?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
-        Ok(toml::from_str(&contents)?)
+        let mut config: Self = toml::from_str(&contents)?;
+        if config.promotion_pipeline.len() == 1 {
+            return Err(anyhow!(
+                "The promotion_pipeline must contain at least two environments"
+            ));
+        }
+        for (repo_name, repo_config) in &config.repositories {
+            let workspace = &repo_config.workspace;
+            if *workspace != WorkspaceName::default() && !config.workspaces.contains_key(workspace)
+            {
+                return Err(anyhow!(
+                    "The workspace {workspace} of {repo_name} isn't configured in workspaces"
+                ));
+            }
+        }
+        for (workspace, workspace_config) in &config.workspaces {
+            if *workspace != WorkspaceName::default()
+                && workspace_config.token_source == TokenSource::Env
+                && workspace_config.token_env.is_none()
+            {
+                return Err(anyhow!("The workspace {workspace} has no token_env"));
+            }
+        }
+        config.apply_repository_defaults();
+        config.resolve_paths(config_file_path.parent().unwrap_or(Path::new("")));
+        Ok(config)
+    }
+
+    /// Configuration of the workspace, the default one reading its token from `SHORTCUT_TOKEN`
+    /// unless configured
+    pub fn workspace(&self, workspace: &WorkspaceName) -> WorkspaceConfiguration {
+        self.workspaces.get(workspace).cloned().unwrap_or_default()
     }
+
+    /// Resolve the relative paths of the template, repositories and SSH keys against the
+    /// directory of the configuration file, so that the tool can run from any directory
+    fn resolve_paths(&mut self, directory: &Path) {
+        self.template_file = directory.join(&self.template_file);
+        for repo_config in self.repositories.values_mut() {
+            repo_config.location = repo_config.location.relative_to(directory);
+            if let Some(RepositoryAuth::SshKey { private_key, .. }) = &mut repo_config.auth {
+                *private_key = directory.join(&*private_key);
+            }
+        }
+    }
+
+    /// Use the top-level settings for repositories which don't override them
+    fn apply_repository_defaults(&mut self) {
+        for repo_config in self.repositories.values_mut() {
+            repo_config
+                .exclude_authors
+                .get_or_insert_with(|| self.exclude_authors.clone());
+        }
+    }
+}
+
+/// Directory where the tool caches data between runs, eg
+/// `~/.cache/shortcut_release_helper` on Linux
+pub fn cache_dir() -> Result<PathBuf> {
+    let cache_dir = dirs::cache_dir().ok_or_else(|| anyhow!("Could not find a cache directory"))?;
+    Ok(cache_dir.join(env!("CARGO_PKG_NAME")))
+}
+
+/// Check that the cache directory can be created and written to, returning it
+pub fn check_cache_dir() -> Result<PathBuf> {
+    let cache_dir = cache_dir()?;
+    fs::create_dir_all(&cache_dir)
+        .with_context(|| format!("Could not create the cache directory {cache_dir:?}"))?;
+    let probe_path = cache_dir.join(".probe");
+    fs::write(&probe_path, "")
+        .and_then(|()| fs::remove_file(&probe_path))
+        .with_context(|| format!("Could not write to the cache directory {cache_dir:?}"))?;
+    Ok(cache_dir)
 }
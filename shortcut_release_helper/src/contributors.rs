@@ -0,0 +1,138 @@
+//! This module builds the list of contributors of a release
+//!
+//! Contributors are the authors of the unreleased commits, as well as their co-authors credited
+//! with `Co-authored-by:` trailers, like when pair programming.
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::types::{CommitAuthor, RepoToCommits};
+
+/// Author or co-author of at least one unreleased commit
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct Contributor {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// Number of commits authored or co-authored
+    pub commit_count: usize,
+}
+
+/// List the authors and co-authors of the commits of all repositories, identified by their email
+/// (or name when missing), from the most to the least commits
+pub fn contributors(commits: &RepoToCommits) -> Vec<Contributor> {
+    let mut contributors: HashMap<String, Contributor> = HashMap::new();
+    for commit in commits.values().flatten() {
+        let mut commit_contributors = HashMap::new();
+        for author in std::iter::once(&commit.author).chain(&commit.co_authors) {
+            if let Some(key) = contributor_key(author) {
+                commit_contributors.entry(key).or_insert(author);
+            }
+        }
+        for (key, author) in commit_contributors {
+            contributors
+                .entry(key)
+                .or_insert_with(|| Contributor {
+                    name: author.name.clone(),
+                    email: author.email.clone(),
+                    commit_count: 0,
+                })
+                .commit_count += 1;
+        }
+    }
+    let mut contributors = contributors.into_values().collect::<Vec<_>>();
+    contributors.sort_by(|contributor, other| {
+        other
+            .commit_count
+            .cmp(&contributor.commit_count)
+            .then_with(|| contributor.name.cmp(&other.name))
+    });
+    contributors
+}
+
+/// Key identifying the author, ie their email in lowercase or their name
+fn contributor_key(author: &CommitAuthor) -> Option<String> {
+    author
+        .email
+        .as_ref()
+        .map(|email| email.to_lowercase())
+        .or_else(|| author.name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{test_commit, RepositoryName, UnreleasedCommit};
+
+    fn author(name: &str, email: Option<&str>) -> CommitAuthor {
+        CommitAuthor {
+            name: Some(name.to_owned()),
+            email: email.map(str::to_owned),
+        }
+    }
+
+    fn commit(author: CommitAuthor, co_authors: Vec<CommitAuthor>) -> UnreleasedCommit {
+        UnreleasedCommit {
+            author,
+            co_authors,
+            ..test_commit(0, 0, None)
+        }
+    }
+
+    fn contributor(name: &str, email: Option<&str>, commit_count: usize) -> Contributor {
+        Contributor {
+            name: Some(name.to_owned()),
+            email: email.map(str::to_owned),
+            commit_count,
+        }
+    }
+
+    #[test]
+    fn no_contributors() {
+        assert!(contributors(&RepoToCommits::new()).is_empty());
+        let anonymous = CommitAuthor {
+            name: None,
+            email: None,
+        };
+        let commits = RepoToCommits::from([(
+            RepositoryName::new("dev".to_owned()),
+            vec![commit(anonymous, Vec::new())],
+        )]);
+        assert!(contributors(&commits).is_empty());
+    }
+
+    #[test]
+    fn count_contributors() {
+        let jane = author("Jane", Some("jane@example.com"));
+        let commits = RepoToCommits::from([
+            (
+                RepositoryName::new("dev".to_owned()),
+                vec![
+                    // Not counted twice when co-authoring their own commit
+                    commit(
+                        jane.clone(),
+                        vec![author("Jane D", Some("Jane@Example.com"))],
+                    ),
+                    commit(author("Bob", None), vec![jane.clone()]),
+                ],
+            ),
+            (
+                RepositoryName::new("legacy".to_owned()),
+                vec![
+                    commit(author("Alice", Some("alice@example.com")), Vec::new()),
+                    commit(author("Bob", None), Vec::new()),
+                    commit(author("Carol", Some("carol@example.com")), Vec::new()),
+                ],
+            ),
+        ]);
+        // From the most to the least commits, then by name
+        assert_eq!(
+            contributors(&commits),
+            [
+                contributor("Bob", None, 2),
+                contributor("Jane", Some("jane@example.com"), 2),
+                contributor("Alice", Some("alice@example.com"), 1),
+                contributor("Carol", Some("carol@example.com"), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! This module parses [Conventional Commits](https://www.conventionalcommits.org/) messages
+//!
+//! Commits following the specification start with a type, like `fix: prevent racing of
+//! requests`, which can be used to group commits which aren't linked to a story.
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::types::{RepoToCommits, UnreleasedCommit};
+
+/// Type used for commits which don't follow the specification
+pub const OTHER_COMMIT_TYPE: &str = "other";
+
+lazy_static! {
+    static ref BREAKING_CHANGE_RE: Regex =
+        Regex::new(r"(?m)^BREAKING[ -]CHANGE: ").expect("Could not compile BREAKING_CHANGE_RE");
+}
+
+/// Information parsed from a Conventional Commits message
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct ConventionalCommit {
+    /// Type of the commit, in lowercase, like `feat` or `fix`
+    #[serde(rename = "type")]
+    pub commit_type: String,
+    pub scope: Option<String>,
+    /// Whether the commit is marked as a breaking change, with a `!` after the type or scope or
+    /// with a `BREAKING CHANGE` footer
+    pub breaking: bool,
+}
+
+impl ConventionalCommit {
+    pub fn parse(message: &str) -> Option<Self> {
+        lazy_static! {
+            static ref SUBJECT_RE: Regex =
+                Regex::new(r"^(?P<type>[a-zA-Z]+)(?:\((?P<scope>[^)]*)\))?(?P<breaking>!)?: \S")
+                    .expect("Could not compile SUBJECT_RE");
+        };
+        let captures = SUBJECT_RE.captures(message)?;
+        Some(Self {
+            commit_type: captures["type"].to_lowercase(),
+            scope: captures
+                .name("scope")
+                .map(|scope| scope.as_str().to_owned()),
+            breaking: captures.name("breaking").is_some() || BREAKING_CHANGE_RE.is_match(message),
+        })
+    }
+}
+
+/// Whether the commit is a breaking change, according to its Conventional Commits message or to
+/// a `BREAKING CHANGE` footer in a message not following the specification
+pub fn is_breaking(commit: &UnreleasedCommit) -> bool {
+    commit
+        .conventional
+        .as_ref()
+        .is_some_and(|conventional| conventional.breaking)
+        || commit
+            .message
+            .as_deref()
+            .is_some_and(|message| BREAKING_CHANGE_RE.is_match(message))
+}
+
+/// Group commits by their Conventional Commits type, commits not following the specification
+/// being grouped under [`OTHER_COMMIT_TYPE`]
+pub fn group_by_type(commits: &RepoToCommits) -> HashMap<String, RepoToCommits> {
+    let mut commits_by_type: HashMap<String, RepoToCommits> = HashMap::new();
+    for (repo_name, commits) in commits {
+        for commit in commits {
+            let commit_type = commit
+                .conventional
+                .as_ref()
+                .map_or(OTHER_COMMIT_TYPE, |conventional| {
+                    conventional.commit_type.as_str()
+                });
+            commits_by_type
+                .entry(commit_type.to_owned())
+                .or_default()
+                .entry(repo_name.clone())
+                .or_default()
+                .push(commit.clone());
+        }
+    }
+    commits_by_type
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conventional(commit_type: &str, scope: Option<&str>, breaking: bool) -> ConventionalCommit {
+        ConventionalCommit {
+            commit_type: commit_type.to_owned(),
+            scope: scope.map(ToOwned::to_owned),
+            breaking,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            ConventionalCommit::parse("fix: prevent racing of requests"),
+            Some(conventional("fix", None, false))
+        );
+        assert_eq!(
+            ConventionalCommit::parse("Feat(api): add an endpoint"),
+            Some(conventional("feat", Some("api"), false))
+        );
+        assert_eq!(
+            ConventionalCommit::parse("refactor(api)!: drop the v1 endpoints"),
+            Some(conventional("refactor", Some("api"), true))
+        );
+        assert_eq!(
+            ConventionalCommit::parse("chore!: drop Node 16"),
+            Some(conventional("chore", None, true))
+        );
+        assert_eq!(
+            ConventionalCommit::parse("feat: new flag\n\nBREAKING CHANGE: the old one is gone"),
+            Some(conventional("feat", None, true))
+        );
+        assert_eq!(
+            ConventionalCommit::parse("feat(): empty scope"),
+            Some(conventional("feat", Some(""), false))
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(ConventionalCommit::parse(""), None);
+        assert_eq!(ConventionalCommit::parse("Fix the build"), None);
+        assert_eq!(ConventionalCommit::parse("fix:no space"), None);
+        assert_eq!(ConventionalCommit::parse("fix: "), None);
+        assert_eq!(ConventionalCommit::parse("fix(api: unclosed scope"), None);
+        assert_eq!(
+            ConventionalCommit::parse("\nfix: not on the first line"),
+            None
+        );
+    }
+
+    #[test]
+    fn breaking_change_footer() {
+        assert!(BREAKING_CHANGE_RE.is_match("Rework\n\nBREAKING-CHANGE: config format"));
+        assert!(!BREAKING_CHANGE_RE.is_match("Rework\n\nNot a BREAKING CHANGE: here"));
+    }
+}
@@ -0,0 +1,191 @@
+//! Backends for the history walks and tree comparisons, which dominate the run time of the
+//! commit search on large repositories
+//!
+//! libgit2 is always available, through the [`git2::Repository`] itself, while the faster
+//! [`GixBackend`] requires the `gix` feature. Both match the paths literally, a path matching
+//! the file at this path or all the files of the directory at this path.
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use git2::{DiffOptions, Oid as GitOid, Repository as GitRepository};
+
+#[cfg(feature = "gix")]
+pub use super::gix_backend::GixBackend;
+
+pub trait HistoryBackend {
+    /// Ids of the commits reachable from `to` but not from `from`, only following the first
+    /// parent of merge commits when `first_parent` is set. Recent commits come first.
+    fn commits_between(&self, from: GitOid, to: GitOid, first_parent: bool) -> Result<Vec<GitOid>>;
+
+    /// Whether the commit modifies any file under the paths, compared to its first parent (or
+    /// the empty tree for a root commit)
+    fn touches_paths(&self, commit_id: GitOid, paths: &[PathBuf]) -> Result<bool>;
+}
+
+/// Open a backend faster than libgit2 for the repository at `git_dir`, if one is available and
+/// can open it
+#[cfg_attr(not(feature = "gix"), allow(unused_variables))]
+pub fn open(git_dir: &Path) -> Option<Box<dyn HistoryBackend>> {
+    #[cfg(feature = "gix")]
+    match GixBackend::open(git_dir) {
+        Ok(backend) => return Some(Box::new(backend)),
+        Err(err) => tracing::warn!("Falling back to libgit2, could not open with gix: {err}"),
+    }
+    None
+}
+
+impl HistoryBackend for GitRepository {
+    fn commits_between(&self, from: GitOid, to: GitOid, first_parent: bool) -> Result<Vec<GitOid>> {
+        let mut rev_walk = self.revwalk()?;
+        rev_walk.push(to)?;
+        rev_walk.hide(from)?;
+        if first_parent {
+            rev_walk.simplify_first_parent()?;
+        }
+        Ok(rev_walk.collect::<Result<_, _>>()?)
+    }
+
+    fn touches_paths(&self, commit_id: GitOid, paths: &[PathBuf]) -> Result<bool> {
+        let commit = self.find_commit(commit_id)?;
+        let tree = commit.tree()?;
+        let parent_tree = commit
+            .parents()
+            .next()
+            .map(|parent| parent.tree())
+            .transpose()?;
+        let mut diff_options = DiffOptions::new();
+        // Literal paths rather than patterns, like the gix backend
+        diff_options.disable_pathspec_match(true);
+        for path in paths {
+            diff_options.pathspec(path);
+        }
+        let diff =
+            self.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_options))?;
+        Ok(diff.deltas().len() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path};
+
+    use git2::{Oid, Repository, Signature};
+
+    use super::*;
+
+    /// Repository whose commits add `a/b.txt`, then `c.txt`, then modify `a/d.txt`, returning
+    /// the ids of the commits from the oldest
+    fn create_repository(path: &Path) -> (Repository, Vec<Oid>) {
+        let _ = fs::remove_dir_all(path);
+        let repository = Repository::init_bare(path).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let mut commit_ids = Vec::new();
+        let files: [&[(&str, &str)]; 3] = [
+            &[("a/b.txt", "b")],
+            &[("a/b.txt", "b"), ("c.txt", "c")],
+            &[("a/b.txt", "b"), ("a/d.txt", "d"), ("c.txt", "c")],
+        ];
+        for files in files {
+            let mut index = git2::Index::new().unwrap();
+            for (path, content) in files {
+                let blob_id = repository.blob(content.as_bytes()).unwrap();
+                let entry = git2::IndexEntry {
+                    ctime: git2::IndexTime::new(0, 0),
+                    mtime: git2::IndexTime::new(0, 0),
+                    dev: 0,
+                    ino: 0,
+                    mode: 0o100644,
+                    uid: 0,
+                    gid: 0,
+                    file_size: content.len() as u32,
+                    id: blob_id,
+                    flags: path.len() as u16,
+                    flags_extended: 0,
+                    path: path.as_bytes().to_vec(),
+                };
+                index.add(&entry).unwrap();
+            }
+            let tree_id = index.write_tree_to(&repository).unwrap();
+            let tree = repository.find_tree(tree_id).unwrap();
+            let parents = commit_ids
+                .last()
+                .map(|id| repository.find_commit(*id).unwrap())
+                .into_iter()
+                .collect::<Vec<_>>();
+            let commit_id = repository
+                .commit(
+                    None,
+                    &signature,
+                    &signature,
+                    "Commit",
+                    &tree,
+                    &parents.iter().collect::<Vec<_>>(),
+                )
+                .unwrap();
+            commit_ids.push(commit_id);
+        }
+        (repository, commit_ids)
+    }
+
+    /// Which of the commits touch the paths, from the oldest
+    fn touched(backend: &dyn HistoryBackend, commit_ids: &[Oid], paths: &[&str]) -> Vec<bool> {
+        let paths = paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+        commit_ids
+            .iter()
+            .map(|id| backend.touches_paths(*id, &paths).unwrap())
+            .collect()
+    }
+
+    fn check_backend(backend: &dyn HistoryBackend, commit_ids: &[Oid]) {
+        assert_eq!(
+            backend
+                .commits_between(commit_ids[0], commit_ids[2], false)
+                .unwrap(),
+            vec![commit_ids[2], commit_ids[1]]
+        );
+        assert!(backend
+            .commits_between(commit_ids[2], commit_ids[2], false)
+            .unwrap()
+            .is_empty());
+        assert_eq!(touched(backend, commit_ids, &["a"]), [true, false, true]);
+        assert_eq!(
+            touched(backend, commit_ids, &["a/b.txt"]),
+            [true, false, false]
+        );
+        assert_eq!(
+            touched(backend, commit_ids, &["c.txt"]),
+            [false, true, false]
+        );
+        assert_eq!(
+            touched(backend, commit_ids, &["c.txt", "a/d.txt"]),
+            [false, true, true]
+        );
+        // Paths are literal: neither a prefix of a file name nor a pattern match
+        assert_eq!(touched(backend, commit_ids, &["c"]), [false, false, false]);
+        assert_eq!(
+            touched(backend, commit_ids, &["a/*"]),
+            [false, false, false]
+        );
+        assert_eq!(
+            touched(backend, commit_ids, &["missing"]),
+            [false, false, false]
+        );
+    }
+
+    #[test]
+    fn git2_backend() {
+        let path = env::temp_dir().join(format!("history_backend_git2_{}", std::process::id()));
+        let (repository, commit_ids) = create_repository(&path);
+        check_backend(&repository, &commit_ids);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[cfg(feature = "gix")]
+    #[test]
+    fn gix_backend() {
+        let path = env::temp_dir().join(format!("history_backend_gix_{}", std::process::id()));
+        let (_, commit_ids) = create_repository(&path);
+        check_backend(&GixBackend::open(&path).unwrap(), &commit_ids);
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
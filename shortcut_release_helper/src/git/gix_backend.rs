@@ -0,0 +1,71 @@
+//! History backend based on [gitoxide](https://github.com/GitoxideLabs/gitoxide)
+//!
+//! It walks the history with the commit-graph when available, and compares trees path by path
+//! instead of diffing them, which is substantially faster than libgit2 on large repositories.
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use git2::Oid as GitOid;
+use gix::{
+    revision::walk::Sorting, traverse::commit::simple::CommitTimeOrder, ObjectId,
+    Repository as GixRepository,
+};
+
+use super::backend::HistoryBackend;
+
+pub struct GixBackend {
+    repository: GixRepository,
+}
+
+impl GixBackend {
+    pub fn open(git_dir: &Path) -> Result<Self> {
+        Ok(Self {
+            repository: gix::open(git_dir)?,
+        })
+    }
+}
+
+impl HistoryBackend for GixBackend {
+    fn commits_between(&self, from: GitOid, to: GitOid, first_parent: bool) -> Result<Vec<GitOid>> {
+        let mut walk = self
+            .repository
+            .rev_walk([to_object_id(to)])
+            .with_hidden([to_object_id(from)])
+            .use_commit_graph(true)
+            .sorting(Sorting::ByCommitTime(CommitTimeOrder::NewestFirst));
+        if first_parent {
+            walk = walk.first_parent_only();
+        }
+        walk.all()?.map(|info| Ok(to_oid(info?.id)?)).collect()
+    }
+
+    fn touches_paths(&self, commit_id: GitOid, paths: &[PathBuf]) -> Result<bool> {
+        let commit = self.repository.find_commit(to_object_id(commit_id))?;
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parent_ids().next() {
+            Some(parent_id) => parent_id.object()?.try_into_commit()?.tree()?,
+            None => self.repository.empty_tree(),
+        };
+        // A file or directory is modified iff the id of its entry changed, which avoids diffing
+        // the whole trees
+        for path in paths {
+            let entry_id = |tree: &gix::Tree| -> Result<Option<ObjectId>> {
+                Ok(tree
+                    .lookup_entry_by_path(path)?
+                    .map(|entry| entry.object_id()))
+            };
+            if entry_id(&parent_tree)? != entry_id(&tree)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn to_object_id(oid: GitOid) -> ObjectId {
+    ObjectId::from_bytes_or_panic(oid.as_bytes())
+}
+
+fn to_oid(object_id: ObjectId) -> Result<GitOid, git2::Error> {
+    GitOid::from_bytes(object_id.as_bytes())
+}
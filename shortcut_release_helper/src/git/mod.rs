@@ -0,0 +1,1203 @@
+//! This module groups git-related operation
+//!
+//! The `Repository` structures wraps a [`git2::Repository`], walking the history with a
+//! [`HistoryBackend`].
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset};
+use git2::{
+    AutotagOption, Commit as GitCommit, Config as GitConfig, Cred, CredentialType, Delta, Diff,
+    Error as GitError, ErrorCode as GitErrorCode, FetchOptions, FetchPrune, FileMode, Mailmap,
+    Oid as GitOid, RemoteCallbacks, Repository as GitRepository, Signature, Time as GitTime,
+};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+use semver::Version;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use self::backend::HistoryBackend;
+use crate::codeowners::{CodeOwners, CODEOWNERS_PATHS};
+use crate::config::cache_dir;
+use crate::conventional_commits::ConventionalCommit;
+use crate::progress::Progress;
+use crate::release_ignore::{ReleaseIgnore, RELEASE_IGNORE_PATH};
+use crate::shortcut::StoryId;
+use crate::types::{
+    CommitAuthor, DiffStats, HeadCommit, MergeBase, NextBranches, RepositoryAuth,
+    RepositoryConfiguration, RepositoryName, RepositoryReference, RepositoryRemote,
+    SignatureStatus, UnreleasedCommit,
+};
+
+mod backend;
+#[cfg(feature = "gix")]
+mod gix_backend;
+
+/// Depth of a fetch converting a shallow repository to a complete one
+const UNSHALLOW_DEPTH: i32 = i32::MAX;
+
+pub struct Repository<'a> {
+    repository: GitRepository,
+    /// Backend faster than libgit2 walking the history, when available
+    history: Option<Box<dyn HistoryBackend>>,
+    release_branch: &'a RepositoryReference,
+    next_branches: &'a NextBranches,
+    remote: &'a RepositoryRemote,
+    paths: &'a [PathBuf],
+    detect_cherry_picks: bool,
+    first_parent: bool,
+    exclude_authors: Vec<Regex>,
+    /// Canonical names and emails of authors, from the `.mailmap` of the repository
+    mailmap: Mailmap,
+    commit_url_template: Option<&'a str>,
+    auth: Option<&'a RepositoryAuth>,
+    options: GitOptions,
+    /// Whether branches should be resolved against the remote-tracking branches first
+    use_remote_branches: bool,
+}
+
+/// Options of the commit search, set from the command line
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GitOptions {
+    /// Fetch all repositories before looking for commits
+    pub fetch: bool,
+    /// Compute the diff statistics of every unreleased commit
+    pub with_diffstat: bool,
+    /// Verify the signature of every unreleased commit
+    pub verify_signatures: bool,
+    /// Search local repositories in a mirror in the cache directory instead of the working copy
+    pub isolated: bool,
+}
+
+/// Submodule whose pinned commit changed between the release and next branches
+pub struct ChangedSubmodule {
+    /// Path of the submodule, relative to the repository root
+    pub path: PathBuf,
+    /// Path of the checked out submodule
+    pub location: PathBuf,
+    /// Commit pinned in the release branch
+    pub release_commit: GitOid,
+    /// Commit pinned in the next branch
+    pub next_commit: GitOid,
+}
+
+/// Files of the primary next branch configuring the search
+struct BranchFiles {
+    release_ignore: ReleaseIgnore,
+    code_owners: CodeOwners,
+}
+
+pub struct UnreleasedCommits {
+    /// Heads of the next branches, the primary one first
+    pub next_heads: Vec<HeadCommit>,
+    /// Merge base of the release branch and the primary next branch
+    pub merge_base: MergeBase,
+    /// Commits of any next branch, without duplicates
+    pub unreleased_commits: Vec<UnreleasedCommit>,
+    /// Ids of the stories excluded by the `.releaseignore` of the primary next branch
+    pub ignored_story_ids: Vec<StoryId>,
+    /// Commits present in the release branch but not the primary next branch, like hotfixes
+    /// which were never merged back
+    pub missing_in_next: Vec<UnreleasedCommit>,
+    /// Commits of each step of the promotion pipeline, empty without one
+    pub promoted_commits: Vec<Vec<UnreleasedCommit>>,
+}
+
+impl<'a> Repository<'a> {
+    pub fn new(configuration: &'a RepositoryConfiguration, options: GitOptions) -> Result<Self> {
+        let repository = match configuration.location.remote_url() {
+            Some(url) => {
+                Self::clone_or_update(url, &configuration.remote, configuration.auth.as_ref())?
+            }
+            None if options.isolated || configuration.isolated => {
+                Self::mirror_local(configuration.location.as_ref(), &configuration.remote)?
+            }
+            None => GitRepository::open(configuration.location.as_ref())?,
+        };
+        if !has_commit_graph(&repository) {
+            debug!(
+                "No commit-graph found, running `git commit-graph write --reachable` in the \
+                repository would speed up the search"
+            );
+        }
+        let exclude_authors = configuration
+            .exclude_authors
+            .iter()
+            .flatten()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mailmap = repository.mailmap()?;
+        let history = backend::open(repository.path());
+        Ok(Self {
+            repository,
+            history,
+            release_branch: &configuration.release_branch,
+            next_branches: &configuration.next_branch,
+            remote: &configuration.remote,
+            paths: &configuration.paths,
+            detect_cherry_picks: configuration.detect_cherry_picks,
+            first_parent: configuration.first_parent,
+            exclude_authors,
+            mailmap,
+            commit_url_template: configuration.commit_url_template.as_deref(),
+            auth: configuration.auth.as_ref(),
+            options,
+            use_remote_branches: false,
+        })
+    }
+
+    /// Mirror the remote repository at `url` into a bare repository in the cache directory,
+    /// cloning it on the first run and updating it on the following ones. All branches of the
+    /// remote are mirrored as local branches, so they can be resolved by their short name.
+    fn clone_or_update(
+        url: &str,
+        remote_name: &RepositoryRemote,
+        auth: Option<&RepositoryAuth>,
+    ) -> Result<GitRepository> {
+        let path = cache_dir()?
+            .join("repositories")
+            .join(cache_directory_name(url));
+        let repository = if path.exists() {
+            debug!("Updating {url} in {path:?}");
+            GitRepository::open_bare(&path)?
+        } else {
+            info!("Cloning {url} in {path:?}");
+            let repository = GitRepository::init_bare(&path)?;
+            repository.remote(remote_name.as_ref(), url)?;
+            repository
+        };
+        repository.find_remote(remote_name.as_ref())?.fetch(
+            &["+refs/heads/*:refs/heads/*"],
+            Some(&mut fetch_options(&repository, auth)?),
+            None,
+        )?;
+        write_commit_graph(&repository);
+        Ok(repository)
+    }
+
+    /// Mirror the local repository at `path` into a bare repository in the cache directory, so
+    /// that the search doesn't race with the git operations made in the working copy. Branches,
+    /// remote-tracking branches and tags are mirrored, and the remote keeps its URL from the
+    /// working copy so that it can still be fetched from.
+    fn mirror_local(path: &Path, remote_name: &RepositoryRemote) -> Result<GitRepository> {
+        let path = path.canonicalize()?;
+        let location = path
+            .to_str()
+            .ok_or_else(|| anyhow!("Invalid repository path {path:?}"))?;
+        let mirror_path = cache_dir()?
+            .join("mirrors")
+            .join(cache_directory_name(location));
+        let mirror = if mirror_path.exists() {
+            debug!("Updating the mirror of {path:?} in {mirror_path:?}");
+            GitRepository::open_bare(&mirror_path)?
+        } else {
+            info!("Mirroring {path:?} in {mirror_path:?}");
+            GitRepository::init_bare(&mirror_path)?
+        };
+        let checkout = GitRepository::open(&path)?;
+        if let Some(url) = checkout
+            .find_remote(remote_name.as_ref())
+            .ok()
+            .and_then(|remote| remote.url().map(ToOwned::to_owned))
+        {
+            match mirror.find_remote(remote_name.as_ref()) {
+                Ok(_) => mirror.remote_set_url(remote_name.as_ref(), &url)?,
+                Err(_) => {
+                    mirror.remote(remote_name.as_ref(), &url)?;
+                }
+            }
+        }
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.prune(FetchPrune::On);
+        mirror.remote_anonymous(location)?.fetch(
+            &[
+                "+refs/heads/*:refs/heads/*",
+                "+refs/remotes/*:refs/remotes/*",
+                "+refs/tags/*:refs/tags/*",
+            ],
+            Some(&mut fetch_options),
+            None,
+        )?;
+        write_commit_graph(&mirror);
+        Ok(mirror)
+    }
+
+    /// Whether the repository is a shallow clone, like most CI checkouts. Part of its history is
+    /// then missing, so commits can't be found reliably.
+    pub fn is_shallow(&self) -> bool {
+        self.repository.is_shallow()
+    }
+
+    /// Fetch the complete history of a shallow repository from the remote, like
+    /// `git fetch --unshallow`
+    pub fn unshallow(&self) -> Result<()> {
+        info!(remote = %self.remote, "Fetching the complete history of the shallow repository");
+        let mut remote = self.repository.find_remote(self.remote.as_ref())?;
+        let mut fetch_options = fetch_options(&self.repository, self.auth)?;
+        fetch_options.depth(UNSHALLOW_DEPTH);
+        remote
+            .fetch::<&str>(&[], Some(&mut fetch_options), None)
+            .map_err(|err| {
+                anyhow!(
+                    "The repository is a shallow clone and its complete history could not be \
+                    fetched from {remote}: {err}. Run `git fetch --unshallow`, or clone the \
+                    repository with its complete history (eg `fetch-depth: 0` for the GitHub \
+                    checkout action)",
+                    remote = self.remote
+                )
+            })?;
+        Ok(())
+    }
+
+    /// Fetch the release and next branches from the remote. Once fetched, branches are resolved
+    /// against their remote-tracking branch (eg `origin/master`) when it exists.
+    pub fn fetch(&mut self) -> Result<()> {
+        let mut remote = self.repository.find_remote(self.remote.as_ref())?;
+        // Commits can't be fetched by id, in which case the remote's default refspecs are used.
+        // Tags are always fetched.
+        let refspecs = std::iter::once(self.release_branch)
+            .chain(self.next_branches.iter())
+            .filter(|reference| reference.latest_tag_pattern().is_none())
+            .map(|reference| reference.branch_name())
+            .filter(|branch| !is_full_sha(branch))
+            .map(|branch| format!("+refs/heads/{branch}:refs/remotes/{}/{branch}", self.remote))
+            .collect::<Vec<_>>();
+        info!(remote = %self.remote, "Fetching {refspecs:?}");
+        let mut fetch_options = fetch_options(&self.repository, self.auth)?;
+        remote.fetch(&refspecs, Some(&mut fetch_options), None)?;
+        self.use_remote_branches = true;
+        Ok(())
+    }
+
+    /// Create an annotated tag named `name` at the `target` commit. Fails if the tag already
+    /// exists.
+    pub fn tag(&self, name: &str, target: GitOid, message: &str) -> Result<()> {
+        let target = self.repository.find_object(target, None)?;
+        let tagger = self.repository.signature()?;
+        let tag_id = self
+            .repository
+            .tag(name, &target, &tagger, message, false)?;
+        info!(%tag_id, "Created tag {name} at {target}", target = target.id());
+        Ok(())
+    }
+
+    /// Resolve the release branch and every next branch, returning the id of the commit of each
+    /// reference, the release branch first
+    pub fn resolve_references(&'a self) -> Result<Vec<(&'a RepositoryReference, GitOid)>> {
+        std::iter::once(self.release_branch)
+            .chain(self.next_branches.iter())
+            .map(|reference| Ok((reference, self.find_commit(reference)?.id())))
+            .collect()
+    }
+
+    /// Return the list of commits present in any of the next branches but not the release
+    /// branch, as well as the head commits of the next branches and the merge base of the
+    /// release branch and the primary next branch
+    pub fn find_unreleased_commits_and_head(
+        &'a self,
+        repo_name: &RepositoryName,
+    ) -> Result<UnreleasedCommits> {
+        let release_head = self.find_commit(self.release_branch)?;
+        let branch_files = self.branch_files(&self.find_commit(self.next_branches.primary())?)?;
+        let mut next_heads = Vec::new();
+        let mut seen_commit_ids = HashSet::new();
+        let mut unreleased_commits = Vec::new();
+        let mut primary = None;
+        for next_branch in self.next_branches.iter() {
+            let next_head = self.find_commit(next_branch)?;
+            debug!(%next_branch, "Next commit {:?}", next_head.id());
+            debug!("Finding merge base");
+            let merge_base = self
+                .repository
+                .merge_base(release_head.id(), next_head.id())?;
+            debug!("Merge base {commit:?}", commit = merge_base);
+            let released_patch_ids = if self.detect_cherry_picks {
+                self.patch_ids(merge_base, release_head.id())?
+            } else {
+                HashSet::new()
+            };
+            // The same commit may be part of several next branches, like a feature branch based
+            // on the next branch
+            for commit in self.find_branch_commits(
+                merge_base,
+                next_head.id(),
+                &released_patch_ids,
+                &branch_files,
+                format!("{repo_name} {next_branch}"),
+            )? {
+                if seen_commit_ids.insert(commit.id) {
+                    unreleased_commits.push(commit);
+                }
+            }
+            next_heads.push(HeadCommit {
+                id: next_head.id(),
+                message: next_head.message().map(|msg| msg.to_owned()),
+                branch: next_branch.to_string(),
+            });
+            primary.get_or_insert((merge_base, next_head));
+        }
+        let (merge_base, next_head) =
+            primary.ok_or_else(|| anyhow!("No next branch configured"))?;
+        let missing_in_next =
+            self.find_missing_in_next(merge_base, &release_head, &next_head, &branch_files)?;
+        Ok(UnreleasedCommits {
+            next_heads,
+            merge_base: MergeBase::new(merge_base),
+            unreleased_commits,
+            ignored_story_ids: branch_files.release_ignore.story_ids,
+            missing_in_next,
+            promoted_commits: Vec::new(),
+        })
+    }
+
+    /// Return the list of commits present in the `unreleased` reference but not the `released`
+    /// one, like the commits promoted from an environment to the next one
+    pub fn find_promoted_commits(
+        &self,
+        repo_name: &RepositoryName,
+        released: &RepositoryReference,
+        unreleased: &RepositoryReference,
+    ) -> Result<Vec<UnreleasedCommit>> {
+        let released_head = self.find_commit(released)?;
+        let unreleased_head = self.find_commit(unreleased)?;
+        let branch_files = self.branch_files(&unreleased_head)?;
+        let merge_base = self
+            .repository
+            .merge_base(released_head.id(), unreleased_head.id())?;
+        let released_patch_ids = if self.detect_cherry_picks {
+            self.patch_ids(merge_base, released_head.id())?
+        } else {
+            HashSet::new()
+        };
+        self.find_branch_commits(
+            merge_base,
+            unreleased_head.id(),
+            &released_patch_ids,
+            &branch_files,
+            format!("{repo_name} {unreleased}"),
+        )
+    }
+
+    /// Commit pinned for the submodule at `path` in the tree of the reference, if any
+    pub fn submodule_commit(
+        &self,
+        reference: &RepositoryReference,
+        path: &Path,
+    ) -> Result<Option<GitOid>> {
+        let entry = match self.find_commit(reference)?.tree()?.get_path(path) {
+            Ok(entry) => entry,
+            Err(err) if err.code() == GitErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok((entry.filemode() == i32::from(FileMode::Commit)).then(|| entry.id()))
+    }
+
+    /// Read the `.releaseignore` and `CODEOWNERS` files from the tree of the commit
+    fn branch_files(&self, commit: &GitCommit) -> Result<BranchFiles> {
+        let release_ignore = self
+            .read_file(commit, RELEASE_IGNORE_PATH)?
+            .map(|content| ReleaseIgnore::parse(&content))
+            .transpose()?
+            .unwrap_or_default();
+        let mut code_owners = CodeOwners::default();
+        for path in CODEOWNERS_PATHS {
+            if let Some(content) = self.read_file(commit, path)? {
+                code_owners = CodeOwners::parse(&content)?;
+                break;
+            }
+        }
+        Ok(BranchFiles {
+            release_ignore,
+            code_owners,
+        })
+    }
+
+    /// Content of the file at `path` in the tree of the commit, if it exists
+    fn read_file(&self, commit: &GitCommit, path: &str) -> Result<Option<String>> {
+        let entry = match commit.tree()?.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(err) if err.code() == GitErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let blob = entry.to_object(&self.repository)?.peel_to_blob()?;
+        let content = String::from_utf8(blob.content().to_vec())
+            .map_err(|err| anyhow!("Invalid {path}: {err}"))?;
+        debug!("Found {path}");
+        Ok(Some(content))
+    }
+
+    /// Return the list of commits reachable from `next_head` but not from `merge_base`, skipping
+    /// the ones cherry-picked in the release branch
+    fn find_branch_commits(
+        &self,
+        merge_base: GitOid,
+        next_head: GitOid,
+        released_patch_ids: &HashSet<GitOid>,
+        branch_files: &BranchFiles,
+        progress_label: String,
+    ) -> Result<Vec<UnreleasedCommit>> {
+        let merged_branches = self.merged_branches(merge_base, next_head)?;
+        let commit_ids =
+            self.history()
+                .commits_between(merge_base, next_head, self.first_parent)?;
+        let progress = Progress::new(progress_label, commit_ids.len());
+        commit_ids
+            .into_iter()
+            .inspect(|commit_id| {
+                debug!(ancestor_id = ?commit_id);
+                progress.inc();
+            })
+            .map(|commit_id| self.repository.find_commit(commit_id))
+            // When following first parents, merge commits are the ones describing the changes
+            .filter_ok(|commit| self.first_parent || commit.parent_count() < 2)
+            .map(|commit| {
+                let commit = commit?;
+                if !self.touches_paths(&commit)? {
+                    return Ok(None);
+                }
+                if branch_files
+                    .release_ignore
+                    .is_ignored(commit.id(), commit.message())
+                {
+                    debug!(commit_id = ?commit.id(), "Skipping commit ignored by {RELEASE_IGNORE_PATH}");
+                    return Ok(None);
+                }
+                if self.is_cherry_picked(&commit, released_patch_ids)? {
+                    debug!(commit_id = ?commit.id(), "Skipping cherry-picked commit");
+                    return Ok(None);
+                }
+                let author = self.author(&commit)?;
+                if self.is_excluded_author(&author) {
+                    debug!(commit_id = ?commit.id(), %author, "Skipping commit by excluded author");
+                    return Ok(None);
+                }
+                let merged_branch = merged_branches.get(&commit.id()).cloned();
+                self.unreleased_commit(&commit, merged_branch, &branch_files.code_owners)
+                    .map(Some)
+            })
+            .filter_map(Result::transpose)
+            .collect()
+    }
+
+    /// Return the list of non-merge commits present in the release branch but not the next
+    /// branch. Commits cherry-picked to the next branch are not reported when cherry-pick
+    /// detection is enabled.
+    fn find_missing_in_next(
+        &self,
+        merge_base: GitOid,
+        release_head: &GitCommit,
+        next_head: &GitCommit,
+        branch_files: &BranchFiles,
+    ) -> Result<Vec<UnreleasedCommit>> {
+        let next_patch_ids = if self.detect_cherry_picks {
+            self.patch_ids(merge_base, next_head.id())?
+        } else {
+            HashSet::new()
+        };
+        let mut missing_in_next = Vec::new();
+        for commit_id in self
+            .history()
+            .commits_between(merge_base, release_head.id(), false)?
+        {
+            let commit = self.repository.find_commit(commit_id)?;
+            if commit.parent_count() > 1
+                || !self.touches_paths(&commit)?
+                || branch_files
+                    .release_ignore
+                    .is_ignored(commit.id(), commit.message())
+                || self.is_cherry_picked(&commit, &next_patch_ids)?
+                || self.is_excluded_author(&self.author(&commit)?)
+            {
+                continue;
+            }
+            debug!(commit_id = ?commit.id(), "Commit missing in next branch");
+            missing_in_next.push(self.unreleased_commit(
+                &commit,
+                None,
+                &branch_files.code_owners,
+            )?);
+        }
+        missing_in_next.sort_by_key(|commit| commit.committed_at);
+        Ok(missing_in_next)
+    }
+
+    /// Build the commit exposed to the template, with the name of the branch it was merged from
+    fn unreleased_commit(
+        &self,
+        commit: &GitCommit,
+        merged_branch: Option<String>,
+        code_owners: &CodeOwners,
+    ) -> Result<UnreleasedCommit> {
+        let author = self.author(commit)?;
+        Ok(UnreleasedCommit {
+            id: commit.id(),
+            message: commit.message().map(|msg| msg.to_owned()),
+            author: commit_author(&author),
+            co_authors: commit
+                .message()
+                .map(co_authors)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|co_author| self.canonical_co_author(co_author))
+                .collect(),
+            authored_at: to_datetime(author.when()),
+            committed_at: to_datetime(commit.time()),
+            diff_stats: self
+                .options
+                .with_diffstat
+                .then(|| self.diff_stats(commit))
+                .transpose()?,
+            signature: self
+                .options
+                .verify_signatures
+                .then(|| self.signature_status(commit))
+                .transpose()?,
+            conventional: commit.message().and_then(ConventionalCommit::parse),
+            pull_request: None,
+            merged_branch,
+            owners: self.owners(commit, code_owners)?,
+            suggested_stories: Vec::new(),
+            commit_url: self
+                .commit_url_template
+                .map(|template| template.replace("{sha}", &commit.id().to_string())),
+        })
+    }
+
+    /// Return the submodules whose pinned commit changed between the release and next branches.
+    /// Submodules which aren't checked out are skipped, as their history isn't available.
+    pub fn changed_submodules(&'a self) -> Result<Vec<ChangedSubmodule>> {
+        let Some(workdir) = self.repository.workdir() else {
+            warn!("Skipping submodules of bare repository");
+            return Ok(Vec::new());
+        };
+        let release_tree = self.find_commit(self.release_branch)?.tree()?;
+        let next_tree = self.find_commit(self.next_branches.primary())?.tree()?;
+        let diff =
+            self.repository
+                .diff_tree_to_tree(Some(&release_tree), Some(&next_tree), None)?;
+        let mut changed_submodules = Vec::new();
+        for delta in diff.deltas() {
+            let (old_file, new_file) = (delta.old_file(), delta.new_file());
+            if delta.status() != Delta::Modified
+                || old_file.mode() != FileMode::Commit
+                || new_file.mode() != FileMode::Commit
+            {
+                continue;
+            }
+            let Some(path) = new_file.path() else {
+                continue;
+            };
+            let location = workdir.join(path);
+            if !location.join(".git").exists() {
+                warn!(?path, "Skipping submodule which isn't checked out");
+                continue;
+            }
+            debug!(?path, from = ?old_file.id(), to = ?new_file.id(), "Submodule changed");
+            changed_submodules.push(ChangedSubmodule {
+                path: path.to_owned(),
+                location,
+                release_commit: old_file.id(),
+                next_commit: new_file.id(),
+            });
+        }
+        Ok(changed_submodules)
+    }
+
+    /// Map each commit brought by a merge commit reachable from `to` but not from `from` to the
+    /// name of the merged branch, as found in the merge commit message. When following first
+    /// parents, merge commits are mapped to the branch they merged instead.
+    fn merged_branches(&self, from: GitOid, to: GitOid) -> Result<HashMap<GitOid, String>> {
+        let mut merged_branches = HashMap::new();
+        for commit_id in self.history().commits_between(from, to, false)? {
+            let commit = self.repository.find_commit(commit_id)?;
+            if commit.parent_count() < 2 {
+                continue;
+            }
+            let Some(branch) = commit.message().and_then(merged_branch_name) else {
+                continue;
+            };
+            if self.first_parent {
+                merged_branches.insert(commit.id(), branch);
+                continue;
+            }
+            let branch_commit_ids = self.history().commits_between(
+                commit.parent_id(0)?,
+                commit.parent_id(1)?,
+                false,
+            )?;
+            // Commits are walked from the most recent merge, which takes precedence over merges
+            // inside the merged branch
+            for branch_commit_id in branch_commit_ids {
+                merged_branches
+                    .entry(branch_commit_id)
+                    .or_insert_with(|| branch.clone());
+            }
+        }
+        Ok(merged_branches)
+    }
+
+    /// Backend walking the history, libgit2 itself unless a faster one is available
+    fn history(&self) -> &dyn HistoryBackend {
+        self.history.as_deref().unwrap_or(&self.repository)
+    }
+
+    /// Whether the commit modifies any file under the configured paths, compared to its first
+    /// parent. Always true when no path is configured.
+    fn touches_paths(&self, commit: &GitCommit) -> Result<bool> {
+        if self.paths.is_empty() {
+            return Ok(true);
+        }
+        self.history().touches_paths(commit.id(), self.paths)
+    }
+
+    /// Whether the change introduced by the commit is already present in the release branch,
+    /// given the patch ids of the released commits
+    fn is_cherry_picked(
+        &self,
+        commit: &GitCommit,
+        released_patch_ids: &HashSet<GitOid>,
+    ) -> Result<bool, GitError> {
+        if released_patch_ids.is_empty() {
+            return Ok(false);
+        }
+        let patch_id = self.diff_to_parent(commit)?.patchid(None)?;
+        Ok(released_patch_ids.contains(&patch_id))
+    }
+
+    /// Author of the commit, with the canonical name and email from the mailmap
+    fn author(&self, commit: &GitCommit) -> Result<Signature<'static>, GitError> {
+        commit.author_with_mailmap(&self.mailmap)
+    }
+
+    /// Co-author with the canonical name and email from the mailmap, when both are known
+    fn canonical_co_author(&self, co_author: CommitAuthor) -> CommitAuthor {
+        let (Some(name), Some(email)) = (&co_author.name, &co_author.email) else {
+            return co_author;
+        };
+        Signature::now(name, email)
+            .and_then(|signature| self.mailmap.resolve_signature(&signature))
+            .map_or(co_author, |signature| commit_author(&signature))
+    }
+
+    /// Whether the name or email of the author matches one of the excluded authors regexes
+    fn is_excluded_author(&self, author: &Signature) -> bool {
+        self.exclude_authors.iter().any(|exclude_author| {
+            [author.name(), author.email()]
+                .into_iter()
+                .flatten()
+                .any(|name_or_email| exclude_author.is_match(name_or_email))
+        })
+    }
+
+    /// Owners of the files touched by the commit, from the most to the least files owned
+    fn owners(&self, commit: &GitCommit, code_owners: &CodeOwners) -> Result<Vec<String>> {
+        if code_owners.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut owned_files: HashMap<&str, usize> = HashMap::new();
+        for delta in self.diff_to_parent(commit)?.deltas() {
+            let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) else {
+                continue;
+            };
+            for owner in code_owners.owners(path) {
+                *owned_files.entry(owner).or_default() += 1;
+            }
+        }
+        Ok(owned_files
+            .into_iter()
+            .sorted_by(|(owner, count), (other_owner, other_count)| {
+                other_count.cmp(count).then_with(|| owner.cmp(other_owner))
+            })
+            .map(|(owner, _)| owner.to_owned())
+            .collect())
+    }
+
+    /// Number of files changed, and lines inserted and deleted by the commit
+    fn diff_stats(&self, commit: &GitCommit) -> Result<DiffStats, GitError> {
+        let stats = self.diff_to_parent(commit)?.stats()?;
+        Ok(DiffStats {
+            files_changed: stats.files_changed(),
+            insertions: stats.insertions(),
+            deletions: stats.deletions(),
+        })
+    }
+
+    /// Status of the GPG or SSH signature of the commit. Signatures are verified with
+    /// `git verify-commit`, as libgit2 can't verify them, so the trusted keys configured in git
+    /// (eg `gpg.ssh.allowedSignersFile`) are used.
+    fn signature_status(&self, commit: &GitCommit) -> Result<SignatureStatus, GitError> {
+        match self.repository.extract_signature(&commit.id(), None) {
+            Err(err) if err.code() == GitErrorCode::NotFound => {
+                return Ok(SignatureStatus::Unsigned)
+            }
+            Err(err) => return Err(err),
+            Ok(_) => {}
+        }
+        let output = Command::new("git")
+            .arg("--git-dir")
+            .arg(self.repository.path())
+            .args(["verify-commit", "--raw"])
+            .arg(commit.id().to_string())
+            .output()
+            .map_err(|err| {
+                GitError::from_str(&format!("Could not run git verify-commit: {err}"))
+            })?;
+        if output.status.success() {
+            return Ok(SignatureStatus::Signed);
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        debug!(commit_id = ?commit.id(), %stderr, "Signature not verified");
+        // GPG reports bad signatures with a BADSIG status line, other failures (like missing
+        // or untrusted keys) can't be told apart
+        if stderr.contains("BADSIG") {
+            Ok(SignatureStatus::Bad)
+        } else {
+            Ok(SignatureStatus::UnknownKey)
+        }
+    }
+
+    /// Patch ids (as computed by `git patch-id`) of the non-merge commits reachable from `to` but
+    /// not from `from`. Commits with the same patch id introduce the same change, which is how
+    /// `git cherry` detects cherry-picked commits.
+    fn patch_ids(&self, from: GitOid, to: GitOid) -> Result<HashSet<GitOid>> {
+        let patch_ids = self
+            .history()
+            .commits_between(from, to, false)?
+            .into_iter()
+            .map(|commit_id| self.repository.find_commit(commit_id))
+            .filter_ok(|commit| commit.parent_count() < 2)
+            .map(|commit| self.diff_to_parent(&commit?)?.patchid(None))
+            .collect::<Result<_, GitError>>()?;
+        Ok(patch_ids)
+    }
+
+    /// Diff between the commit and its first parent (or the empty tree for a root commit)
+    fn diff_to_parent(&self, commit: &GitCommit) -> Result<Diff<'_>, GitError> {
+        let tree = commit.tree()?;
+        let parent_tree = commit
+            .parents()
+            .next()
+            .map(|parent| parent.tree())
+            .transpose()?;
+        self.repository
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+    }
+
+    /// Resolve a reference with `git rev-parse` semantics, like `master`, `origin/release`,
+    /// `v3.4.0^{commit}`, `next~5` or a SHA. Once fetched, the reference is resolved against the
+    /// remote first, eg as `origin/next~5`.
+    fn find_commit(&'a self, reference: &RepositoryReference) -> Result<GitCommit<'a>> {
+        if let Some(pattern) = reference.latest_tag_pattern() {
+            return self.find_latest_tag(pattern);
+        }
+        let remote_spec = format!("{}/{}", self.remote, reference);
+        let specs: Vec<&str> = if self.use_remote_branches {
+            vec![remote_spec.as_str(), reference.as_ref()]
+        } else {
+            vec![reference.as_ref()]
+        };
+        let mut last_error = None;
+        for spec in specs {
+            match self.repository.revparse_single(spec) {
+                Ok(object) => {
+                    debug!("Resolved {reference} as {spec}");
+                    return Ok(object.peel_to_commit()?);
+                }
+                Err(err)
+                    if matches!(
+                        err.code(),
+                        GitErrorCode::NotFound | GitErrorCode::InvalidSpec
+                    ) =>
+                {
+                    last_error = Some(err);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let error = last_error.map(|err| err.message().to_owned());
+        Err(anyhow!(
+            "Could not resolve {reference}: {error}. Existing references are: {references}",
+            error = error.unwrap_or_default(),
+            references = self.reference_names()?
+        ))
+    }
+
+    /// Short names of the branches and tags of the repository, listed when a reference can't be
+    /// resolved
+    fn reference_names(&self) -> Result<String> {
+        const MAX_REFERENCE_NAMES: usize = 20;
+        let mut names = self
+            .repository
+            .references()?
+            .filter_map(|reference| reference.ok()?.shorthand().map(str::to_owned))
+            .collect::<Vec<_>>();
+        names.sort();
+        let count = names.len();
+        let mut reference_names = names
+            .into_iter()
+            .take(MAX_REFERENCE_NAMES)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if count > MAX_REFERENCE_NAMES {
+            reference_names += &format!(" and {} more", count - MAX_REFERENCE_NAMES);
+        }
+        Ok(reference_names)
+    }
+
+    /// Resolve the tag with the highest semantic version amongst the ones matching the glob,
+    /// ignoring tags which aren't semantic versions
+    fn find_latest_tag(&'a self, pattern: &str) -> Result<GitCommit<'a>> {
+        let tag_names = self.repository.tag_names(Some(pattern))?;
+        let (tag_name, version) = latest_version_tag(tag_names.iter().flatten())
+            .ok_or_else(|| anyhow!("No semantic version tag matching {pattern}"))?;
+        debug!(%version, "Resolved latest-tag:{pattern} as {tag_name}");
+        let commit = self
+            .repository
+            .revparse_single(&format!("refs/tags/{tag_name}"))?
+            .peel_to_commit()?;
+        Ok(commit)
+    }
+}
+
+/// Whether the reference is the full SHA of a commit, rather than a branch. Branches with a short
+/// hexadecimal name, like `cafe`, are not SHAs.
+fn is_full_sha(reference: &str) -> bool {
+    reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The tag with the highest semantic version, along with the version, ignoring the tags which
+/// aren't semantic versions
+fn latest_version_tag<'t>(tag_names: impl Iterator<Item = &'t str>) -> Option<(&'t str, Version)> {
+    tag_names
+        .filter_map(|tag_name| Some((tag_name, tag_version(tag_name)?)))
+        .max_by(|(_, version), (_, other_version)| version.cmp(other_version))
+}
+
+/// Parse the semantic version of a tag, ignoring any prefix before the major version like `v`
+fn tag_version(tag_name: &str) -> Option<Version> {
+    Version::parse(tag_name.trim_start_matches(|c: char| !c.is_ascii_digit())).ok()
+}
+
+/// Name of the directory caching the repository at `location`, a path or URL. The location is
+/// readable in the name, and its hash keeps the locations differing only by their punctuation,
+/// like `org/a-b` and `org/a_b`, apart.
+fn cache_directory_name(location: &str) -> String {
+    let readable_location = location
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let hash = format!("{:x}", Sha256::digest(location.as_bytes()));
+    format!("{readable_location}_{}", &hash[..8])
+}
+
+/// Whether the repository has a commit-graph file, which both libgit2 and gitoxide use to walk
+/// the history without parsing every commit
+fn has_commit_graph(repository: &GitRepository) -> bool {
+    let info_path = repository.path().join("objects").join("info");
+    info_path.join("commit-graph").exists()
+        || info_path
+            .join("commit-graphs")
+            .join("commit-graph-chain")
+            .exists()
+}
+
+/// Write or update the commit-graph file of a repository mirrored in the cache directory. This
+/// requires the git command, so failures are only logged.
+fn write_commit_graph(repository: &GitRepository) {
+    let now = Instant::now();
+    let result = Command::new("git")
+        .arg("--git-dir")
+        .arg(repository.path())
+        .args(["commit-graph", "write", "--reachable"])
+        .output();
+    match result {
+        Ok(output) if output.status.success() => debug!(
+            "Commit-graph written in {time}ms",
+            time = now.elapsed().as_millis()
+        ),
+        Ok(output) => warn!(
+            stderr = %String::from_utf8_lossy(&output.stderr),
+            "Could not write the commit-graph"
+        ),
+        Err(err) => warn!("Could not run git commit-graph: {err}"),
+    }
+}
+
+/// Convert a git time to a date in the time zone of the author or committer
+fn to_datetime(time: GitTime) -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).expect("UTC should be a valid offset"));
+    DateTime::from_timestamp(time.seconds(), 0)
+        .unwrap_or_default()
+        .with_timezone(&offset)
+}
+
+fn commit_author(signature: &Signature) -> CommitAuthor {
+    CommitAuthor {
+        name: signature.name().map(|name| name.to_owned()),
+        email: signature.email().map(|email| email.to_owned()),
+    }
+}
+
+/// Co-authors credited in the commit message with `Co-authored-by: Name <email>` trailers
+fn co_authors(message: &str) -> Vec<CommitAuthor> {
+    lazy_static! {
+        static ref CO_AUTHOR_RE: Regex =
+            Regex::new(r"^(.*?)\s*<([^>]*)>$").expect("Could not compile CO_AUTHOR_RE");
+    };
+    let Ok(trailers) = git2::message_trailers_strs(message) else {
+        return Vec::new();
+    };
+    trailers
+        .iter()
+        .filter(|(key, _value)| key.eq_ignore_ascii_case("Co-authored-by"))
+        .filter_map(|(_key, value)| {
+            let captures = CO_AUTHOR_RE.captures(value.trim())?;
+            let non_empty = |capture: &str| (!capture.is_empty()).then(|| capture.to_owned());
+            let co_author = CommitAuthor {
+                name: non_empty(&captures[1]),
+                email: non_empty(&captures[2]),
+            };
+            (co_author.name.is_some() || co_author.email.is_some()).then_some(co_author)
+        })
+        .collect()
+}
+
+/// Name of the branch merged by a merge commit, as written by `git merge` or GitHub
+fn merged_branch_name(message: &str) -> Option<String> {
+    lazy_static! {
+        static ref MERGED_BRANCH_RE: Regex = Regex::new(
+            r"^Merge (?:(?:remote-tracking )?branch '([^']+)'|pull request #\d+ from (\S+))"
+        )
+        .expect("Could not compile MERGED_BRANCH_RE");
+    };
+    let captures = MERGED_BRANCH_RE.captures(message)?;
+    captures
+        .get(1)
+        .or_else(|| captures.get(2))
+        .map(|branch| branch.as_str().to_owned())
+}
+
+/// Options used for all fetches, downloading all tags and authenticating with
+/// [`credentials_callback`]
+fn fetch_options(
+    repository: &GitRepository,
+    auth: Option<&RepositoryAuth>,
+) -> Result<FetchOptions<'static>> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(credentials_callback(repository.config()?, auth.cloned()));
+    let mut fetch_options = FetchOptions::new();
+    fetch_options
+        .remote_callbacks(callbacks)
+        .download_tags(AutotagOption::All);
+    Ok(fetch_options)
+}
+
+/// Build a libgit2 credentials callback, using the configured authentication method if any.
+/// Otherwise, the SSH agent, the configured git credential helpers and the default credentials
+/// are tried in turn. Each method is only attempted once, as libgit2 calls the callback again
+/// when authentication fails.
+fn credentials_callback(
+    config: GitConfig,
+    auth: Option<RepositoryAuth>,
+) -> impl FnMut(&str, Option<&str>, CredentialType) -> Result<Cred, GitError> {
+    let mut tried_auth = false;
+    let mut tried_ssh_agent = false;
+    let mut tried_credential_helper = false;
+    let mut tried_default = false;
+    move |url, username_from_url, allowed_types| {
+        if let Some(auth) = &auth {
+            if tried_auth {
+                return Err(GitError::from_str(&format!(
+                    "Authentication to {url} failed with the configured {auth:?} credentials"
+                )));
+            }
+            tried_auth = true;
+            return configured_credentials(auth, username_from_url);
+        }
+        if allowed_types.contains(CredentialType::SSH_KEY) && !tried_ssh_agent {
+            tried_ssh_agent = true;
+            return Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"));
+        }
+        if allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_credential_helper {
+            tried_credential_helper = true;
+            return Cred::credential_helper(&config, url, username_from_url);
+        }
+        if allowed_types.contains(CredentialType::DEFAULT) && !tried_default {
+            tried_default = true;
+            return Cred::default();
+        }
+        Err(GitError::from_str(&format!(
+            "No valid credentials available for {url}"
+        )))
+    }
+}
+
+/// Credentials of the configured authentication method, reading secrets from the environment
+fn configured_credentials(
+    auth: &RepositoryAuth,
+    username_from_url: Option<&str>,
+) -> Result<Cred, GitError> {
+    let env_var = |name: &str| {
+        env::var(name)
+            .map_err(|err| GitError::from_str(&format!("Could not read {name} variable: {err}")))
+    };
+    match auth {
+        RepositoryAuth::SshAgent => Cred::ssh_key_from_agent(username_from_url.unwrap_or("git")),
+        RepositoryAuth::SshKey {
+            private_key,
+            passphrase_env,
+        } => {
+            let passphrase = passphrase_env.as_deref().map(env_var).transpose()?;
+            Cred::ssh_key(
+                username_from_url.unwrap_or("git"),
+                None,
+                private_key,
+                passphrase.as_deref(),
+            )
+        }
+        RepositoryAuth::Token {
+            token_env,
+            username,
+        } => Cred::userpass_plaintext(
+            username.as_deref().unwrap_or("x-access-token"),
+            &env_var(token_env)?,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_directory_names() {
+        let name = cache_directory_name("https://github.com/org/a-b");
+        assert!(name.starts_with("https___github_com_org_a_b_"), "{name}");
+        assert_eq!(name, cache_directory_name("https://github.com/org/a-b"));
+        assert_ne!(name, cache_directory_name("https://github.com/org/a_b"));
+        assert_ne!(
+            cache_directory_name("/src/a.b"),
+            cache_directory_name("/src/a_b")
+        );
+    }
+
+    fn author(name: Option<&str>, email: Option<&str>) -> CommitAuthor {
+        CommitAuthor {
+            name: name.map(str::to_owned),
+            email: email.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn co_author_trailers() {
+        assert!(co_authors("").is_empty());
+        let message = "Add the endpoint\n\nPair programmed\n\n\
+            Co-authored-by: Jane Doe <jane@example.com>\n\
+            co-authored-by: <john@example.com>\n\
+            Co-Authored-By: Anonymous <>\n\
+            Co-authored-by: No email\n\
+            Co-authored-by: <>\n\
+            Reviewed-by: Alice <alice@example.com>";
+        assert_eq!(
+            co_authors(message),
+            [
+                author(Some("Jane Doe"), Some("jane@example.com")),
+                author(None, Some("john@example.com")),
+                author(Some("Anonymous"), None),
+            ]
+        );
+        // Only the trailers of the last paragraph
+        assert!(co_authors("Co-authored-by: Jane <jane@example.com>\n\nBody").is_empty());
+    }
+
+    #[test]
+    fn merged_branch_names() {
+        let branch = |message: &str| merged_branch_name(message);
+        assert_eq!(
+            branch("Merge branch 'sc-12/add-endpoint'").as_deref(),
+            Some("sc-12/add-endpoint")
+        );
+        assert_eq!(
+            branch("Merge branch 'feature' into next\n\nConflicts:\n\tsrc/main.rs").as_deref(),
+            Some("feature")
+        );
+        assert_eq!(
+            branch("Merge remote-tracking branch 'origin/sc-34-fix' into master").as_deref(),
+            Some("origin/sc-34-fix")
+        );
+        assert_eq!(
+            branch("Merge pull request #56 from impero-com/sc-78/add-endpoint\n\nAdd the endpoint")
+                .as_deref(),
+            Some("impero-com/sc-78/add-endpoint")
+        );
+        for message in [
+            "",
+            "Add the endpoint",
+            "Merge the endpoint",
+            "Revert \"Merge branch 'feature'\"",
+            "Merge pull request from impero-com/feature",
+            "Merge branch feature",
+        ] {
+            assert_eq!(branch(message), None, "{message}");
+        }
+    }
+
+    #[test]
+    fn tag_versions() {
+        let version = |tag_name: &str| tag_version(tag_name).map(|version| version.to_string());
+        assert_eq!(version("1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(version("v1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(version("release-1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(version("v2.0.0-rc.1").as_deref(), Some("2.0.0-rc.1"));
+        for tag_name in ["", "v", "latest", "v1.2", "1.2.3.4", "release-2024-01-01"] {
+            assert_eq!(version(tag_name), None, "{tag_name}");
+        }
+    }
+
+    #[test]
+    fn latest_version_tags() {
+        assert_eq!(latest_version_tag(std::iter::empty()), None);
+        assert_eq!(latest_version_tag(["latest", "v1.2"].into_iter()), None);
+        let tag_names = [
+            "v1.10.0",
+            "v2.0.0-rc.2",
+            "latest",
+            "v1.9.0",
+            "v2.0.0-rc.10",
+            "v2.0.0-beta",
+        ];
+        // Pre-releases are ordered before their release, and by their numbered identifiers
+        assert_eq!(
+            latest_version_tag(tag_names.into_iter()).map(|(tag_name, _)| tag_name),
+            Some("v2.0.0-rc.10")
+        );
+        let tag_names = tag_names.into_iter().chain(["release-2.0.0"]);
+        assert_eq!(
+            latest_version_tag(tag_names),
+            Some(("release-2.0.0", Version::new(2, 0, 0)))
+        );
+    }
+
+    #[test]
+    fn full_shas() {
+        assert!(is_full_sha("1a2b3c4d5e6f708192a3b4c5d6e7f8091A2B3C4D"));
+        for branch in [
+            "",
+            "cafe",
+            "add",
+            "1a2b3c4",
+            "1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4g",
+        ] {
+            assert!(!is_full_sha(branch), "{branch}");
+        }
+    }
+}
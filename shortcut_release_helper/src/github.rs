@@ -0,0 +1,139 @@
+//! This module retrieves GitHub pull requests referenced by commits
+//!
+//! Squash merges made from GitHub usually only reference the pull request number, as in
+//! `Fix the login page (#1234)`. The pull request title and description often reference the
+//! Shortcut story, so they are retrieved to link such commits to their story.
+use anyhow::{anyhow, Result};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::{header, Client};
+use tracing::debug;
+
+use crate::shortcut::find_story_id;
+use crate::types::{
+    GithubApiKey, GithubRelease, GithubReleaseAsset, GithubRepository, PullRequest,
+    UnreleasedCommit,
+};
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+/// Maximum number of pull requests retrieved at the same time, to stay clear of the secondary
+/// rate limit of GitHub
+const PULL_REQUEST_CONCURRENCY: usize = 8;
+
+pub struct GithubClient {
+    client: Client,
+    api_key: Option<GithubApiKey>,
+}
+
+impl GithubClient {
+    pub fn new(api_key: Option<GithubApiKey>) -> Result<Self> {
+        let client = Client::builder()
+            .user_agent(env!("CARGO_PKG_NAME"))
+            .build()?;
+        Ok(Self { client, api_key })
+    }
+
+    /// Check that the GitHub API can be reached, whatever the response
+    pub async fn check_reachable(&self) -> Result<()> {
+        self.client
+            .get(GITHUB_API_URL)
+            .send()
+            .await
+            .map_err(|err| anyhow!("Could not reach {GITHUB_API_URL}, check the network: {err}"))?;
+        Ok(())
+    }
+
+    async fn get_pull_request(
+        &self,
+        repository: &GithubRepository,
+        number: u64,
+    ) -> Result<PullRequest> {
+        let url = format!("{GITHUB_API_URL}/repos/{repository}/pulls/{number}");
+        let mut request = self
+            .client
+            .get(&url)
+            .header(header::ACCEPT, "application/vnd.github+json");
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let pull_request = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| anyhow!("Error while retrieving pull request {url}: {err}"))?
+            .json::<PullRequest>()
+            .await?;
+        Ok(pull_request)
+    }
+
+    /// Latest release of the repository, excluding the drafts and prereleases
+    pub async fn get_latest_release(&self, repository: &str) -> Result<GithubRelease> {
+        let url = format!("{GITHUB_API_URL}/repos/{repository}/releases/latest");
+        let mut request = self
+            .client
+            .get(&url)
+            .header(header::ACCEPT, "application/vnd.github+json");
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let release = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| anyhow!("Error while retrieving the latest release {url}: {err}"))?
+            .json::<GithubRelease>()
+            .await?;
+        Ok(release)
+    }
+
+    /// Download the content of a release asset
+    pub async fn download_asset(&self, asset: &GithubReleaseAsset) -> Result<Vec<u8>> {
+        let mut request = self
+            .client
+            .get(&asset.url)
+            .header(header::ACCEPT, "application/octet-stream");
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let content = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| anyhow!("Error while downloading {}: {err}", asset.name))?
+            .bytes()
+            .await?;
+        Ok(content.to_vec())
+    }
+
+    /// Attach their pull request to the commits which are not linked to a story, and whose
+    /// subject ends with a pull request number, like `Fix the login page (#1234)`
+    pub async fn add_pull_requests(
+        &self,
+        repository: &GithubRepository,
+        commits: &mut [UnreleasedCommit],
+    ) -> Result<()> {
+        lazy_static! {
+            static ref PULL_REQUEST_RE: Regex =
+                Regex::new(r"\(#(\d+)\)\s*$").expect("Could not compile PULL_REQUEST_RE");
+        };
+        let pull_request_commits = commits.iter_mut().filter_map(|commit| {
+            let message = commit.message.as_deref()?;
+            if find_story_id(message).is_some() {
+                return None;
+            }
+            let subject = message.lines().next()?;
+            let number = PULL_REQUEST_RE.captures(subject)?[1].parse::<u64>().ok()?;
+            Some((commit, number))
+        });
+        stream::iter(pull_request_commits)
+            .map(|(commit, number)| async move {
+                debug!(commit_id = ?commit.id, "Retrieving pull request {number}");
+                commit.pull_request = Some(self.get_pull_request(repository, number).await?);
+                Ok::<_, anyhow::Error>(())
+            })
+            .buffer_unordered(PULL_REQUEST_CONCURRENCY)
+            .try_collect::<()>()
+            .await
+    }
+}
@@ -18,7 +18,8 @@
 //!
 //! # Configuration
 //!
-//! This tool expects a `config.toml`, in the current working directory, like so:
+//! This tool expects a `config.toml`, in the current working directory or given with `--config`,
+//! like so:
 //!
 //! ```toml
 //! template_file = "template.md.jinja"
@@ -32,51 +33,216 @@
 //!
 //! # Debugging
 //!
-//! You can use `RUST_LOG` to control the amount logged by the utility in the console.
+//! You can use `-v` (up to `-vvv`) and `-q` to control the amount logged by the utility in the
+//! console, or `RUST_LOG` for finer control.
 
 #[macro_use]
 extern crate derive_more;
 
 use std::{
-    collections::{HashMap, HashSet},
-    env::{var, VarError},
+    collections::{BTreeMap, HashMap, HashSet},
+    env::{self, var},
+    ffi::OsString,
     fs,
-    path::PathBuf,
-    time::Instant,
+    io::{self, IsTerminal},
+    mem,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
 };
 
 use ansi_term::{
     Colour::{Blue, Green, Red},
     Style,
 };
-use anyhow::{anyhow, Result};
-use clap::Parser;
-use git::{Repository, UnreleasedCommits};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Local, NaiveDate};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use git::{GitOptions, Repository, UnreleasedCommits};
 use itertools::Itertools;
+use regex::Regex;
 use serde::Serialize;
-use shortcut::{ReleaseContent, StoryId};
-use shortcut_client::models::{Epic, Story};
-use tracing::{debug, info};
-use types::{RepoToCommits, RepoToHeadCommit};
+use shortcut::{
+    MissingStory, MissingStoryReason, ReleaseContent, ReleaseEpic, ReleaseObjective,
+    ReleaseSection, ReleaseStory, StoryId,
+};
+use shortcut_client::models::Iteration;
+use tracing::{debug, info, warn};
+use types::{
+    DiffStats, RepoToCommits, RepoToDiffStats, RepoToHeadCommit, RepoToHeadCommits,
+    RepoToMergeBase, RepoToSignatureStats, SignatureStats,
+};
 
 use crate::{
     config::AppConfig,
-    shortcut::{parse_commits, ShortcutClient, StoryLabelFilter},
-    types::{RepositoryConfiguration, RepositoryName, ShortcutApiKey},
+    contributors::Contributor,
+    github::GithubClient,
+    manifest::Manifest,
+    output::prompt_default,
+    review::ReleaseDetails,
+    shortcut::{
+        parse_commits, ShortcutClient, ShortcutOptions, StoryFilter, StoryIdParser, STORY_TYPES,
+    },
+    shortcut_cache::{CacheMode, ShortcutCache},
+    shortcut_fixtures::{read_fixtures, write_fixtures},
+    shortcut_workspaces::ShortcutWorkspaces,
+    types::{
+        GithubApiKey, NextBranches, OutputFormat, PublishTarget, ReleaseContainer, ReleaseGap,
+        RepositoryConfiguration, RepositoryLocation, RepositoryName, RepositoryReference,
+        RepositoryReferenceOverride, ShortcutApiKey, WorkspaceName,
+    },
 };
 
+mod codeowners;
+mod commit_list;
 mod config;
+mod contributors;
+mod conventional_commits;
 mod git;
+mod github;
+mod manifest;
+#[macro_use]
+mod output;
+mod progress;
+mod release_ignore;
+mod review;
+mod self_update;
 mod shortcut;
+mod shortcut_cache;
+mod shortcut_fixtures;
+mod shortcut_workspaces;
 mod template;
+mod token;
 mod types;
 
 /// A command-line tool to generate release notes.
 #[derive(Parser, Debug)]
 #[clap(author, about, long_about = None, disable_version_flag = true)]
-struct Args {
-    /// Output file for the release notes
-    output_file: PathBuf,
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+    /// Do all the reading, printing the release notes instead of writing them, and print the
+    /// files that would be written and the changes that would be made to Shortcut and the
+    /// repositories instead of making them
+    #[clap(long, global = true)]
+    dry_run: bool,
+    /// Configuration file, instead of the one of the SHORTCUT_RELEASE_CONFIG environment variable
+    /// or else `config.toml` in the current directory. Relative paths in it are relative to it.
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    /// Log more details, can be used up to three times: `-v` for the debug logs, `-vv` for the
+    /// trace logs, `-vvv` for the trace logs of the libraries too - RUST_LOG has priority
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Only log the warnings and errors - RUST_LOG has priority
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// Environment variable with the path of the configuration file, when not given with --config
+const CONFIG_ENV: &str = "SHORTCUT_RELEASE_CONFIG";
+
+impl Cli {
+    /// Path of the configuration file, from --config, the environment, or in the current directory
+    fn config_path(&self) -> PathBuf {
+        self.config
+            .clone()
+            .or_else(|| env::var_os(CONFIG_ENV).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("config.toml"))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate the release notes, the default command when none is given
+    Generate(Box<GenerateArgs>),
+    /// Print the summary of the release, without generating the release notes
+    Summary(Box<ReleaseArgs>),
+    /// Find the release like generate, and publish it to Shortcut instead of writing the release
+    /// notes
+    Publish(Box<PublishArgs>),
+    /// Check the configuration, the references of every repository and the template, without
+    /// reaching Shortcut
+    Check,
+    /// Diagnose the environment, checking the Shortcut tokens, the repositories, the template,
+    /// the cache directory and the network
+    Doctor,
+    /// Print the completion script of the shell, eg `source <(shortcut_release_helper completions
+    /// bash)` in `~/.bashrc`
+    Completions { shell: Shell },
+    /// Replace this binary with the one of the latest GitHub release when it is newer. Its
+    /// checksum is verified, which only detects corrupted downloads as the releases aren't signed
+    SelfUpdate,
+}
+
+/// Arguments of the generate command
+#[derive(clap::Args, Debug)]
+struct GenerateArgs {
+    /// Output file for the release notes, or `-` for the standard output
+    #[clap(required_unless_present = "stdout")]
+    output_file: Option<PathBuf>,
+    /// Write the release notes to the standard output, printing the summary on the standard error
+    #[clap(long, conflicts_with = "output_file")]
+    stdout: bool,
+    /// Review the stories and unparsed commits of the release on the terminal before writing the
+    /// release notes, to exclude some of them, and edit the name and description of the release
+    #[clap(long)]
+    interactive: bool,
+    /// Format of the release notes: `markdown` rendered with the template, or `json` to
+    /// serialize the whole release instead
+    #[clap(long, default_value_t)]
+    format: OutputFormat,
+    #[clap(flatten)]
+    metadata: MetadataArgs,
+    /// Create an annotated tag with this name at the head of the next branch of every repository
+    /// once the release notes are generated, with the release notes as message
+    #[clap(long, conflicts_with = "commits_from")]
+    tag: Option<String>,
+    /// Write the manifest of the release, listing its commits and stories, to this file
+    #[clap(long)]
+    manifest: Option<PathBuf>,
+}
+
+impl GenerateArgs {
+    /// File to write the release notes to, or `None` for the standard output
+    fn output_file(&self) -> Option<&Path> {
+        self.output_file
+            .as_deref()
+            .filter(|output_file| *output_file != Path::new("-"))
+    }
+}
+
+/// Arguments of the publish command
+#[derive(clap::Args, Debug)]
+struct PublishArgs {
+    /// What to publish the release to, one or more of: `label` adds the --label to
+    /// its stories, `comment` comments on them with the version, date and next heads of the
+    /// release, `version-field` sets their configured version custom field to the version,
+    /// `workflow-state` moves them to the configured released workflow state, `shortcut` creates
+    /// the --container of the release and moves them to it, and `doc` publishes the release notes
+    /// as a Doc
+    #[clap(required = true, conflicts_with_all = ["offline", "replay"])]
+    targets: Vec<PublishTarget>,
+    /// Label added to the stories with the `label` target, where `{version}` is replaced with the
+    /// version
+    #[clap(long, default_value = "released-{version}")]
+    label: String,
+    /// Kind of the container of the release created with the `shortcut` target, `epic` or
+    /// `iteration`
+    #[clap(long, default_value_t)]
+    container: ReleaseContainer,
+    /// Title of the Doc published with the `doc` target, updated if it exists - the name of the
+    /// release per default
+    #[clap(long)]
+    doc_title: Option<String>,
+    #[clap(flatten)]
+    metadata: MetadataArgs,
+}
+
+/// Arguments describing the release, common to the generate and publish commands
+#[derive(clap::Args, Debug)]
+struct MetadataArgs {
     /// Version to release
     #[clap(long)]
     version: Option<String>,
@@ -86,6 +252,36 @@ struct Args {
     /// Description of the release
     #[clap(long)]
     description: Option<String>,
+    /// Never prompt for the version and name when they aren't given, eg on the CI. They are only
+    /// prompted for when the standard input is a terminal anyway.
+    #[clap(long)]
+    no_input: bool,
+    #[clap(flatten)]
+    release: ReleaseArgs,
+    /// Fail without writing the release notes nor publishing the release when some stories
+    /// aren't in a workflow state of the done type
+    #[clap(long)]
+    require_done: bool,
+}
+
+impl MetadataArgs {
+    /// The name of the release, or else `Release <version>`
+    fn release_name(&self) -> Option<String> {
+        self.name.clone().or_else(|| {
+            self.version
+                .as_ref()
+                .map(|version| format!("Release {version}"))
+        })
+    }
+}
+
+/// Arguments finding the release, common to the commands
+#[derive(clap::Args, Debug)]
+struct ReleaseArgs {
+    /// Date of the release, as `YYYY-MM-DD`, to flag the stories shipped past their deadline -
+    /// today per default
+    #[clap(long)]
+    date: Option<NaiveDate>,
     /// Id of story to exclude, can be used multiple times
     #[clap(long)]
     exclude_story_id: Vec<StoryId>,
@@ -96,33 +292,299 @@ struct Args {
     /// Label of story to include, can be used multiple times
     #[clap(long)]
     include_story_label: Vec<String>,
+    /// Name of the workflow state of the stories to exclude, can be used multiple times - has
+    /// priority over include-story-state
+    #[clap(long)]
+    exclude_story_state: Vec<String>,
+    /// Name of the workflow state of the stories to include, can be used multiple times
+    #[clap(long)]
+    include_story_state: Vec<String>,
+    /// Only include the stories in a workflow state of the done type
+    #[clap(long)]
+    done_stories_only: bool,
+    /// Type of the stories to exclude, can be used multiple times - has priority over
+    /// include-story-type
+    #[clap(long, value_parser = STORY_TYPES)]
+    exclude_story_type: Vec<String>,
+    /// Type of the stories to include, can be used multiple times
+    #[clap(long, value_parser = STORY_TYPES)]
+    include_story_type: Vec<String>,
+    /// Name or mention name of the team (Shortcut group) of the stories to exclude, can be used
+    /// multiple times - has priority over include-team
+    #[clap(long)]
+    exclude_team: Vec<String>,
+    /// Name or mention name of the team (Shortcut group) of the stories to include, can be used
+    /// multiple times
+    #[clap(long)]
+    include_team: Vec<String>,
+    /// Id of the epic whose stories to exclude, can be used multiple times - has priority over
+    /// include-epic-id
+    #[clap(long)]
+    exclude_epic_id: Vec<i64>,
+    /// Id of the epic whose stories to include, can be used multiple times
+    #[clap(long)]
+    include_epic_id: Vec<i64>,
+    /// Name or id of the Shortcut project of the stories to exclude, can be used multiple times -
+    /// has priority over include-project
+    #[clap(long)]
+    exclude_project: Vec<String>,
+    /// Name or id of the Shortcut project of the stories to include, can be used multiple times
+    #[clap(long)]
+    include_project: Vec<String>,
+    /// Name of the iteration of the stories to include, eg `Sprint 42`, can be used multiple
+    /// times
+    #[clap(long)]
+    iteration: Vec<String>,
+    /// Search Shortcut with the subject and branch of each unparsed commit, and suggest up to
+    /// this number of stories it may belong to, at most 25
+    #[clap(
+        long,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=25),
+        conflicts_with_all = ["offline", "replay"]
+    )]
+    suggest_stories: Option<usize>,
     /// Exclude unparsed commits
     #[clap(long)]
     exclude_unparsed_commits: bool,
+    /// Fetch the release and next branches of every repository before looking for commits
+    #[clap(long)]
+    fetch: bool,
+    /// Search a mirror of every local repository, kept in the cache directory, instead of the
+    /// working copy
+    #[clap(long)]
+    isolated: bool,
+    /// Compute the number of files changed, and lines inserted and deleted by every commit
+    #[clap(long)]
+    with_diffstat: bool,
+    /// Verify the GPG or SSH signature of every commit, and print the signature status of the
+    /// commits of each repository
+    #[clap(long)]
+    verify_signatures: bool,
+    /// Name of a configured repository to restrict the release to, can be used multiple times
+    #[clap(long = "repo")]
+    repos: Vec<RepositoryName>,
+    /// Release branch or commit to use for every repository instead of the configured one
+    #[clap(long)]
+    release_ref: Option<RepositoryReference>,
+    /// Next branch or commit to use for every repository instead of the configured one
+    #[clap(long)]
+    next_ref: Option<RepositoryReference>,
+    /// References of a single repository, as `<repository>=<release>..<next>`, can be used
+    /// multiple times - has priority over release-ref and next-ref
+    #[clap(long = "ref")]
+    refs: Vec<RepositoryReferenceOverride>,
+    /// File listing the unreleased commits as `<repository>\t<sha>\t<message>` lines, or `-` to
+    /// read them from the standard input, instead of finding them in the repositories
+    #[clap(long)]
+    commits_from: Option<PathBuf>,
+    /// Manifest written by a previous run, whose commits and stories are excluded, can be used
+    /// multiple times
+    #[clap(long)]
+    baseline: Vec<PathBuf>,
+    /// Retrieve all stories and epics from Shortcut without reading nor writing the cache
+    #[clap(long, conflicts_with = "refresh")]
+    no_cache: bool,
+    /// Retrieve all stories and epics from Shortcut, and update the cache with them
+    #[clap(long)]
+    refresh: bool,
+    /// Read the stories and epics from the cache only, whatever their age, leaving out the ones
+    /// that aren't cached - no Shortcut token is needed, and pull requests aren't retrieved from
+    /// GitHub
+    #[clap(long, conflicts_with_all = ["no_cache", "refresh", "fetch"])]
+    offline: bool,
+    /// Write the stories and epics retrieved from Shortcut to this fixtures file
+    #[clap(long)]
+    record: Option<PathBuf>,
+    /// Read the stories and epics from a fixtures file written with record, instead of Shortcut -
+    /// no Shortcut token is needed
+    #[clap(long, conflicts_with_all = ["offline", "record", "no_cache", "refresh"])]
+    replay: Option<PathBuf>,
+    /// Write the counts of the summary as JSON to this file, or `-` for the standard output,
+    /// printing the summary on the standard error
+    #[clap(long)]
+    summary_json: Option<PathBuf>,
+    /// Fail once the summary is printed when the release has any of these gaps, comma-separated:
+    /// unparsed-commits, not-done-stories or missing-stories. The exit code is then 3, instead of
+    /// 1 for the other errors.
+    #[clap(long, value_delimiter = ',')]
+    fail_on: Vec<ReleaseGap>,
+    /// Fail on all the gaps of --fail-on
+    #[clap(long)]
+    strict: bool,
+    /// File containing the Shortcut token of the default workspace, eg a CI secret mount,
+    /// instead of the SHORTCUT_TOKEN environment variable
+    #[clap(long, conflicts_with_all = ["offline", "replay"])]
+    token_file: Option<PathBuf>,
+    /// Read the Shortcut token of the default workspace from the standard input, instead of the
+    /// SHORTCUT_TOKEN environment variable
+    #[clap(long, conflicts_with_all = ["offline", "replay", "token_file"])]
+    token_stdin: bool,
+}
+
+/// The label to apply to the stories of the release, replacing `{version}` with the version
+fn release_label(label: &str, version: Option<&str>) -> Result<String> {
+    if !label.contains("{version}") {
+        return Ok(label.to_owned());
+    }
+    let version =
+        version.ok_or_else(|| anyhow!("The label {label} requires the version of the release"))?;
+    Ok(label.replace("{version}", version))
+}
+
+/// The start of the comments of the release with this version, eg `Shipped in 3.4.0 `, telling
+/// them apart from the ones of other releases whatever their date and heads
+fn release_comment_marker(version: &str) -> String {
+    format!("Shipped in {version} ")
+}
+
+/// The comment on a story of the release, eg `Shipped in 3.4.0 on 2024-06-01 (api dev @ abc1234)`,
+/// with the head of the next branch of each repository the story has commits in
+fn release_comment(release: &Release, story: &ReleaseStory) -> String {
+    let mut comment = String::from("Shipped");
+    if let Some(version) = release.version {
+        comment.push_str(&format!(" in {version}"));
+    }
+    comment.push_str(&format!(" on {}", release.date));
+    let heads = story
+        .repositories
+        .iter()
+        .filter_map(|repo_name| {
+            let head = release.next_heads.get(repo_name)?;
+            let id = head.id.to_string();
+            Some(format!("{repo_name} {} @ {}", head.branch, &id[..7]))
+        })
+        .join(", ");
+    if !heads.is_empty() {
+        comment.push_str(&format!(" ({heads})"));
+    }
+    comment
+}
+
+/// Only keep the repositories given on the command line, if any
+fn select_repositories(
+    repositories: &mut HashMap<RepositoryName, RepositoryConfiguration>,
+    repo_names: &[RepositoryName],
+) -> Result<()> {
+    if repo_names.is_empty() {
+        return Ok(());
+    }
+    if let Some(unknown) = repo_names
+        .iter()
+        .find(|repo_name| !repositories.contains_key(repo_name))
+    {
+        bail!(
+            "Unknown repository {unknown} in --repo, the configured ones are: {}",
+            repositories
+                .keys()
+                .sorted_by_key(|name| name.to_string())
+                .join(", ")
+        );
+    }
+    repositories.retain(|repo_name, _| repo_names.contains(repo_name));
+    Ok(())
+}
+
+/// Override the configured release and next references with the ones given on the command line
+fn override_references(
+    repositories: &mut HashMap<RepositoryName, RepositoryConfiguration>,
+    args: &ReleaseArgs,
+) -> Result<()> {
+    for repo_config in repositories.values_mut() {
+        if let Some(release_ref) = &args.release_ref {
+            repo_config.release_branch = release_ref.clone();
+        }
+        if let Some(next_ref) = &args.next_ref {
+            repo_config.next_branch = NextBranches::single(next_ref.clone());
+        }
+    }
+    for reference_override in &args.refs {
+        let repo_config = repositories
+            .get_mut(&reference_override.repository)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown repository {} in --ref",
+                    reference_override.repository
+                )
+            })?;
+        if let Some(release_branch) = &reference_override.release_branch {
+            repo_config.release_branch = release_branch.clone();
+        }
+        if let Some(next_branch) = &reference_override.next_branch {
+            repo_config.next_branch = NextBranches::single(next_branch.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Tag the head of the primary next branch of every local repository. Repositories configured
+/// with a URL are skipped, as they are only mirrored in the cache directory, while isolated
+/// repositories are tagged in their working copy.
+fn tag_repositories(
+    repositories: &HashMap<RepositoryName, RepositoryConfiguration>,
+    next_heads: &RepoToHeadCommit,
+    tag_name: &str,
+    message: &str,
+) -> Result<()> {
+    for (repo_name, repo_config) in repositories {
+        if repo_config.location.remote_url().is_some() {
+            warn!(repo = %repo_name, "Not tagging remote repository");
+            continue;
+        }
+        let next_head = next_heads
+            .get(repo_name)
+            .ok_or_else(|| anyhow!("Missing head of the next branch of {repo_name}"))?;
+        let repo_config = RepositoryConfiguration {
+            isolated: false,
+            ..repo_config.clone()
+        };
+        Repository::new(&repo_config, GitOptions::default())?.tag(
+            tag_name,
+            next_head.id,
+            message,
+        )?;
+    }
+    Ok(())
 }
 
+/// Find the unreleased commits of the repository, followed by the ones of its changed submodules
+/// when enabled, named `<repository>/<path>`
 #[tracing::instrument(level = "info", skip_all, fields(repo = %repo_name))]
 fn find_unreleased_commits(
     repo_name: &RepositoryName,
     repo_config: &RepositoryConfiguration,
-) -> Result<UnreleasedCommits> {
+    git_options: GitOptions,
+    promotion_pipeline: &[String],
+) -> Result<Vec<(RepositoryName, UnreleasedCommits)>> {
     info!(
         release_branch = %repo_config.release_branch,
         next_branch = %repo_config.next_branch
     );
     debug!("Initializing repository");
-    let repo = {
+    let mut repo = {
         let now = Instant::now();
-        let repo = Repository::new(repo_config)?;
+        let repo = Repository::new(repo_config, git_options)?;
         debug!(
             "Initialization done in {time}ms",
             time = now.elapsed().as_millis()
         );
         repo
     };
-    let commits = {
+    if repo.is_shallow() {
         let now = Instant::now();
-        let commits = repo.find_unreleased_commits_and_head()?;
+        repo.unshallow()?;
+        debug!(
+            "Unshallow done in {time}ms",
+            time = now.elapsed().as_millis()
+        );
+    }
+    if git_options.fetch || repo_config.fetch {
+        let now = Instant::now();
+        repo.fetch()?;
+        debug!("Fetch done in {time}ms", time = now.elapsed().as_millis());
+    }
+    let mut commits = {
+        let now = Instant::now();
+        let commits = repo.find_unreleased_commits_and_head(repo_name)?;
         info!(
             "Found {commit_count} unreleased commits in {time}ms",
             commit_count = commits.unreleased_commits.len(),
@@ -130,101 +592,1463 @@ fn find_unreleased_commits(
         );
         commits
     };
-    Ok(commits)
+    let environment_references = promotion_pipeline
+        .iter()
+        .map(|environment| {
+            repo_config.environments.get(environment).ok_or_else(|| {
+                anyhow!("Missing reference of the {environment} environment in {repo_name}")
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // The steps reuse the repository opened and fetched for the release
+    let steps = promotion_pipeline
+        .windows(2)
+        .zip(environment_references.windows(2));
+    for (environments, references) in steps {
+        info!(
+            "Finding the commits promoted from {} to {}",
+            environments[1], environments[0]
+        );
+        commits.promoted_commits.push(repo.find_promoted_commits(
+            repo_name,
+            references[0],
+            references[1],
+        )?);
+    }
+    let mut repo_names_and_commits = vec![(repo_name.clone(), commits)];
+    if repo_config.submodules {
+        for submodule in repo.changed_submodules()? {
+            // The environments of a submodule are the commits pinned in each environment, its
+            // steps being skipped when it isn't pinned in all of them
+            let submodule_environments = environment_references
+                .iter()
+                .zip(promotion_pipeline)
+                .map(|(reference, environment)| {
+                    let commit = repo.submodule_commit(reference, &submodule.path)?;
+                    Ok(commit.map(|commit| {
+                        (
+                            environment.clone(),
+                            RepositoryReference::new(commit.to_string()),
+                        )
+                    }))
+                })
+                .collect::<Result<Option<HashMap<_, _>>>>()?;
+            let submodule_pipeline = match &submodule_environments {
+                Some(_) => promotion_pipeline,
+                None => &[],
+            };
+            let submodule_config = RepositoryConfiguration {
+                location: RepositoryLocation::new(submodule.location),
+                release_branch: RepositoryReference::new(submodule.release_commit.to_string()),
+                next_branch: NextBranches::single(RepositoryReference::new(
+                    submodule.next_commit.to_string(),
+                )),
+                paths: Vec::new(),
+                github_repository: None,
+                commit_url_template: None,
+                environments: submodule_environments.unwrap_or_default(),
+                ..repo_config.clone()
+            };
+            repo_names_and_commits.extend(find_unreleased_commits(
+                &repo_name.submodule(&submodule.path),
+                &submodule_config,
+                git_options,
+                submodule_pipeline,
+            )?);
+        }
+    }
+    Ok(repo_names_and_commits)
 }
 
-fn print_summary(release: &ReleaseContent) {
+/// Find the unreleased commits of all repositories and their submodules, in parallel, along with
+/// the commits of each step of the promotion pipeline
+async fn find_all_unreleased_commits(
+    repositories: HashMap<RepositoryName, RepositoryConfiguration>,
+    git_options: GitOptions,
+    promotion_pipeline: &[String],
+) -> Result<Vec<(RepositoryName, UnreleasedCommits)>> {
+    let repo_names_and_unreleased_commits =
+        futures::future::try_join_all(repositories.into_iter().map(|(name, repo_config)| {
+            let promotion_pipeline = promotion_pipeline.to_vec();
+            tokio::task::spawn_blocking::<_, Result<_>>(move || {
+                find_unreleased_commits(&name, &repo_config, git_options, &promotion_pipeline)
+            })
+        }))
+        .await?;
+    let mut all_unreleased_commits = Vec::new();
+    for repo_and_submodules_commits in repo_names_and_unreleased_commits {
+        all_unreleased_commits.extend(repo_and_submodules_commits?);
+    }
+    Ok(all_unreleased_commits)
+}
+
+/// Counts of the summary of the release, also written as JSON with --summary-json
+#[derive(Debug, Default, Serialize)]
+pub struct ReleaseSummary {
+    pub stories: usize,
+    pub security_stories: usize,
+    pub breaking_stories: usize,
+    pub breaking_commits: usize,
+    /// Archived stories included in the release
+    pub archived_stories: usize,
+    /// Archived stories left out of the release, also counted in `missing_stories`
+    pub excluded_archived_stories: usize,
+    pub overdue_stories: usize,
+    pub not_done_stories: usize,
+    /// Stories with linked code in repositories where none of their commits are released
+    pub unreleased_repository_stories: usize,
+    /// Stories referenced by commits which don't exist, are archived or can't be accessed
+    pub missing_stories: usize,
+    pub epics: usize,
+    pub partial_epics: usize,
+    pub excluded_commits: usize,
+    pub reverted_commit_pairs: usize,
+    /// Unparsed commits by repository
+    pub unparsed_commits: BTreeMap<String, usize>,
+    /// Commits present in the release branch but not the next branch, by repository
+    pub missing_in_next: BTreeMap<String, usize>,
+}
+
+impl ReleaseSummary {
+    fn new(
+        release: &ReleaseContent,
+        missing_in_next: &RepoToCommits,
+        security_label: &str,
+    ) -> Self {
+        let count_stories = |predicate: fn(&ReleaseStory) -> bool| {
+            release
+                .stories
+                .iter()
+                .filter(|story| predicate(story))
+                .count()
+        };
+        let count_by_repository = |commits: &RepoToCommits| {
+            commits
+                .iter()
+                .filter(|(_, commits)| !commits.is_empty())
+                .map(|(repo_name, commits)| (repo_name.to_string(), commits.len()))
+                .collect()
+        };
+        Self {
+            stories: release.stories.len(),
+            security_stories: release
+                .stories
+                .iter()
+                .filter(|story| shortcut::has_label(story, security_label))
+                .count(),
+            breaking_stories: count_stories(|story| story.breaking),
+            breaking_commits: release.breaking_commits.values().map(Vec::len).sum(),
+            archived_stories: count_stories(|story| story.story.archived),
+            excluded_archived_stories: release
+                .missing_stories
+                .iter()
+                .filter(|missing_story| missing_story.reason == MissingStoryReason::Archived)
+                .count(),
+            overdue_stories: count_stories(|story| story.overdue),
+            not_done_stories: count_stories(|story| !story.workflow_state_done),
+            unreleased_repository_stories: count_stories(|story| {
+                !story.unreleased_repositories.is_empty()
+            }),
+            missing_stories: release.missing_stories.len(),
+            epics: release.epics.len(),
+            partial_epics: release
+                .epics
+                .iter()
+                .filter(|epic| !epic.progress.complete)
+                .count(),
+            excluded_commits: release.excluded_commits,
+            reverted_commit_pairs: release.reverted_commit_pairs,
+            unparsed_commits: count_by_repository(&release.unparsed_commits),
+            missing_in_next: count_by_repository(missing_in_next),
+        }
+    }
+}
+
+/// Write the summary as JSON to the file, or to the standard output with `-`
+fn write_summary_json(summary: &ReleaseSummary, path: &Path, dry_run: bool) -> Result<()> {
+    let summary_json = serde_json::to_string_pretty(summary)?;
+    if path == Path::new("-") {
+        println!("{summary_json}");
+    } else if dry_run {
+        message!("Would write the summary to {path:?}");
+    } else {
+        fs::write(path, summary_json)
+            .with_context(|| format!("Could not write the summary to {path:?}"))?;
+    }
+    Ok(())
+}
+
+fn print_summary(summary: &ReleaseSummary, release: &ReleaseContent) {
     let header_style = Style::new().bold();
-    println!(
+    message!(
         "{}: {}",
         header_style.paint("Total stories"),
-        Green.paint(release.stories.len().to_string())
+        Green.paint(summary.stories.to_string())
     );
-    println!(
+    let counts = [
+        ("Total security fixes", summary.security_stories, Red),
+        ("Total breaking stories", summary.breaking_stories, Red),
+        ("Total breaking commits", summary.breaking_commits, Red),
+        (
+            "Total archived stories included",
+            summary.archived_stories,
+            Red,
+        ),
+        (
+            "Total archived stories excluded",
+            summary.excluded_archived_stories,
+            Blue,
+        ),
+        (
+            "Total stories shipped past their deadline",
+            summary.overdue_stories,
+            Red,
+        ),
+    ];
+    for (header, count, colour) in counts {
+        if count > 0 {
+            message!(
+                "\n{}: {}",
+                header_style.paint(header),
+                colour.paint(count.to_string())
+            );
+        }
+    }
+    if summary.missing_stories > 0 {
+        message!(
+            "\n{}: {}",
+            header_style.paint("Total missing stories"),
+            Red.paint(summary.missing_stories.to_string())
+        );
+        for missing_story in &release.missing_stories {
+            message!(
+                "  {} ({})",
+                Blue.paint(format!("sc-{}", missing_story.id)),
+                missing_story.reason
+            );
+        }
+    }
+    message!(
         "\n{}: {}",
         header_style.paint("Total epics"),
-        Green.paint(release.epics.len().to_string())
+        Green.paint(summary.epics.to_string())
     );
-    for (repo, commits) in &release.unparsed_commits {
-        if !commits.is_empty() {
-            println!(
-                "\n{}{}: {}",
-                header_style.paint("Total unparsed commits in "),
-                Blue.paint(repo.as_ref()),
-                Red.paint(commits.len().to_string())
+    let counts = [
+        ("Total partially shipped epics", summary.partial_epics, Red),
+        ("Total excluded commits", summary.excluded_commits, Blue),
+        (
+            "Total reverted commit pairs",
+            summary.reverted_commit_pairs,
+            Blue,
+        ),
+    ];
+    for (header, count, colour) in counts {
+        if count > 0 {
+            message!(
+                "\n{}: {}",
+                header_style.paint(header),
+                colour.paint(count.to_string())
             );
         }
     }
+    for (repo, count) in &summary.unparsed_commits {
+        message!(
+            "\n{}{}: {}",
+            header_style.paint("Total unparsed commits in "),
+            Blue.paint(repo),
+            Red.paint(count.to_string())
+        );
+    }
+    for (repo, count) in &summary.missing_in_next {
+        message!(
+            "\n{}{}: {}",
+            header_style.paint("Total commits missing in next in "),
+            Blue.paint(repo),
+            Red.paint(count.to_string())
+        );
+    }
 }
 
+fn print_promotion_summary(promotion_steps: &[PromotionStep]) {
+    let header_style = Style::new().bold();
+    for step in promotion_steps {
+        message!(
+            "\n{}{} to {}: {}",
+            header_style.paint("Total stories promoted from "),
+            Blue.paint(&step.from),
+            Blue.paint(&step.to),
+            Green.paint(step.stories.len().to_string())
+        );
+    }
+}
+
+/// Print the stories of the release which aren't in a done workflow state, along with their
+/// owners
+fn print_not_done_summary(stories: &[ReleaseStory]) {
+    let not_done = stories
+        .iter()
+        .filter(|story| !story.workflow_state_done)
+        .collect::<Vec<_>>();
+    if not_done.is_empty() {
+        return;
+    }
+    let header_style = Style::new().bold();
+    message!(
+        "\n{}: {}",
+        header_style.paint("Total stories not done"),
+        Red.paint(not_done.len().to_string())
+    );
+    for story in &not_done {
+        let owners = story
+            .owners
+            .iter()
+            .map(|owner| format!("@{}", owner.mention_name))
+            .join(", ");
+        message!(
+            "  {} {} ({}){}",
+            Blue.paint(format!("sc-{}", story.story.id)),
+            story.story.name,
+            story
+                .workflow_state_name
+                .as_deref()
+                .unwrap_or("unknown state"),
+            if owners.is_empty() {
+                String::new()
+            } else {
+                format!(" - {owners}")
+            }
+        );
+    }
+}
+
+/// Print the stories with merged pull requests or commits linked in Shortcut in repositories
+/// where none of their commits are released
+fn print_unreleased_repositories_summary(stories: &[ReleaseStory]) {
+    let unreleased = stories
+        .iter()
+        .filter(|story| !story.unreleased_repositories.is_empty())
+        .collect::<Vec<_>>();
+    if unreleased.is_empty() {
+        return;
+    }
+    let header_style = Style::new().bold();
+    message!(
+        "\n{}: {}",
+        header_style.paint("Total stories with linked code missing from the release"),
+        Red.paint(unreleased.len().to_string())
+    );
+    for story in &unreleased {
+        message!(
+            "  {} {} ({})",
+            Blue.paint(format!("sc-{}", story.story.id)),
+            story.story.name,
+            story
+                .unreleased_repositories
+                .iter()
+                .map(|repo_name| repo_name.as_ref())
+                .join(", ")
+        );
+    }
+}
+
+fn print_owner_summary(unparsed_commits_by_owner: &HashMap<String, RepoToCommits>) {
+    if unparsed_commits_by_owner
+        .keys()
+        .all(|owner| owner == codeowners::UNOWNED)
+    {
+        return;
+    }
+    let header_style = Style::new().bold();
+    for (owner, commits) in unparsed_commits_by_owner
+        .iter()
+        .sorted_by_key(|(owner, _)| *owner)
+    {
+        message!(
+            "\n{}{}: {}",
+            header_style.paint("Total unparsed commits owned by "),
+            Blue.paint(owner),
+            Red.paint(commits.values().map(Vec::len).sum::<usize>().to_string())
+        );
+    }
+}
+
+fn print_signature_summary(signature_stats: &RepoToSignatureStats) {
+    let header_style = Style::new().bold();
+    for (repo, stats) in signature_stats {
+        message!(
+            "\n{}{}: {} signed, {} unsigned, {} unknown key, {} bad",
+            header_style.paint("Commit signatures in "),
+            Blue.paint(repo.as_ref()),
+            Green.paint(stats.signed.to_string()),
+            Red.paint(stats.unsigned.to_string()),
+            Red.paint(stats.unknown_key.to_string()),
+            Red.paint(stats.bad.to_string())
+        );
+    }
+}
+
+/// Commits deployed to an environment of the promotion pipeline but not to the previous one
 #[derive(Debug, Serialize)]
+pub struct PromotionStep {
+    /// Environment the commits are promoted from, eg `staging`
+    pub from: String,
+    /// Environment the commits are promoted to, eg `production`
+    pub to: String,
+    pub stories: Vec<ReleaseStory>,
+    pub epics: Vec<ReleaseEpic>,
+    pub unparsed_commits: RepoToCommits,
+}
+
+/// Stories and commits of the release which are breaking changes
+#[derive(Debug, Default, Serialize)]
+pub struct BreakingChanges {
+    /// Stories with the breaking label or a breaking commit
+    pub stories: Vec<ReleaseStory>,
+    /// Commits marked as breaking changes, by repository
+    pub commits: RepoToCommits,
+}
+
+#[derive(Debug, Default, Serialize)]
 pub struct Release<'a> {
     pub name: Option<&'a str>,
     pub version: Option<&'a str>,
     pub description: Option<&'a str>,
-    pub stories: Vec<Story>,
-    pub epics: Vec<Epic>,
+    /// Date of the release, as `YYYY-MM-DD`
+    pub date: String,
+    pub stories: Vec<ReleaseStory>,
+    /// Stories referenced by commits which don't exist, are archived or can't be accessed
+    pub missing_stories: Vec<MissingStory>,
+    /// Stories grouped by the name of their team
+    pub stories_by_team: BTreeMap<String, Vec<ReleaseStory>>,
+    /// Sum of the estimates of the stories
+    pub points: i64,
+    /// Sum of the estimates of the stories by the name of their team
+    pub points_by_team: BTreeMap<String, i64>,
+    /// Stories grouped by the name of their iteration
+    pub stories_by_iteration: BTreeMap<String, Vec<ReleaseStory>>,
+    pub breaking_changes: BreakingChanges,
+    /// Stories fixing security issues, according to their label
+    pub security_stories: Vec<ReleaseStory>,
+    /// Other stories grouped in the configured sections, by label
+    pub sections: Vec<ReleaseSection>,
+    /// Other stories without the label of any configured section
+    pub unsectioned_stories: Vec<ReleaseStory>,
+    pub epics: Vec<ReleaseEpic>,
+    /// Objectives (milestones) of the epics, along with their epics and stories
+    pub objectives: Vec<ReleaseObjective>,
+    pub iterations: Vec<Iteration>,
     pub unparsed_commits: RepoToCommits,
+    /// Unparsed commits grouped by Conventional Commits type
+    pub unparsed_commits_by_type: HashMap<String, RepoToCommits>,
+    /// Unparsed commits grouped by owner, according to the `CODEOWNERS` of the repositories
+    pub unparsed_commits_by_owner: HashMap<String, RepoToCommits>,
+    /// Heads of the primary next branches
     pub next_heads: RepoToHeadCommit,
+    /// Heads of all the next branches, the primary one first
+    pub next_branch_heads: RepoToHeadCommits,
+    pub merge_bases: RepoToMergeBase,
+    /// Commits present in the release branch but not the next branch
+    pub missing_in_next: RepoToCommits,
+    pub diff_stats: RepoToDiffStats,
+    pub signature_stats: RepoToSignatureStats,
+    /// Authors and co-authors of all the unreleased commits
+    pub contributors: Vec<Contributor>,
+    /// Steps of the promotion pipeline, from the most released environment
+    pub promotion_steps: Vec<PromotionStep>,
 }
 
+/// Read the API token of each workspace
+fn workspace_api_keys(
+    config: &AppConfig,
+    workspaces: &HashSet<WorkspaceName>,
+    args: &ReleaseArgs,
+) -> Result<HashMap<WorkspaceName, ShortcutApiKey>> {
+    let default_api_key = if let Some(token_file) = &args.token_file {
+        Some(token::read_token_file(token_file)?)
+    } else if args.token_stdin {
+        if args.commits_from.as_deref() == Some(Path::new("-")) {
+            bail!("The token and the commit list can't both be read from the standard input");
+        }
+        Some(token::read_token_stdin()?)
+    } else {
+        None
+    };
+    workspaces
+        .iter()
+        .map(|workspace| {
+            let api_key = match &default_api_key {
+                Some(api_key) if *workspace == WorkspaceName::default() => api_key.clone(),
+                _ => token::read_api_key(workspace, &config.workspace(workspace))?,
+            };
+            Ok((workspace.clone(), api_key))
+        })
+        .collect()
+}
+
+/// The options of the Shortcut clients, from the configuration
+fn shortcut_options(config: &AppConfig) -> ShortcutOptions {
+    ShortcutOptions {
+        concurrency: config.shortcut_concurrency,
+        max_attempts: config.shortcut_max_attempts,
+        requests_per_minute: config.shortcut_requests_per_minute,
+        api_url: var("SHORTCUT_API_URL")
+            .ok()
+            .or_else(|| config.shortcut_api_url.clone()),
+        proxy: config.proxy.clone(),
+        archived: config.archived,
+    }
+}
+
+/// Create the Shortcut client of each workspace, reading from Shortcut, the cache or the replayed
+/// fixtures depending on the arguments
+fn shortcut_workspaces(
+    config: &AppConfig,
+    args: &ReleaseArgs,
+    workspaces: HashSet<WorkspaceName>,
+    api_keys: &HashMap<WorkspaceName, ShortcutApiKey>,
+) -> Result<ShortcutWorkspaces> {
+    let cache_mode = if args.offline {
+        CacheMode::Offline
+    } else if args.no_cache {
+        CacheMode::Disabled
+    } else if args.refresh {
+        CacheMode::Refresh
+    } else {
+        CacheMode::Enabled
+    };
+    let shortcut_options = shortcut_options(config);
+    let mut replayed_fixtures = args.replay.as_deref().map(read_fixtures).transpose()?;
+    let mut shortcut_clients = HashMap::new();
+    for workspace in workspaces {
+        let shortcut_cache = ShortcutCache::new(
+            &workspace,
+            cache_mode,
+            Duration::from_secs(config.shortcut_cache_ttl_minutes * 60),
+        )?;
+        let shortcut_options = shortcut_options.clone();
+        let shortcut_client = match (api_keys.get(&workspace), &mut replayed_fixtures) {
+            (_, Some(replayed_fixtures)) => ShortcutClient::replay(
+                shortcut_options,
+                shortcut_cache,
+                replayed_fixtures.remove(&workspace).unwrap_or_default(),
+            ),
+            (Some(api_key), None) => {
+                ShortcutClient::new(api_key, shortcut_options, shortcut_cache)?
+            }
+            (None, None) => ShortcutClient::offline(shortcut_options, shortcut_cache),
+        };
+        let shortcut_client = if args.record.is_some() {
+            shortcut_client.record()
+        } else {
+            shortcut_client
+        };
+        shortcut_clients.insert(workspace, shortcut_client);
+    }
+    let repository_workspaces = config
+        .repositories
+        .iter()
+        .map(|(repo_name, repo_config)| (repo_name.clone(), repo_config.workspace.clone()))
+        .collect();
+    Ok(ShortcutWorkspaces::new(
+        shortcut_clients,
+        repository_workspaces,
+    ))
+}
+
+/// The command-line arguments, inserting the generate command when none is given, so that
+/// running the tool without subcommand keeps generating the release notes
+fn args_with_default_command() -> Vec<OsString> {
+    let mut args = env::args_os().collect::<Vec<_>>();
+    let cli_command = Cli::command();
+    let global_args = cli_command
+        .get_arguments()
+        .filter(|arg| arg.is_global_set())
+        .collect::<Vec<_>>();
+    // The global options, like -v or --config <PATH>, may come before the command
+    let mut remaining_args = args.iter().skip(1).map(|arg| arg.to_string_lossy());
+    let first_arg = loop {
+        let Some(arg) = remaining_args.next() else {
+            break None;
+        };
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            let global_arg = global_args
+                .iter()
+                .find(|global_arg| global_arg.get_long() == Some(name));
+            match global_arg {
+                Some(global_arg) if global_arg.get_action().takes_values() && value.is_none() => {
+                    remaining_args.next();
+                    continue;
+                }
+                Some(_) => continue,
+                None => break Some(arg),
+            }
+        }
+        let is_global_shorts = arg.len() > 1
+            && arg.strip_prefix('-').is_some_and(|shorts| {
+                shorts.chars().all(|short| {
+                    global_args
+                        .iter()
+                        .any(|global_arg| global_arg.get_short() == Some(short))
+                })
+            });
+        if !is_global_shorts {
+            break Some(arg);
+        }
+    };
+    // Without any argument, the help lists the commands
+    let has_command = match first_arg {
+        Some(arg) => {
+            matches!(arg.as_ref(), "help" | "-h" | "--help")
+                || cli_command.find_subcommand(arg.as_ref()).is_some()
+        }
+        None => true,
+    };
+    if !has_command {
+        args.insert(1, OsString::from("generate"));
+    }
+    args
+}
+
+/// Prompt for the version and name of the release when they aren't given, unless prompting is
+/// disabled or impossible. The version defaults to the next minor version after the highest one
+/// of the baseline manifests, or of the last manifest written without any, and the name to
+/// `Release <version>`. The name isn't prompted for with the `interactive` review, which edits it
+/// later on.
+fn prompt_missing_metadata(
+    args: &mut MetadataArgs,
+    interactive: bool,
+    baselines: &[(PathBuf, Manifest)],
+) -> Result<()> {
+    let reads_stdin =
+        args.release.token_stdin || args.release.commits_from.as_deref() == Some(Path::new("-"));
+    if args.no_input || reads_stdin || !io::stdin().is_terminal() {
+        return Ok(());
+    }
+    if args.version.is_none() {
+        let last_manifest = if baselines.is_empty() {
+            Manifest::read_last()
+        } else {
+            None
+        };
+        let previous_version = baselines
+            .iter()
+            .map(|(_, baseline)| baseline)
+            .chain(&last_manifest)
+            .filter_map(|manifest| {
+                let version = manifest.version.as_deref()?;
+                semver::Version::parse(version.trim_start_matches('v')).ok()
+            })
+            .max();
+        let next_version = previous_version
+            .map(|version| semver::Version::new(version.major, version.minor + 1, 0).to_string());
+        args.version = prompt_default("Version of the release", next_version)?;
+    }
+    if args.name.is_none() && !interactive {
+        let default_name = args
+            .version
+            .as_ref()
+            .map(|version| format!("Release {version}"));
+        args.name = prompt_default("Name of the release", default_name)?;
+    }
+    Ok(())
+}
+
+/// Exit code when the release has some of the gaps of --fail-on, to tell them from the errors
+const GAPS_EXIT_CODE: u8 = 3;
+
+/// Error of a release with some of the gaps of --fail-on
+#[derive(Debug, Display)]
+#[display(fmt = "The release has gaps: {_0}")]
+struct GapsError(String);
+
+impl std::error::Error for GapsError {}
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match try_main().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            if err.is::<GapsError>() {
+                ExitCode::from(GAPS_EXIT_CODE)
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+async fn try_main() -> Result<()> {
     let _ = dotenvy::dotenv().ok();
-    tracing_subscriber::fmt::init();
-    let args = Args::parse();
-    let api_key = ShortcutApiKey::new(var("SHORTCUT_TOKEN").map_err(|err| match err {
-        VarError::NotPresent => anyhow!("Missing SHORTCUT_TOKEN environment variable. Please provide it in a .env file or set it in your environment."),
-        VarError::NotUnicode(_) => err.into(),
-    })?);
-    let config = AppConfig::parse(&PathBuf::from("config.toml"))?;
-    let template_content = fs::read_to_string(&config.template_file)?;
-    let template = template::FileTemplate::new(&template_content)?;
-    let repo_names_and_heads_and_commits = futures::future::try_join_all(
-        config.repositories.into_iter().map(|(name, repo_config)| {
-            tokio::task::spawn_blocking::<_, Result<_>>(move || {
-                let commits = find_unreleased_commits(&name, &repo_config)?;
-                Ok((name, commits.next_head, commits.unreleased_commits))
-            })
-        }),
-    )
-    .await?;
-    let next_heads = repo_names_and_heads_and_commits
+    let mut cli = Cli::parse_from(args_with_default_command());
+    let release_args = match &cli.command {
+        Command::Generate(args) => Some(&args.metadata.release),
+        Command::Summary(args) => Some(args.as_ref()),
+        Command::Publish(args) => Some(&args.metadata.release),
+        _ => None,
+    };
+    let summary_json_stdout = release_args
+        .and_then(|args| args.summary_json.as_deref())
+        .is_some_and(|summary_json| summary_json == Path::new("-"));
+    let notes_stdout = match &cli.command {
+        Command::Generate(args) => args.output_file().is_none(),
+        _ => false,
+    };
+    if summary_json_stdout && notes_stdout {
+        bail!("The release notes and the summary can't both be written to the standard output");
+    }
+    if summary_json_stdout || notes_stdout {
+        output::use_stderr();
+    }
+    output::init_logs(cli.verbose, cli.quiet);
+    let baselines = release_args
+        .into_iter()
+        .flat_map(|args| &args.baseline)
+        .map(|baseline_path| Ok((baseline_path.clone(), Manifest::read(baseline_path)?)))
+        .collect::<Result<Vec<_>>>()?;
+    match &mut cli.command {
+        Command::Generate(args) => {
+            prompt_missing_metadata(&mut args.metadata, args.interactive, &baselines)?
+        }
+        Command::Publish(args) => prompt_missing_metadata(&mut args.metadata, false, &baselines)?,
+        _ => {}
+    }
+    let config_path = cli.config_path();
+    match &cli.command {
+        Command::Generate(args) => {
+            let release_args = &args.metadata.release;
+            run(
+                release_args,
+                &baselines,
+                Some(args),
+                None,
+                &config_path,
+                cli.dry_run,
+            )
+            .await
+        }
+        Command::Summary(args) => {
+            run(args, &baselines, None, None, &config_path, cli.dry_run).await
+        }
+        Command::Publish(args) => {
+            let release_args = &args.metadata.release;
+            run(
+                release_args,
+                &baselines,
+                None,
+                Some(args),
+                &config_path,
+                cli.dry_run,
+            )
+            .await
+        }
+        Command::Check => check(&config_path),
+        Command::Doctor => doctor(&config_path).await,
+        Command::Completions { shell } => {
+            clap_complete::generate(
+                *shell,
+                &mut Cli::command(),
+                env!("CARGO_BIN_NAME"),
+                &mut io::stdout(),
+            );
+            Ok(())
+        }
+        Command::SelfUpdate => {
+            let api_key = var("GITHUB_TOKEN").ok().map(GithubApiKey::new);
+            self_update::self_update(api_key, cli.dry_run).await
+        }
+    }
+}
+
+/// Fail with a [`GapsError`] when the release has any of the gaps of --fail-on, or of all of them
+/// with --strict
+fn check_gaps(args: &ReleaseArgs, summary: &ReleaseSummary) -> Result<()> {
+    let gaps = if args.strict {
+        ReleaseGap::ALL.as_slice()
+    } else {
+        &args.fail_on
+    };
+    let failed_gaps = gaps
         .iter()
-        .map(|repo_name_and_head_and_commit| {
-            let (repo_name, next_head, _commits) = repo_name_and_head_and_commit
-                .as_ref()
-                .map_err(|err| anyhow!("{:?}", err))?;
-            Ok((repo_name.clone(), next_head.clone()))
+        .unique()
+        .filter_map(|gap| {
+            let count = match gap {
+                ReleaseGap::UnparsedCommits => summary.unparsed_commits.values().sum(),
+                ReleaseGap::NotDoneStories => summary.not_done_stories,
+                ReleaseGap::MissingStories => summary.missing_stories,
+            };
+            (count > 0).then(|| format!("{count} {gap}"))
         })
-        .collect::<Result<HashMap<_, _>>>()?;
-    let repo_names_and_commits = repo_names_and_heads_and_commits
-        .into_iter()
-        .map_ok(|(repo_name, _next_head, commits)| (repo_name, commits))
-        .collect::<Result<HashMap<_, _>>>()?;
-    let exclude_story_ids = HashSet::from_iter(args.exclude_story_id.iter().copied());
-    let parsed_commits = parse_commits(repo_names_and_commits, &exclude_story_ids)?;
-    debug!("Got result {:?}", parsed_commits);
-    let shortcut_client = ShortcutClient::new(&api_key);
-    let release_content = shortcut_client
+        .collect_vec();
+    if !failed_gaps.is_empty() {
+        return Err(GapsError(failed_gaps.join(", ")).into());
+    }
+    Ok(())
+}
+
+/// Check that the configuration is valid, that the release and next references of every
+/// repository resolve, and that the template renders a synthetic release, printing the result of
+/// each check. Shortcut is never reached, though remote repositories are cloned or updated.
+fn check(config_path: &Path) -> Result<()> {
+    let config = parse_config(config_path)?;
+    message!("{} {config_path:?}", Green.paint("ok"));
+    let failed_count = check_repositories(&config) + check_template(&config);
+    if failed_count > 0 {
+        bail!("{failed_count} checks failed");
+    }
+    Ok(())
+}
+
+/// Diagnose the environment: run the checks of the check command, and also check the cache
+/// directory, that Shortcut and GitHub can be reached and that the Shortcut tokens are valid
+async fn doctor(config_path: &Path) -> Result<()> {
+    let config = parse_config(config_path)?;
+    message!("{} {config_path:?}", Green.paint("ok"));
+    let mut failed_count = usize::from(print_check(
+        "cache directory",
+        config::check_cache_dir().map(|cache_dir| format!("{cache_dir:?}")),
+    ));
+    let shortcut_options = shortcut_options(&config);
+    let workspaces = config
+        .repositories
+        .values()
+        .map(|repo_config| repo_config.workspace.clone())
+        .collect::<HashSet<_>>();
+    for workspace in workspaces.into_iter().sorted_by_key(ToString::to_string) {
+        let workspace_config = config.workspace(&workspace);
+        let client = token::read_api_key(&workspace, &workspace_config).and_then(|api_key| {
+            let shortcut_cache =
+                ShortcutCache::new(&workspace, CacheMode::Disabled, Duration::ZERO)?;
+            ShortcutClient::new(&api_key, shortcut_options.clone(), shortcut_cache)
+        });
+        let client = match client {
+            Ok(client) => client,
+            Err(err) => {
+                failed_count += usize::from(print_check(&format!("{workspace} token"), Err(err)));
+                continue;
+            }
+        };
+        let reachable = client.check_reachable().await;
+        let is_reachable = reachable.is_ok();
+        failed_count += usize::from(print_check(
+            &format!("{workspace} Shortcut API"),
+            reachable.map(|()| String::new()),
+        ));
+        if !is_reachable {
+            continue;
+        }
+        let member = client
+            .check_token(workspace_config.url_slug.as_deref())
+            .await
+            .map(|member| match member {
+                Some(member) => format!(
+                    "{} (@{}) in the Shortcut workspace {}",
+                    member.name, member.mention_name, member.workspace2.url_slug
+                ),
+                None => String::new(),
+            });
+        failed_count += usize::from(print_check(&format!("{workspace} token"), member));
+    }
+    if config
+        .repositories
+        .values()
+        .any(|repo_config| repo_config.github_repository.is_some())
+    {
+        let github_client = GithubClient::new(var("GITHUB_TOKEN").ok().map(GithubApiKey::new))?;
+        let reachable = github_client.check_reachable().await;
+        failed_count += usize::from(print_check("GitHub API", reachable.map(|()| String::new())));
+    }
+    failed_count += check_repositories(&config) + check_template(&config);
+    if failed_count > 0 {
+        bail!("{failed_count} checks failed");
+    }
+    Ok(())
+}
+
+/// Parse the configuration, also compiling its patterns
+fn parse_config(config_path: &Path) -> Result<AppConfig> {
+    let config = AppConfig::parse(config_path)?;
+    StoryIdParser::new(
+        &config.branch_story_id_pattern,
+        &config.story_trailers,
+        &config.repositories,
+    )?;
+    for pattern in &config.exclude_commit_patterns {
+        Regex::new(pattern)?;
+    }
+    Ok(config)
+}
+
+/// Print the result of a check of the check or doctor commands along with its details, returning
+/// whether it failed
+fn print_check(name: &str, result: Result<String>) -> bool {
+    match result {
+        Ok(details) if details.is_empty() => message!("{} {name}", Green.paint("ok")),
+        Ok(details) => message!("{} {name}: {details}", Green.paint("ok")),
+        Err(err) => {
+            message!("{} {name}: {err:#}", Red.paint("error"));
+            return true;
+        }
+    }
+    false
+}
+
+/// Check that every repository can be opened and that its release and next references resolve,
+/// returning the number of failed checks
+fn check_repositories(config: &AppConfig) -> usize {
+    let repositories = config
+        .repositories
+        .iter()
+        .sorted_by_key(|(repo_name, _)| repo_name.to_string());
+    let mut failed_count = 0;
+    for (repo_name, repo_config) in repositories {
+        let references = Repository::new(repo_config, GitOptions::default())
+            .context("Could not open the repository, check its location and access")
+            .and_then(|repo| {
+                Ok(repo
+                    .resolve_references()?
+                    .into_iter()
+                    .map(|(reference, id)| format!("{reference} @ {}", &id.to_string()[..7]))
+                    .join(", "))
+            });
+        failed_count += usize::from(print_check(repo_name.as_ref(), references));
+    }
+    failed_count
+}
+
+/// Check that the template renders a synthetic release, returning the number of failed checks
+fn check_template(config: &AppConfig) -> usize {
+    let template_file = &config.template_file;
+    let rendered = fs::read_to_string(template_file)
+        .map_err(anyhow::Error::from)
+        .and_then(|template_content| {
+            template::FileTemplate::new(&template_content)?.render(&synthetic_release())
+        });
+    usize::from(print_check(
+        &format!("{template_file:?}"),
+        rendered.map(|_| String::new()),
+    ))
+}
+
+/// An empty release, to check that the template renders
+fn synthetic_release<'a>() -> Release<'a> {
+    Release {
+        name: Some("Check"),
+        version: Some("0.0.0"),
+        description: Some("Release rendered by the check command"),
+        date: Local::now().date_naive().format("%Y-%m-%d").to_string(),
+        ..Release::default()
+    }
+}
+
+/// Find the release, excluding the content of the `baselines` manifests, and print its summary,
+/// then generate its release notes when given the arguments of the generate command, or publish
+/// it when given the ones of the publish command. With `dry_run`, nothing is written nor changed,
+/// only printed.
+async fn run(
+    args: &ReleaseArgs,
+    baselines: &[(PathBuf, Manifest)],
+    generate_args: Option<&GenerateArgs>,
+    publish_args: Option<&PublishArgs>,
+    config_path: &Path,
+    dry_run: bool,
+) -> Result<()> {
+    let mut config = AppConfig::parse(config_path)?;
+    select_repositories(&mut config.repositories, &args.repos)?;
+    let metadata = generate_args
+        .map(|generate_args| &generate_args.metadata)
+        .or(publish_args.map(|publish_args| &publish_args.metadata));
+    let publishes = |target| publish_args.is_some_and(|args| args.targets.contains(&target));
+    if let Some(generate_args) = generate_args {
+        let reads_stdin = args.token_stdin || args.commits_from.as_deref() == Some(Path::new("-"));
+        if generate_args.interactive && reads_stdin {
+            bail!("The interactive review can't be combined with reading the standard input");
+        }
+        if generate_args.format == OutputFormat::Json && generate_args.tag.is_some() {
+            bail!("Tagging the repositories requires the markdown format");
+        }
+    }
+    // The publish targets are checked before the git work
+    if publishes(PublishTarget::WorkflowState) && config.released_workflow_states.is_empty() {
+        bail!("Moving the stories requires the released_workflow_states configuration");
+    }
+    let version_field = match publish_args {
+        Some(publish_args) if publishes(PublishTarget::VersionField) => {
+            let field_name = config.version_custom_field.clone().ok_or_else(|| {
+                anyhow!(
+                    "Setting the version of the stories requires the version_custom_field \
+                    configuration"
+                )
+            })?;
+            let version =
+                publish_args.metadata.version.clone().ok_or_else(|| {
+                    anyhow!("Setting the version of the stories requires --version")
+                })?;
+            Some((field_name, version))
+        }
+        _ => None,
+    };
+    let label = match publish_args {
+        Some(publish_args) if publishes(PublishTarget::Label) => Some(release_label(
+            &publish_args.label,
+            publish_args.metadata.version.as_deref(),
+        )?),
+        _ => None,
+    };
+    // The epic or iteration of the release is named after it
+    let container_name = match publish_args {
+        Some(publish_args) if publishes(PublishTarget::Shortcut) => {
+            let name = publish_args.metadata.release_name().ok_or_else(|| {
+                anyhow!("Publishing the release to Shortcut requires --name or --version")
+            })?;
+            Some((publish_args.container, name))
+        }
+        _ => None,
+    };
+    let doc_title = match publish_args {
+        Some(publish_args) if publishes(PublishTarget::Doc) => Some(
+            publish_args
+                .doc_title
+                .clone()
+                .or_else(|| publish_args.metadata.release_name())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Publishing the release notes requires --doc-title, --name or --version"
+                    )
+                })?,
+        ),
+        _ => None,
+    };
+    let mut workspaces = config
+        .repositories
+        .values()
+        .map(|repo_config| repo_config.workspace.clone())
+        .collect::<HashSet<_>>();
+    if args.commits_from.is_some() {
+        // The listed commits may belong to repositories which aren't configured
+        workspaces.insert(WorkspaceName::default());
+    }
+    let api_keys = if args.offline || args.replay.is_some() {
+        HashMap::new()
+    } else {
+        workspace_api_keys(&config, &workspaces, args)?
+    };
+    let shortcut_workspaces = shortcut_workspaces(&config, args, workspaces, &api_keys)?;
+    // Fail fast on an invalid token, before the git work
+    shortcut_workspaces.check_tokens(&config.workspaces).await?;
+    override_references(&mut config.repositories, args)?;
+    // Only the Markdown release notes need the template, read before the git work too
+    let renders_markdown = generate_args
+        .is_some_and(|generate_args| generate_args.format == OutputFormat::Markdown)
+        || doc_title.is_some();
+    let template_content = renders_markdown
+        .then(|| fs::read_to_string(&config.template_file))
+        .transpose()?;
+    let template = template_content
+        .as_deref()
+        .map(template::FileTemplate::new)
+        .transpose()?;
+    let github_repositories = config
+        .repositories
+        .iter()
+        .filter_map(|(name, repo_config)| {
+            let github_repository = repo_config.github_repository.clone()?;
+            Some((name.clone(), github_repository))
+        })
+        .collect::<HashMap<_, _>>();
+    let git_options = GitOptions {
+        fetch: args.fetch,
+        with_diffstat: args.with_diffstat,
+        verify_signatures: args.verify_signatures,
+        isolated: args.isolated,
+    };
+    let repositories = config.repositories.clone();
+    // A pre-computed commit list bypasses git entirely, including the promotion pipeline
+    let promotion_pipeline = match &args.commits_from {
+        Some(_) if !config.promotion_pipeline.is_empty() => {
+            warn!("Skipping the promotion pipeline, as the commits are read from a list");
+            &[]
+        }
+        Some(_) => &[],
+        None => config.promotion_pipeline.as_slice(),
+    };
+    let step_count = promotion_pipeline.len().saturating_sub(1);
+    let mut step_commits = vec![RepoToCommits::new(); step_count];
+    let mut next_heads = HashMap::new();
+    let mut next_branch_heads = HashMap::new();
+    let mut merge_bases = HashMap::new();
+    let mut missing_in_next = HashMap::new();
+    let mut repo_names_and_commits = HashMap::new();
+    let mut exclude_story_ids = HashSet::from_iter(args.exclude_story_id.iter().copied());
+    let all_unreleased_commits = match &args.commits_from {
+        Some(commits_from) => {
+            repo_names_and_commits = commit_list::read_commits(commits_from)?;
+            Vec::new()
+        }
+        None => {
+            find_all_unreleased_commits(config.repositories, git_options, promotion_pipeline)
+                .await?
+        }
+    };
+    for (repo_name, mut unreleased_commits) in all_unreleased_commits {
+        let promoted_commits = mem::take(&mut unreleased_commits.promoted_commits);
+        for (step_commits, promoted_commits) in step_commits.iter_mut().zip(promoted_commits) {
+            step_commits.insert(repo_name.clone(), promoted_commits);
+        }
+        if let Some(next_head) = unreleased_commits.next_heads.first() {
+            next_heads.insert(repo_name.clone(), next_head.clone());
+        }
+        next_branch_heads.insert(repo_name.clone(), unreleased_commits.next_heads);
+        merge_bases.insert(repo_name.clone(), unreleased_commits.merge_base);
+        missing_in_next.insert(repo_name.clone(), unreleased_commits.missing_in_next);
+        exclude_story_ids.extend(unreleased_commits.ignored_story_ids);
+        repo_names_and_commits.insert(repo_name, unreleased_commits.unreleased_commits);
+    }
+    for (baseline_path, baseline) in baselines {
+        let excluded_commits = baseline.exclude_commits(&mut repo_names_and_commits);
+        info!("Excluded {excluded_commits} commits released in {baseline_path:?}");
+        exclude_story_ids.extend(&baseline.story_ids);
+    }
+    if args.offline && !github_repositories.is_empty() {
+        warn!("Skipping the pull requests, as GitHub isn't reached offline");
+    } else if !github_repositories.is_empty() {
+        let github_client = GithubClient::new(var("GITHUB_TOKEN").ok().map(GithubApiKey::new))?;
+        for (repo_name, github_repository) in &github_repositories {
+            if let Some(commits) = repo_names_and_commits.get_mut(repo_name) {
+                github_client
+                    .add_pull_requests(github_repository, commits)
+                    .await?;
+            }
+        }
+    }
+    let diff_stats = if args.with_diffstat {
+        repo_names_and_commits
+            .iter()
+            .map(|(repo_name, commits)| {
+                let mut diff_stats = DiffStats::default();
+                for commit_diff_stats in commits.iter().filter_map(|commit| commit.diff_stats) {
+                    diff_stats += commit_diff_stats;
+                }
+                (repo_name.clone(), diff_stats)
+            })
+            .collect()
+    } else {
+        RepoToDiffStats::new()
+    };
+    let signature_stats: RepoToSignatureStats = if args.verify_signatures {
+        repo_names_and_commits
+            .iter()
+            .map(|(repo_name, commits)| {
+                let mut signature_stats = SignatureStats::default();
+                for signature in commits.iter().filter_map(|commit| commit.signature) {
+                    signature_stats.add(signature);
+                }
+                (repo_name.clone(), signature_stats)
+            })
+            .collect()
+    } else {
+        RepoToSignatureStats::new()
+    };
+    let contributors = contributors::contributors(&repo_names_and_commits);
+    let mut manifest = Manifest {
+        name: metadata.and_then(|metadata| metadata.name.clone()),
+        version: metadata.and_then(|metadata| metadata.version.clone()),
+        ..Manifest::default()
+    };
+    let story_id_parser = StoryIdParser::new(
+        &config.branch_story_id_pattern,
+        &config.story_trailers,
+        &repositories,
+    )?;
+    let exclude_commit_patterns = config
+        .exclude_commit_patterns
+        .iter()
+        .map(|pattern| Regex::new(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    let parse_workspace_commits = |commits| {
+        let parsed_commits = parse_commits(
+            commits,
+            &exclude_story_ids,
+            &exclude_commit_patterns,
+            &story_id_parser,
+        )?;
+        debug!("Got result {:?}", parsed_commits);
+        Ok(parsed_commits)
+    };
+    let story_filter = StoryFilter::new(&args.exclude_story_label, &args.include_story_label)
+        .with_states(
+            &args.exclude_story_state,
+            &args.include_story_state,
+            args.done_stories_only,
+        )
+        .with_types(&args.exclude_story_type, &args.include_story_type)
+        .with_teams(&args.exclude_team, &args.include_team)
+        .with_epics(&args.exclude_epic_id, &args.include_epic_id)
+        .with_projects(&args.exclude_project, &args.include_project)
+        .with_iterations(&args.iteration);
+    let include_unparsed_commits = !args.exclude_unparsed_commits;
+    let release_date = args.date.unwrap_or_else(|| Local::now().date_naive());
+    let mut release_content = shortcut_workspaces
         .get_release(
-            parsed_commits,
-            StoryLabelFilter::new(&args.exclude_story_label, &args.include_story_label),
+            repo_names_and_commits,
+            parse_workspace_commits,
+            &story_filter,
         )
         .await?;
-    print_summary(&release_content);
-    let include_unparsed_commits = !args.exclude_unparsed_commits;
+    release_content.flag_overdue(release_date);
+    release_content.flag_breaking(&config.breaking_label);
+    release_content.flag_unreleased_repositories(&github_repositories);
+    if let Some(limit) = args.suggest_stories.filter(|_| include_unparsed_commits) {
+        shortcut_workspaces
+            .suggest_stories(&mut release_content.unparsed_commits, limit)
+            .await?;
+    }
+    let mut promotion_steps = Vec::new();
+    let environments = promotion_pipeline.windows(2);
+    for (environments, step_commits) in environments.zip(step_commits) {
+        let (released, unreleased) = (&environments[0], &environments[1]);
+        let mut step_content = shortcut_workspaces
+            .get_release(step_commits, parse_workspace_commits, &story_filter)
+            .await?;
+        step_content.flag_overdue(release_date);
+        step_content.flag_breaking(&config.breaking_label);
+        promotion_steps.push(PromotionStep {
+            from: unreleased.clone(),
+            to: released.clone(),
+            stories: step_content.stories,
+            epics: step_content.epics,
+            unparsed_commits: if include_unparsed_commits {
+                step_content.unparsed_commits
+            } else {
+                RepoToCommits::new()
+            },
+        });
+    }
+    if let Some(record_path) = &args.record {
+        if dry_run {
+            message!("Would record the Shortcut responses to {record_path:?}");
+        } else if let Some(fixtures) = shortcut_workspaces.take_recorded() {
+            write_fixtures(&fixtures, record_path)?;
+        }
+    }
+    let summary = ReleaseSummary::new(&release_content, &missing_in_next, &config.security_label);
+    print_summary(&summary, &release_content);
+    print_not_done_summary(&release_content.stories);
+    print_unreleased_repositories_summary(&release_content.stories);
+    print_promotion_summary(&promotion_steps);
+    print_signature_summary(&signature_stats);
+    let mut unparsed_commits = if include_unparsed_commits {
+        mem::take(&mut release_content.unparsed_commits)
+    } else {
+        RepoToCommits::new()
+    };
+    let mut unparsed_commits_by_owner = codeowners::group_by_owner(&unparsed_commits);
+    print_owner_summary(&unparsed_commits_by_owner);
+    if let Some(summary_json) = &args.summary_json {
+        write_summary_json(&summary, summary_json, dry_run)?;
+    }
+    check_gaps(args, &summary)?;
+    let Some(metadata) = metadata else {
+        return Ok(());
+    };
+    let mut details = ReleaseDetails {
+        name: metadata.name.clone(),
+        description: metadata.description.clone(),
+    };
+    if generate_args.is_some_and(|generate_args| generate_args.interactive) {
+        review::review(&mut release_content, &mut unparsed_commits, &mut details)?;
+        unparsed_commits_by_owner = codeowners::group_by_owner(&unparsed_commits);
+        manifest.name = details.name.clone();
+    }
+    let not_done_stories = release_content
+        .stories
+        .iter()
+        .filter(|story| !story.workflow_state_done)
+        .count();
+    if metadata.require_done && not_done_stories > 0 {
+        bail!("{not_done_stories} stories of the release aren't done");
+    }
+    let breaking_changes = BreakingChanges {
+        stories: release_content
+            .stories
+            .iter()
+            .filter(|story| story.breaking)
+            .cloned()
+            .collect(),
+        commits: release_content.breaking_commits,
+    };
+    let (security_stories, other_stories) =
+        shortcut::split_by_label(&release_content.stories, &config.security_label);
+    let (sections, unsectioned_stories) =
+        shortcut::group_by_section(&config.sections, &other_stories);
     let release = Release {
-        name: args.name.as_deref(),
-        version: args.version.as_deref(),
-        description: args.description.as_deref(),
+        name: details.name.as_deref(),
+        version: metadata.version.as_deref(),
+        description: details.description.as_deref(),
+        date: release_date.format("%Y-%m-%d").to_string(),
+        stories_by_team: shortcut::group_by_team(&release_content.stories),
+        points: shortcut::total_points(&release_content.stories),
+        points_by_team: shortcut::points_by_team(&release_content.stories),
+        stories_by_iteration: shortcut::group_by_iteration(&release_content.stories),
+        breaking_changes,
+        security_stories,
+        sections,
+        unsectioned_stories,
+        objectives: shortcut::group_by_objective(
+            &release_content.objectives,
+            &release_content.epics,
+            &release_content.stories,
+        ),
         stories: release_content.stories,
+        missing_stories: release_content.missing_stories,
         epics: release_content.epics,
-        unparsed_commits: include_unparsed_commits
-            .then_some(release_content.unparsed_commits)
-            .unwrap_or_default(),
+        iterations: release_content.iterations,
+        unparsed_commits_by_type: conventional_commits::group_by_type(&unparsed_commits),
+        unparsed_commits_by_owner,
+        unparsed_commits,
         next_heads,
+        next_branch_heads,
+        merge_bases,
+        missing_in_next,
+        diff_stats,
+        signature_stats,
+        contributors,
+        promotion_steps,
+    };
+    let release_notes = match &template {
+        Some(template) => template.render(&release)?,
+        None => serde_json::to_string_pretty(&release)?,
     };
-    template.render_to_file(&release, &args.output_file)?;
+    if let Some(generate_args) = generate_args {
+        match generate_args.output_file() {
+            Some(output_file) if dry_run => {
+                message!("Would write the release notes to {output_file:?}:\n\n{release_notes}");
+            }
+            Some(output_file) => fs::write(output_file, &release_notes)?,
+            None => print!("{release_notes}"),
+        }
+        if let Some(manifest_path) = &generate_args.manifest {
+            manifest.record_commits(&release.stories, &release.epics, &release.unparsed_commits);
+            manifest.story_ids = release
+                .stories
+                .iter()
+                .map(|story| u32::try_from(story.story.id).map(StoryId::from))
+                .collect::<Result<_, _>>()?;
+            if dry_run {
+                message!("Would write the manifest to {manifest_path:?}");
+            } else {
+                manifest.write(manifest_path)?;
+            }
+        }
+        if let Some(tag_name) = &generate_args.tag {
+            if dry_run {
+                message!("Would tag the next branch of every local repository as {tag_name}");
+            } else {
+                tag_repositories(&repositories, &release.next_heads, tag_name, &release_notes)?;
+            }
+        }
+    }
+    if let Some(label) = &label {
+        shortcut_workspaces
+            .apply_label(&release.stories, label, dry_run)
+            .await?;
+    }
+    if publishes(PublishTarget::Comment) {
+        shortcut_workspaces
+            .comment_stories(
+                &release.stories,
+                |story| release_comment(&release, story),
+                release.version.map(release_comment_marker).as_deref(),
+                dry_run,
+            )
+            .await?;
+    }
+    if let Some((field_name, version)) = &version_field {
+        shortcut_workspaces
+            .set_custom_field(&release.stories, field_name, version, dry_run)
+            .await?;
+    }
+    if let Some((container, name)) = &container_name {
+        shortcut_workspaces
+            .publish_container(&release.stories, *container, name, &release.date, dry_run)
+            .await?;
+    }
+    if let Some(title) = &doc_title {
+        shortcut_workspaces
+            .publish_doc(title, &release_notes, dry_run)
+            .await?;
+    }
+    if publishes(PublishTarget::WorkflowState) {
+        shortcut_workspaces
+            .move_stories(&release.stories, &config.released_workflow_states, dry_run)
+            .await?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[clap(flatten)]
+        release: ReleaseArgs,
+    }
+
+    fn check(args: &[&str], summary: &ReleaseSummary) -> Result<()> {
+        let cli = TestCli::try_parse_from(["test"].iter().chain(args)).unwrap();
+        check_gaps(&cli.release, summary)
+    }
+
+    #[test]
+    fn check_gaps_without_gaps() {
+        let summary = ReleaseSummary {
+            not_done_stories: 2,
+            ..Default::default()
+        };
+        assert!(check(&[], &summary).is_ok());
+        assert!(check(&["--strict"], &ReleaseSummary::default()).is_ok());
+        // Gaps counted as zero don't fail the release
+        let summary = ReleaseSummary {
+            unparsed_commits: BTreeMap::from([("dev".to_string(), 0)]),
+            ..Default::default()
+        };
+        assert!(check(&["--fail-on", "unparsed-commits"], &summary).is_ok());
+    }
+
+    #[test]
+    fn check_gaps_fails() {
+        let summary = ReleaseSummary {
+            not_done_stories: 2,
+            missing_stories: 1,
+            unparsed_commits: BTreeMap::from([("dev".to_string(), 3), ("legacy".to_string(), 1)]),
+            ..Default::default()
+        };
+        let err = check(
+            &[
+                "--fail-on",
+                "missing-stories,unparsed-commits",
+                "--fail-on",
+                "missing-stories",
+            ],
+            &summary,
+        )
+        .unwrap_err();
+        assert!(err.is::<GapsError>());
+        assert_eq!(
+            err.to_string(),
+            "The release has gaps: 1 missing-stories, 4 unparsed-commits"
+        );
+        let err = check(&["--strict"], &summary).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The release has gaps: 4 unparsed-commits, 2 not-done-stories, 1 missing-stories"
+        );
+        assert!(TestCli::try_parse_from(["test", "--fail-on", "unparsed"]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! This module records the content of a release in a manifest, so that a later run can exclude
+//! what was already released by using it as a baseline
+//!
+//! A copy of the last manifest written is recorded in the cache directory, so that the version of
+//! the next release can be suggested without a baseline.
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    config::cache_dir,
+    shortcut::{ReleaseEpic, ReleaseStory, StoryId},
+    types::{RepoToCommits, RepositoryName},
+};
+
+/// Path of the copy of the last manifest written
+fn last_manifest_path() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("last_manifest.json"))
+}
+
+/// Commits and stories of a release, written as JSON
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Ids of the commits of the release, by repository
+    pub commits: HashMap<RepositoryName, Vec<String>>,
+    /// Ids of the stories of the release
+    pub story_ids: Vec<StoryId>,
+}
+
+impl Manifest {
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read the manifest {path:?}"))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid manifest {path:?}"))
+    }
+
+    /// The last manifest written, if it was recorded
+    pub fn read_last() -> Option<Self> {
+        let path = last_manifest_path()
+            .inspect_err(|err| warn!("Could not find the last manifest: {err:?}"))
+            .ok()?;
+        if !path.exists() {
+            return None;
+        }
+        Self::read(&path)
+            .inspect_err(|err| warn!("Ignoring the last manifest: {err:?}"))
+            .ok()
+    }
+
+    /// Write the manifest, and record a copy as the last manifest
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, &content)
+            .with_context(|| format!("Could not write the manifest {path:?}"))?;
+        let recorded = last_manifest_path().and_then(|last_path| {
+            fs::create_dir_all(last_path.parent().unwrap_or(&last_path))?;
+            Ok(fs::write(last_path, &content)?)
+        });
+        if let Err(err) = recorded {
+            warn!("Could not record the last manifest: {err:?}");
+        }
+        Ok(())
+    }
+
+    /// Record the ids of the commits shipped in the release notes: the ones of the stories and
+    /// epics, and the unparsed commits. The commits left out of the release, eg of the excluded
+    /// stories, aren't recorded so that a later release using the manifest as a baseline keeps
+    /// them.
+    pub fn record_commits(
+        &mut self,
+        stories: &[ReleaseStory],
+        epics: &[ReleaseEpic],
+        unparsed_commits: &RepoToCommits,
+    ) {
+        let mut recorded_commits: HashMap<RepositoryName, Vec<String>> = HashMap::new();
+        let released_commits = stories
+            .iter()
+            .map(|story| &story.commits)
+            .chain(epics.iter().map(|epic| &epic.commits))
+            .chain([unparsed_commits]);
+        for repo_to_commits in released_commits {
+            for (repo_name, commits) in repo_to_commits {
+                let commit_ids = recorded_commits.entry(repo_name.clone()).or_default();
+                for commit in commits {
+                    let commit_id = commit.id.to_string();
+                    if !commit_ids.contains(&commit_id) {
+                        commit_ids.push(commit_id);
+                    }
+                }
+            }
+        }
+        self.commits = recorded_commits;
+    }
+
+    /// Remove the commits recorded in the manifest, returning how many were removed
+    pub fn exclude_commits(&self, commits: &mut RepoToCommits) -> usize {
+        let mut excluded_commits = 0;
+        for (repo_name, commits) in commits.iter_mut() {
+            let Some(released_commit_ids) = self.commits.get(repo_name) else {
+                continue;
+            };
+            let released_commit_ids = released_commit_ids.iter().collect::<HashSet<_>>();
+            let commit_count = commits.len();
+            commits.retain(|commit| !released_commit_ids.contains(&commit.id.to_string()));
+            excluded_commits += commit_count - commits.len();
+        }
+        excluded_commits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Oid as GitOid;
+
+    use super::*;
+    use crate::{shortcut::ReleaseContent, types::test_commit};
+
+    /// Commits of the `dev` repository, with ids made of the bytes
+    fn commits(id_bytes: &[u8]) -> RepoToCommits {
+        let commits = id_bytes
+            .iter()
+            .map(|id_byte| test_commit(*id_byte, 0, None))
+            .collect();
+        HashMap::from([(RepositoryName::new("dev".to_string()), commits)])
+    }
+
+    fn story(id: i64, commits: RepoToCommits) -> ReleaseStory {
+        let mut story = ReleaseStory {
+            commits,
+            ..Default::default()
+        };
+        story.story.id = id;
+        story
+    }
+
+    #[test]
+    fn record_released_commits() {
+        let mut release = ReleaseContent {
+            stories: vec![story(1, commits(&[1, 2])), story(2, commits(&[3]))],
+            unparsed_commits: commits(&[4, 2]),
+            ..Default::default()
+        };
+        release.exclude_stories(&HashSet::from([2]));
+        let mut manifest = Manifest::default();
+        manifest.record_commits(&release.stories, &release.epics, &release.unparsed_commits);
+        let commit_ids = [1u8, 2, 4].map(|id_byte| GitOid::from_bytes(&[id_byte; 20]).unwrap());
+        assert_eq!(
+            manifest.commits[&RepositoryName::new("dev".to_string())],
+            commit_ids.map(|commit_id| commit_id.to_string())
+        );
+        // The commits of the excluded story are kept for the next release
+        let mut next_commits = commits(&[1, 3, 5]);
+        assert_eq!(manifest.exclude_commits(&mut next_commits), 1);
+        assert_eq!(
+            next_commits[&RepositoryName::new("dev".to_string())].len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! This module prints the messages of the tool, like the summary of the release, and its logs on
+//! the standard output, or on the standard error once the standard output is reserved for the
+//! release notes
+use std::{
+    env::var,
+    io::{self, BufRead, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{bail, Result};
+
+use tracing::{warn, Level};
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::progress;
+
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Print the messages and logs on the standard error from now on
+pub fn use_stderr() {
+    TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub fn to_stderr() -> bool {
+    TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Print the logs allowed by `RUST_LOG` like the messages, or else the ones of the verbosity: the
+/// info ones per default, only the warnings and errors when `quiet`, and with each `verbose` level
+/// the debug ones of the tool, its trace ones, then the trace ones of all the crates. An invalid
+/// `RUST_LOG` is ignored with a warning.
+pub fn init_logs(verbose: u8, quiet: bool) {
+    let tool_target = env!("CARGO_CRATE_NAME");
+    let verbosity_targets = || match (quiet, verbose) {
+        (true, _) => Targets::new().with_default(Level::WARN),
+        (false, 0) => Targets::new().with_default(Level::INFO),
+        (false, 1) => Targets::new()
+            .with_target(tool_target, Level::DEBUG)
+            .with_default(Level::INFO),
+        (false, 2) => Targets::new()
+            .with_target(tool_target, Level::TRACE)
+            .with_default(Level::INFO),
+        (false, _) => Targets::new().with_default(Level::TRACE),
+    };
+    let (targets, parse_error) = match var("RUST_LOG").map(|targets| targets.parse::<Targets>()) {
+        Ok(Ok(targets)) => (targets, None),
+        Ok(Err(err)) => (verbosity_targets(), Some(err)),
+        Err(_) => (verbosity_targets(), None),
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(Level::TRACE)
+        .with_writer(writer)
+        .finish()
+        .with(targets)
+        .init();
+    if let Some(err) = parse_error {
+        warn!("Ignoring the invalid RUST_LOG, logging per the verbosity instead: {err}");
+    }
+}
+
+/// Writer of the messages and logs, clearing the progress bars while writing
+fn writer() -> impl Write {
+    ProgressWriter(if to_stderr() {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    })
+}
+
+struct ProgressWriter(Box<dyn Write>);
+
+impl Write for ProgressWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        progress::suspend(|| self.0.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        progress::suspend(|| self.0.write_all(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Print the prompt with the messages and read the answer from the standard input, trimmed
+pub fn prompt(text: &str) -> Result<String> {
+    let mut writer = writer();
+    write!(writer, "{text}: ")?;
+    writer.flush()?;
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        bail!("The standard input was closed while waiting for an answer");
+    }
+    Ok(answer.trim().to_owned())
+}
+
+/// Prompt for a value, keeping the default one on an empty answer, or clearing it on `-`
+pub fn prompt_default(text: &str, default: Option<String>) -> Result<Option<String>> {
+    let answer = match &default {
+        Some(default) => prompt(&format!("{text} [{default}]"))?,
+        None => prompt(text)?,
+    };
+    Ok(match answer.as_str() {
+        "" => default,
+        "-" => None,
+        _ => Some(answer),
+    })
+}
+
+/// Print a message like `println!`, on the standard error when it is used instead of the standard
+/// output, clearing the progress bars meanwhile
+macro_rules! message {
+    ($($arg:tt)*) => {
+        $crate::progress::suspend(|| {
+            if $crate::output::to_stderr() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        })
+    };
+}
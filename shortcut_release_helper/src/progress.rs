@@ -0,0 +1,45 @@
+//! This module shows the progress of the long tasks, like walking the history of a repository or
+//! retrieving the stories, so that large runs aren't silent
+//!
+//! The tasks in progress are drawn as bars on the standard error, only when it is a terminal. The
+//! messages and logs printed meanwhile go through [`suspend`], so that they don't tear the bars.
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use lazy_static::lazy_static;
+
+const TEMPLATE: &str = "{msg} [{bar:20}] {pos}/{len}";
+
+lazy_static! {
+    static ref BARS: MultiProgress = MultiProgress::with_draw_target(ProgressDrawTarget::stderr());
+}
+
+/// Progress of a task with a known number of steps, shown until dropped
+pub struct Progress(ProgressBar);
+
+impl Progress {
+    pub fn new(label: impl Into<String>, total: usize) -> Self {
+        let style = ProgressStyle::with_template(TEMPLATE)
+            .expect("Invalid progress template")
+            .progress_chars("= ");
+        let bar = ProgressBar::new(total as u64)
+            .with_style(style)
+            .with_message(label.into());
+        Self(BARS.add(bar))
+    }
+
+    /// Count one more step of the task as done
+    pub fn inc(&self) {
+        self.0.inc(1);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.0.finish_and_clear();
+        BARS.remove(&self.0);
+    }
+}
+
+/// Run `print` with the progress bars cleared, drawing them again afterwards
+pub fn suspend<T>(print: impl FnOnce() -> T) -> T {
+    BARS.suspend(print)
+}
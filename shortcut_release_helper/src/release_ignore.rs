@@ -0,0 +1,148 @@
+//! This module parses the `.releaseignore` file of a repository, listing what to exclude from
+//! the release next to the code
+//!
+//! Each line excludes either a commit by its full or abbreviated SHA of at least 4 characters
+//! (`commit 1a2b3c4`), the commits referencing a story (`story 1234` or `story sc-1234`), or the
+//! commits whose message matches a regex (`message ^chore\(deps\):`). Empty lines and lines
+//! starting with `#` are ignored.
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use git2::Oid as GitOid;
+use regex::Regex;
+
+use crate::shortcut::StoryId;
+
+/// Path of the file, relative to the repository root
+pub const RELEASE_IGNORE_PATH: &str = ".releaseignore";
+
+/// Shortest abbreviated SHA accepted, like git, so that a prefix doesn't match many commits
+const MIN_SHA_LENGTH: usize = 4;
+
+/// Length of a full SHA
+const SHA_LENGTH: usize = 40;
+
+#[derive(Debug, Default)]
+pub struct ReleaseIgnore {
+    /// SHAs of the excluded commits, in lowercase, possibly abbreviated
+    commits: Vec<String>,
+    /// Ids of the excluded stories, merged with the ones given on the command line
+    pub story_ids: Vec<StoryId>,
+    message_patterns: Vec<Regex>,
+}
+
+impl ReleaseIgnore {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut release_ignore = Self::default();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (kind, value) = line
+                .split_once(char::is_whitespace)
+                .map(|(kind, value)| (kind, value.trim()))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Expected `commit <sha>`, `story <id>` or `message <regex>` on line {} of \
+                        {RELEASE_IGNORE_PATH}, got {line:?}",
+                        index + 1
+                    )
+                })?;
+            let context = || {
+                format!(
+                    "Invalid {kind} on line {} of {RELEASE_IGNORE_PATH}",
+                    index + 1
+                )
+            };
+            match kind {
+                "commit"
+                    if (MIN_SHA_LENGTH..=SHA_LENGTH).contains(&value.len())
+                        && value.chars().all(|c| c.is_ascii_hexdigit()) =>
+                {
+                    release_ignore.commits.push(value.to_lowercase())
+                }
+                "commit" => return Err(anyhow!("Invalid SHA {value:?}")).with_context(context),
+                "story" => release_ignore.story_ids.push(
+                    StoryId::from_str(value.strip_prefix("sc-").unwrap_or(value))
+                        .with_context(context)?,
+                ),
+                "message" => release_ignore
+                    .message_patterns
+                    .push(Regex::new(value).with_context(context)?),
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown exclusion {kind:?} on line {} of {RELEASE_IGNORE_PATH}, expected \
+                        commit, story or message",
+                        index + 1
+                    ))
+                }
+            }
+        }
+        Ok(release_ignore)
+    }
+
+    /// Whether the commit is excluded by its SHA or its message
+    pub fn is_ignored(&self, commit_id: GitOid, message: Option<&str>) -> bool {
+        let sha = commit_id.to_string();
+        self.commits.iter().any(|commit| sha.starts_with(commit))
+            || message.is_some_and(|message| {
+                self.message_patterns
+                    .iter()
+                    .any(|pattern| pattern.is_match(message))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d";
+
+    #[test]
+    fn parse_empty() {
+        let release_ignore = ReleaseIgnore::parse("\n# Comment\n   \n").unwrap();
+        assert!(release_ignore.story_ids.is_empty());
+        assert!(!release_ignore.is_ignored(GitOid::from_str(SHA).unwrap(), Some("Message")));
+    }
+
+    #[test]
+    fn parse_exclusions() {
+        let release_ignore = ReleaseIgnore::parse(
+            "commit 1A2B3C4\ncommit 1a2b\n  story sc-12  \nstory 34\nmessage ^chore\\(deps\\):\n",
+        )
+        .unwrap();
+        assert_eq!(
+            release_ignore.story_ids,
+            [StoryId::from(12), StoryId::from(34)]
+        );
+        let commit_id = GitOid::from_str(SHA).unwrap();
+        let other_commit_id = GitOid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
+        assert!(release_ignore.is_ignored(commit_id, None));
+        assert!(!release_ignore.is_ignored(other_commit_id, None));
+        assert!(release_ignore.is_ignored(other_commit_id, Some("chore(deps): Bump git2")));
+        assert!(!release_ignore.is_ignored(other_commit_id, Some("feat: chore(deps):")));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for (content, error) in [
+            ("commit", "Expected `commit <sha>`"),
+            ("# Comment\nstory", "on line 2"),
+            ("commit 1a2b3cz", "Invalid SHA"),
+            ("commit a", "Invalid SHA"),
+            ("commit 1a2", "Invalid SHA"),
+            (
+                "commit 1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5",
+                "Invalid SHA",
+            ),
+            ("story sc-abc", "Invalid story"),
+            ("message (", "Invalid message"),
+            ("branch main", "Unknown exclusion \"branch\""),
+        ] {
+            let err = ReleaseIgnore::parse(content).unwrap_err();
+            assert!(format!("{err:#}").contains(error), "{content}: {err:#}");
+        }
+    }
+}
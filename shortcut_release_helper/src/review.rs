@@ -0,0 +1,277 @@
+//! This module lets the release manager review the release on the terminal before its release
+//! notes are rendered, excluding some of its stories and unparsed commits, and editing its name and
+//! description
+//!
+//! The stories and commits are listed with a checkbox, all checked at first, and the unchecked
+//! ones are left out of the release notes. The list is drawn on the standard error, so that the
+//! release notes can still be written to the standard output.
+use std::{
+    collections::HashSet,
+    io::{self, Stderr},
+};
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind},
+        execute,
+        terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    widgets::{Block, List, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+use crate::{output::prompt_default, shortcut::ReleaseContent, types::RepoToCommits};
+
+/// Name and description of the release, as edited during the review
+pub struct ReleaseDetails {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Review the stories and unparsed commits of the release, removing the excluded ones, then the
+/// details of the release
+pub fn review(
+    release: &mut ReleaseContent,
+    unparsed_commits: &mut RepoToCommits,
+    details: &mut ReleaseDetails,
+) -> Result<()> {
+    let story_ids = release
+        .stories
+        .iter()
+        .map(|story| story.story.id)
+        .collect::<Vec<_>>();
+    let commits = unparsed_commits
+        .iter()
+        .sorted_by_key(|(repo_name, _)| repo_name.to_string())
+        .flat_map(|(repo_name, commits)| {
+            commits
+                .iter()
+                .map(move |commit| (repo_name.clone(), commit.id))
+        })
+        .collect::<Vec<_>>();
+    let mut items = release
+        .stories
+        .iter()
+        .map(|story| {
+            format!(
+                "sc-{} {} ({})",
+                story.story.id,
+                story.story.name,
+                story
+                    .workflow_state_name
+                    .as_deref()
+                    .unwrap_or("unknown state")
+            )
+        })
+        .collect::<Vec<_>>();
+    for (repo_name, commit_id) in &commits {
+        let subject = unparsed_commits[repo_name]
+            .iter()
+            .find(|commit| commit.id == *commit_id)
+            .and_then(|commit| commit.message.as_deref())
+            .and_then(|message| message.lines().next())
+            .unwrap_or_default();
+        items.push(format!(
+            "{repo_name} {} {subject}",
+            &commit_id.to_string()[..7]
+        ));
+    }
+    let excluded = if items.is_empty() {
+        HashSet::new()
+    } else {
+        select_excluded(Checklist::new(items))?
+    };
+    let excluded_story_ids = excluded
+        .iter()
+        .filter_map(|index| story_ids.get(*index).copied())
+        .collect::<HashSet<_>>();
+    release.exclude_stories(&excluded_story_ids);
+    let excluded_commits = excluded
+        .iter()
+        .filter_map(|index| commits.get(index.checked_sub(story_ids.len())?))
+        .collect::<HashSet<_>>();
+    for (repo_name, commits) in unparsed_commits.iter_mut() {
+        commits.retain(|commit| !excluded_commits.contains(&(repo_name.clone(), commit.id)));
+    }
+    if !excluded.is_empty() {
+        message!(
+            "Excluded {} of the {} stories and unparsed commits",
+            excluded.len(),
+            story_ids.len() + commits.len()
+        );
+    }
+    details.name = prompt_default("Name of the release", details.name.take())?;
+    details.description = prompt_default("Description of the release", details.description.take())?;
+    Ok(())
+}
+
+/// Items of the review, with whether they are checked, ie kept in the release notes
+struct Checklist {
+    items: Vec<String>,
+    checked: Vec<bool>,
+    state: ListState,
+}
+
+impl Checklist {
+    fn new(items: Vec<String>) -> Self {
+        Self {
+            checked: vec![true; items.len()],
+            items,
+            state: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    fn toggle_selected(&mut self) {
+        if let Some(checked) = self
+            .state
+            .selected()
+            .and_then(|index| self.checked.get_mut(index))
+        {
+            *checked = !*checked;
+        }
+    }
+
+    fn check_all(&mut self, checked: bool) {
+        self.checked.fill(checked);
+    }
+
+    /// Indices of the unchecked items
+    fn excluded(&self) -> HashSet<usize> {
+        self.checked.iter().positions(|checked| !checked).collect()
+    }
+}
+
+/// Terminal drawn on the standard error, restored when dropped
+struct ReviewTerminal(Terminal<CrosstermBackend<Stderr>>);
+
+impl ReviewTerminal {
+    fn new() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stderr = io::stderr();
+        if let Err(err) = execute!(stderr, EnterAlternateScreen) {
+            let _ = terminal::disable_raw_mode();
+            return Err(err.into());
+        }
+        Ok(Self(Terminal::new(CrosstermBackend::new(stderr))?))
+    }
+}
+
+impl Drop for ReviewTerminal {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(self.0.backend_mut(), LeaveAlternateScreen);
+        let _ = self.0.show_cursor();
+    }
+}
+
+/// Show the checklist until it is confirmed with enter, returning the indices of the unchecked
+/// items, or failing when the review is cancelled with escape
+fn select_excluded(mut checklist: Checklist) -> Result<HashSet<usize>> {
+    let mut terminal = ReviewTerminal::new()?;
+    loop {
+        terminal.0.draw(|frame| draw(frame, &mut checklist))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => checklist.state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => checklist.state.select_next(),
+            KeyCode::PageUp => checklist.state.scroll_up_by(10),
+            KeyCode::PageDown => checklist.state.scroll_down_by(10),
+            KeyCode::Home => checklist.state.select_first(),
+            KeyCode::End => checklist.state.select_last(),
+            KeyCode::Char(' ') => checklist.toggle_selected(),
+            KeyCode::Char('a') => checklist.check_all(true),
+            KeyCode::Char('n') => checklist.check_all(false),
+            KeyCode::Enter => return Ok(checklist.excluded()),
+            KeyCode::Esc | KeyCode::Char('q') => bail!("The review was cancelled"),
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, checklist: &mut Checklist) {
+    let [list_area, help_area] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    let items = checklist
+        .items
+        .iter()
+        .zip(&checklist.checked)
+        .map(|(item, checked)| format!("[{}] {item}", if *checked { 'x' } else { ' ' }));
+    let excluded_count = checklist
+        .checked
+        .iter()
+        .filter(|checked| !**checked)
+        .count();
+    let list = List::new(items)
+        .block(Block::bordered().title(format!(
+            " Stories and unparsed commits of the release, {excluded_count} of {} excluded ",
+            checklist.items.len()
+        )))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, list_area, &mut checklist.state);
+    frame.render_widget(
+        Paragraph::new(
+            "↑/↓ move, space check or uncheck, a check all, n uncheck all, enter confirm, esc \
+            cancel",
+        ),
+        help_area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    fn checklist() -> Checklist {
+        Checklist::new(vec!["sc-1 One".to_string(), "sc-2 Two".to_string()])
+    }
+
+    #[test]
+    fn toggle_selected() {
+        let mut checklist = checklist();
+        assert!(checklist.excluded().is_empty());
+        checklist.state.select_next();
+        checklist.toggle_selected();
+        assert_eq!(checklist.excluded(), HashSet::from([1]));
+        checklist.toggle_selected();
+        assert!(checklist.excluded().is_empty());
+    }
+
+    #[test]
+    fn check_all() {
+        let mut checklist = checklist();
+        checklist.check_all(false);
+        assert_eq!(checklist.excluded(), HashSet::from([0, 1]));
+        checklist.check_all(true);
+        assert!(checklist.excluded().is_empty());
+    }
+
+    #[test]
+    fn draw_checkboxes() {
+        let mut checklist = checklist();
+        checklist.toggle_selected();
+        let mut terminal = Terminal::new(TestBackend::new(80, 5)).unwrap();
+        terminal.draw(|frame| draw(frame, &mut checklist)).unwrap();
+        let lines = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(80)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>();
+        assert!(lines[0].contains("1 of 2 excluded"));
+        assert!(lines[1].contains("[ ] sc-1 One"));
+        assert!(lines[2].contains("[x] sc-2 Two"));
+    }
+}
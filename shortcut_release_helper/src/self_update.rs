@@ -0,0 +1,106 @@
+//! This module updates the running binary to the latest GitHub release of the tool
+//!
+//! Each release is expected to have, for every platform, an asset named like
+//! `shortcut_release_helper-x86_64-linux` (with `.exe` on Windows), along with its SHA-256
+//! checksum in `shortcut_release_helper-x86_64-linux.sha256`, as printed by `sha256sum`. The
+//! binary is only replaced when the downloaded asset matches its checksum. Since the checksum is
+//! published along with the asset, and the releases aren't signed, this only detects corrupted
+//! downloads, not tampered releases.
+use std::{
+    env::{
+        self,
+        consts::{ARCH, EXE_SUFFIX, OS},
+    },
+    fs,
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use semver::Version;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{
+    github::GithubClient,
+    types::{GithubApiKey, GithubRelease, GithubReleaseAsset},
+};
+
+/// GitHub repository whose releases hold the binaries
+const RELEASE_REPOSITORY: &str = "impero-com/shortcut_release_helper";
+
+/// Replace the running binary by the one of the latest release when it is newer, or only print
+/// the update with `dry_run`
+pub async fn self_update(api_key: Option<GithubApiKey>, dry_run: bool) -> Result<()> {
+    let current_version = Version::parse(env!("CARGO_PKG_VERSION"))?;
+    let client = GithubClient::new(api_key)?;
+    let release = client.get_latest_release(RELEASE_REPOSITORY).await?;
+    let latest_version = Version::parse(release.tag_name.trim_start_matches('v'))
+        .with_context(|| format!("Invalid version of the latest release {}", release.tag_name))?;
+    if latest_version <= current_version {
+        message!("Already up to date with version {current_version}");
+        return Ok(());
+    }
+    let asset_name = format!("{}-{ARCH}-{OS}{EXE_SUFFIX}", env!("CARGO_PKG_NAME"));
+    let asset = find_asset(&release, &asset_name)?;
+    let checksum_asset = find_asset(&release, &format!("{asset_name}.sha256"))?;
+    let current_exe = env::current_exe().context("Could not locate the running binary")?;
+    if dry_run {
+        message!("Would update {current_exe:?} from version {current_version} to {latest_version}");
+        return Ok(());
+    }
+    info!("Downloading {asset_name} of version {latest_version}");
+    let checksum = String::from_utf8(client.download_asset(checksum_asset).await?)
+        .with_context(|| format!("Invalid checksum {}", checksum_asset.name))?;
+    let expected_checksum = checksum
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("Empty checksum {}", checksum_asset.name))?
+        .to_lowercase();
+    let binary = client.download_asset(asset).await?;
+    let actual_checksum = format!("{:x}", Sha256::digest(&binary));
+    if actual_checksum != expected_checksum {
+        bail!(
+            "The checksum of {asset_name} is {actual_checksum} instead of {expected_checksum}, \
+            not updating"
+        );
+    }
+    replace_binary(&current_exe, &binary)?;
+    message!("Updated {current_exe:?} from version {current_version} to {latest_version}");
+    Ok(())
+}
+
+fn find_asset<'a>(release: &'a GithubRelease, name: &str) -> Result<&'a GithubReleaseAsset> {
+    release
+        .assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .ok_or_else(|| anyhow!("No asset {name} in the release {}", release.tag_name))
+}
+
+/// Replace the binary at `path` by `binary`. The new binary is written next to it first, and the
+/// running one moved aside, since Windows doesn't allow overwriting a running binary.
+fn replace_binary(path: &Path, binary: &[u8]) -> Result<()> {
+    let new_path = path.with_extension("new");
+    let old_path = path.with_extension("old");
+    fs::write(&new_path, binary).with_context(|| format!("Could not write {new_path:?}"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::metadata(path)?.permissions().mode();
+        fs::set_permissions(&new_path, fs::Permissions::from_mode(permissions))?;
+    }
+    fs::rename(path, &old_path).with_context(|| format!("Could not move {path:?} aside"))?;
+    if let Err(err) = fs::rename(&new_path, path) {
+        if let Err(restore_err) = fs::rename(&old_path, path) {
+            bail!(
+                "Could not replace {path:?}: {err}, nor restore it from {old_path:?}: \
+                {restore_err}"
+            );
+        }
+        return Err(err).with_context(|| format!("Could not replace {path:?}"));
+    }
+    // The previous binary can't be removed while it is running on Windows, it is removed by the
+    // next update instead
+    let _ = fs::remove_file(&old_path);
+    Ok(())
+}
@@ -1,51 +1,343 @@
-use std::collections::HashSet;
-use std::num::NonZeroU32;
+use std::collections::{BTreeMap, HashSet};
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 use std::{collections::HashMap, str::FromStr};
 
-use anyhow::{anyhow, bail, Result};
-use futures::stream::FuturesUnordered;
-use futures::{Future, StreamExt, TryFutureExt};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{stream, Future, StreamExt};
+use git2::Oid as GitOid;
 use governor::clock::QuantaClock;
-use governor::state::direct::StreamRateLimitExt;
 use governor::state::InMemoryState;
 use governor::state::NotKeyed;
 use governor::Quota;
 use governor::RateLimiter;
 use lazy_static::lazy_static;
+use rand::Rng;
 use regex::Regex;
-use serde::Serialize;
+use reqwest::{Proxy, StatusCode, Url};
+use serde::{de::DeserializeOwned, ser::Error as _, Deserialize, Serialize, Serializer};
 use shortcut_client::apis::configuration as shortcut_cfg;
 use shortcut_client::apis::default_api as shortcut_api;
-use shortcut_client::models::{Epic, Story};
+use shortcut_client::apis::Error as ApiError;
+use shortcut_client::models::{
+    CreateDoc, CreateEpic, CreateIteration, CreateLabelParams, CreateStoryComment, CustomField,
+    CustomFieldValueParams, Epic, Group, Iteration, Member, MemberInfo, Milestone, Project, Story,
+    UpdateDoc, UpdateStory, Workflow, WorkflowState,
+};
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
 
+use crate::conventional_commits::is_breaking;
+use crate::progress::Progress;
+use crate::shortcut_cache::{Cached, ShortcutCache};
+use crate::shortcut_fixtures::Fixtures;
+use crate::types::ArchivedPolicy;
+use crate::types::GithubRepository;
+use crate::types::ReleaseContainer;
 use crate::types::RepoToCommits;
+use crate::types::RepositoryConfiguration;
+use crate::types::RepositoryName;
+use crate::types::SectionConfiguration;
 use crate::types::ShortcutApiKey;
+use crate::types::SuggestedStory;
+use crate::types::UnreleasedCommit;
+use crate::types::WorkspaceName;
+
+/// Delay before the first retry of a failed request
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Maximum delay between two attempts of a request
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Delay before retrying a rate limited request without `Retry-After` header, as Shortcut limits
+/// the requests per minute
+const DEFAULT_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+/// Maximum number of retries of a rate limited request, on top of the retries of other failures
+const MAX_RATE_LIMITED_RETRIES: u32 = 10;
+/// Number of stories per page when searching the updated stories, the maximum allowed
+const SEARCH_PAGE_SIZE: i64 = 25;
+/// Format of the content of the published Docs, the release notes being Markdown
+const DOC_CONTENT_FORMAT: &str = "markdown";
 
 #[derive(Debug)]
 pub struct Commits {
     story_commits: HashMap<StoryId, RepoToCommits>,
+    /// Commits referencing an epic rather than a story, by epic id
+    epic_commits: HashMap<i64, RepoToCommits>,
     unparsed_commits: RepoToCommits,
+    reverted_commit_pairs: usize,
+    excluded_commits: usize,
+}
+
+impl Commits {
+    fn story_ids(&self) -> Vec<i64> {
+        self.story_commits
+            .keys()
+            .map(|story_id| {
+                let story_id: &u32 = story_id.as_ref();
+                *story_id as i64
+            })
+            .collect()
+    }
 }
 
+/// Story types, as named by Shortcut
+pub const STORY_TYPES: [&str; 3] = ["feature", "bug", "chore"];
+
+/// Filters the stories of the release by label, workflow state, type, team, epic, project and
+/// iteration
 #[derive(Debug)]
-pub struct StoryLabelFilter<'a> {
+pub struct StoryFilter<'a> {
     excluded_labels: HashSet<&'a String>,
     included_labels: HashSet<&'a String>,
+    excluded_states: HashSet<&'a String>,
+    included_states: HashSet<&'a String>,
+    /// Only keep the stories in a workflow state of the done type
+    done_only: bool,
+    excluded_types: HashSet<&'a String>,
+    included_types: HashSet<&'a String>,
+    /// Names or mention names of the teams (groups)
+    excluded_teams: HashSet<&'a String>,
+    included_teams: HashSet<&'a String>,
+    excluded_epic_ids: HashSet<i64>,
+    included_epic_ids: HashSet<i64>,
+    /// Names or ids of the projects
+    excluded_projects: HashSet<&'a String>,
+    included_projects: HashSet<&'a String>,
+    /// Names of the iterations
+    included_iterations: HashSet<&'a String>,
+}
+
+/// The data referenced by a story which it can be filtered by, when known
+#[derive(Debug, Default)]
+pub struct StoryRefs<'a> {
+    pub workflow_state: Option<&'a WorkflowState>,
+    pub team: Option<&'a Group>,
+    pub project: Option<&'a Project>,
+    pub iteration: Option<&'a Iteration>,
 }
 
-impl<'a> StoryLabelFilter<'a> {
+impl<'a> StoryFilter<'a> {
     pub fn new(excluded_labels: &'a [String], included_labels: &'a [String]) -> Self {
         Self {
             excluded_labels: HashSet::from_iter(excluded_labels.iter()),
             included_labels: HashSet::from_iter(included_labels.iter()),
+            excluded_states: HashSet::new(),
+            included_states: HashSet::new(),
+            done_only: false,
+            excluded_types: HashSet::new(),
+            included_types: HashSet::new(),
+            excluded_teams: HashSet::new(),
+            included_teams: HashSet::new(),
+            excluded_epic_ids: HashSet::new(),
+            included_epic_ids: HashSet::new(),
+            excluded_projects: HashSet::new(),
+            included_projects: HashSet::new(),
+            included_iterations: HashSet::new(),
+        }
+    }
+
+    /// Also filter the stories by the name of their workflow state, and whether it's a done one
+    pub fn with_states(
+        mut self,
+        excluded_states: &'a [String],
+        included_states: &'a [String],
+        done_only: bool,
+    ) -> Self {
+        self.excluded_states = HashSet::from_iter(excluded_states.iter());
+        self.included_states = HashSet::from_iter(included_states.iter());
+        self.done_only = done_only;
+        self
+    }
+
+    /// Also filter the stories by type, one of [`STORY_TYPES`]
+    pub fn with_types(
+        mut self,
+        excluded_types: &'a [String],
+        included_types: &'a [String],
+    ) -> Self {
+        self.excluded_types = HashSet::from_iter(excluded_types.iter());
+        self.included_types = HashSet::from_iter(included_types.iter());
+        self
+    }
+
+    /// Also filter the stories by team, given by name or mention name
+    pub fn with_teams(
+        mut self,
+        excluded_teams: &'a [String],
+        included_teams: &'a [String],
+    ) -> Self {
+        self.excluded_teams = HashSet::from_iter(excluded_teams.iter());
+        self.included_teams = HashSet::from_iter(included_teams.iter());
+        self
+    }
+
+    /// Also filter the stories by the id of their epic
+    pub fn with_epics(mut self, excluded_epic_ids: &[i64], included_epic_ids: &[i64]) -> Self {
+        self.excluded_epic_ids = HashSet::from_iter(excluded_epic_ids.iter().copied());
+        self.included_epic_ids = HashSet::from_iter(included_epic_ids.iter().copied());
+        self
+    }
+
+    /// Also filter the stories by project, given by name or id
+    pub fn with_projects(
+        mut self,
+        excluded_projects: &'a [String],
+        included_projects: &'a [String],
+    ) -> Self {
+        self.excluded_projects = HashSet::from_iter(excluded_projects.iter());
+        self.included_projects = HashSet::from_iter(included_projects.iter());
+        self
+    }
+
+    /// Also only keep the stories of the iterations, given by name
+    pub fn with_iterations(mut self, included_iterations: &'a [String]) -> Self {
+        self.included_iterations = HashSet::from_iter(included_iterations.iter());
+        self
+    }
+
+    fn has_iterations(&self) -> bool {
+        !self.included_iterations.is_empty()
+    }
+
+    fn has_projects(&self) -> bool {
+        !self.excluded_projects.is_empty() || !self.included_projects.is_empty()
+    }
+
+    /// Warn about the projects of the filter which aren't projects of the workspace
+    fn check_projects(&self, projects: &[Project]) {
+        for project in self.excluded_projects.iter().chain(&self.included_projects) {
+            if !projects
+                .iter()
+                .any(|known| known.name == **project || known.id.to_string() == **project)
+            {
+                warn!("No project named {project} in the Shortcut workspace");
+            }
+        }
+    }
+
+    fn has_teams(&self) -> bool {
+        !self.excluded_teams.is_empty() || !self.included_teams.is_empty()
+    }
+
+    /// Warn about the teams of the filter which aren't groups of the workspace
+    fn check_teams(&self, groups: &[Group]) {
+        for team in self.excluded_teams.iter().chain(&self.included_teams) {
+            if !groups
+                .iter()
+                .any(|group| group.name == **team || group.mention_name == **team)
+            {
+                warn!("No team named {team} in the Shortcut workspace");
+            }
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.excluded_labels.is_empty() && self.included_labels.is_empty()
+        self.excluded_labels.is_empty()
+            && self.included_labels.is_empty()
+            && self.excluded_states.is_empty()
+            && self.included_states.is_empty()
+            && !self.done_only
+            && self.excluded_types.is_empty()
+            && self.included_types.is_empty()
+            && !self.has_teams()
+            && self.excluded_epic_ids.is_empty()
+            && self.included_epic_ids.is_empty()
+            && !self.has_projects()
+            && !self.has_iterations()
+    }
+
+    /// Whether the commits referencing the epic directly are kept, according to the epic filters
+    pub fn filter_epic(&self, epic_id: i64) -> bool {
+        !self.excluded_epic_ids.contains(&epic_id)
+            && (self.included_epic_ids.is_empty() || self.included_epic_ids.contains(&epic_id))
     }
 
-    pub fn filter(&self, story: &Story) -> bool {
+    /// Whether the story is kept, given the data it references. Excluded labels, states, types,
+    /// teams, epics and projects have priority over included ones, and a story must have all the
+    /// included labels but only one of the included states, types, teams, epics, projects and
+    /// iterations.
+    pub fn filter(&self, story: &Story, refs: &StoryRefs<'_>) -> bool {
+        let StoryRefs {
+            workflow_state,
+            team,
+            project,
+            iteration,
+        } = refs;
+        if self.has_iterations()
+            && !iteration
+                .is_some_and(|iteration| self.included_iterations.contains(&iteration.name))
+        {
+            return false;
+        }
+        if self.has_projects() {
+            let project_keys = story
+                .project_id
+                .map(|project_id| project_id.to_string())
+                .into_iter()
+                .chain(project.map(|project| project.name.clone()))
+                .collect::<Vec<_>>();
+            if project_keys
+                .iter()
+                .any(|key| self.excluded_projects.contains(key))
+            {
+                return false;
+            }
+            if !self.included_projects.is_empty()
+                && !project_keys
+                    .iter()
+                    .any(|key| self.included_projects.contains(key))
+            {
+                return false;
+            }
+        }
+        if let Some(epic_id) = story.epic_id {
+            if self.excluded_epic_ids.contains(&epic_id) {
+                return false;
+            }
+        }
+        if !self.included_epic_ids.is_empty()
+            && !story
+                .epic_id
+                .is_some_and(|epic_id| self.included_epic_ids.contains(&epic_id))
+        {
+            return false;
+        }
+        let team_names = team
+            .map(|team| [&team.name, &team.mention_name])
+            .into_iter()
+            .flatten();
+        if team_names
+            .clone()
+            .any(|name| self.excluded_teams.contains(name))
+        {
+            return false;
+        }
+        if !self.included_teams.is_empty()
+            && !team_names
+                .clone()
+                .any(|name| self.included_teams.contains(name))
+        {
+            return false;
+        }
+        if self.excluded_types.contains(&story.story_type)
+            || (!self.included_types.is_empty() && !self.included_types.contains(&story.story_type))
+        {
+            return false;
+        }
+        let state_name = workflow_state.map(|state| &state.name);
+        if state_name.is_some_and(|name| self.excluded_states.contains(name)) {
+            return false;
+        }
+        if !self.included_states.is_empty()
+            && !state_name.is_some_and(|name| self.included_states.contains(name))
+        {
+            return false;
+        }
+        let is_done = workflow_state.is_some_and(|state| state.r#type == DONE_WORKFLOW_STATE_TYPE);
+        if self.done_only && !is_done {
+            return false;
+        }
         let mut included_labels_count = 0;
         for label in &story.labels {
             if self.excluded_labels.contains(&label.name) {
@@ -60,31 +352,249 @@ impl<'a> StoryLabelFilter<'a> {
     }
 }
 
-/// not linked to a story.
-pub fn parse_commits(
-    commits: RepoToCommits,
-    exclude_story_ids: &HashSet<StoryId>,
-) -> Result<Commits> {
+/// Find the id of the story referenced in the text, if any
+pub fn find_story_id(text: &str) -> Option<StoryId> {
     lazy_static! {
         static ref SHORTCUT_RE: Regex = Regex::new(r"(?:(\[|/)sc-|(\[|/)ch|story/)(\d+)")
             .expect("Could not compile SHORTCUT_RE");
     };
+    SHORTCUT_RE
+        .captures(text)
+        .map(|captures| {
+            captures
+                .get(3)
+                .expect("Story id should be captured")
+                .as_str()
+        })
+        .map(|story_id| StoryId::from_str(story_id).expect("Should be parsed as number"))
+}
+
+/// Find the id of the epic referenced in the text, as `[sc-epic-<id>]` or by its URL, if any
+pub fn find_epic_id(text: &str) -> Option<i64> {
+    lazy_static! {
+        static ref EPIC_RE: Regex =
+            Regex::new(r"(?:\[sc-epic-|/epic/)(\d+)").expect("Could not compile EPIC_RE");
+    };
+    EPIC_RE
+        .captures(text)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
+/// Find the id of the epic referenced by the message of the commit, or else by the title or
+/// description of the pull request it comes from
+fn commit_epic_id(commit: &UnreleasedCommit) -> Option<i64> {
+    commit
+        .message
+        .as_deref()
+        .and_then(find_epic_id)
+        .or_else(|| {
+            let pull_request = commit.pull_request.as_ref()?;
+            find_epic_id(&pull_request.title)
+                .or_else(|| pull_request.body.as_deref().and_then(find_epic_id))
+        })
+}
+
+/// Extracts the id of the story referenced by commits
+#[derive(Debug)]
+pub struct StoryIdParser<'a> {
+    branch_story_id_re: Regex,
+    story_trailers: &'a [String],
+    /// Regexes of the story ids referenced in the commit messages of the repositories
+    /// configuring their own
+    repository_story_id_res: HashMap<RepositoryName, Vec<Regex>>,
+}
+
+impl<'a> StoryIdParser<'a> {
+    pub fn new(
+        branch_story_id_pattern: &str,
+        story_trailers: &'a [String],
+        repositories: &HashMap<RepositoryName, RepositoryConfiguration>,
+    ) -> Result<Self> {
+        let mut repository_story_id_res = HashMap::new();
+        for (repo_name, repo_config) in repositories {
+            let Some(patterns) = &repo_config.story_id_patterns else {
+                continue;
+            };
+            let story_id_res = patterns
+                .iter()
+                .map(|pattern| {
+                    let story_id_re = Regex::new(pattern)?;
+                    if story_id_re.captures_len() < 2 {
+                        bail!("The story id pattern {pattern} of {repo_name} has no capture group");
+                    }
+                    Ok(story_id_re)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            repository_story_id_res.insert(repo_name.clone(), story_id_res);
+        }
+        Ok(Self {
+            branch_story_id_re: Regex::new(branch_story_id_pattern)?,
+            story_trailers,
+            repository_story_id_res,
+        })
+    }
+
+    /// Find the id of the story referenced in the text, using the story id patterns of the
+    /// repository (or of its parent repository for a submodule) if any
+    fn text_story_id(&self, repo_name: &RepositoryName, text: &str) -> Option<StoryId> {
+        let story_id_res = self.repository_story_id_res.get(repo_name).or_else(|| {
+            self.repository_story_id_res
+                .iter()
+                .find(|(name, _)| {
+                    repo_name
+                        .as_ref()
+                        .strip_prefix(name.as_ref().as_str())
+                        .is_some_and(|path| path.starts_with('/'))
+                })
+                .map(|(_, story_id_res)| story_id_res)
+        });
+        let Some(story_id_res) = story_id_res else {
+            return find_story_id(text);
+        };
+        story_id_res
+            .iter()
+            .find_map(|story_id_re| story_id_re.captures(text)?.get(1)?.as_str().parse().ok())
+    }
+
+    /// Find the id of the story referenced, in order of precedence, by:
+    /// - the commit message, using the story id patterns of the repository
+    /// - one of the story trailers of the commit, like `Shortcut-Story: 1234`
+    /// - the title or description of the pull request the commit comes from
+    /// - the name of the branch the commit was merged from (using the first capture group of
+    ///   the branch regex)
+    fn commit_story_id(
+        &self,
+        repo_name: &RepositoryName,
+        commit: &UnreleasedCommit,
+    ) -> Option<StoryId> {
+        let text_story_id = |text: &str| self.text_story_id(repo_name, text);
+        commit
+            .message
+            .as_deref()
+            .and_then(|message| text_story_id(message).or_else(|| self.trailer_story_id(message)))
+            .or_else(|| {
+                let pull_request = commit.pull_request.as_ref()?;
+                text_story_id(&pull_request.title)
+                    .or_else(|| pull_request.body.as_deref().and_then(text_story_id))
+            })
+            .or_else(|| {
+                let branch = commit.merged_branch.as_deref()?;
+                self.branch_story_id_re
+                    .captures(branch)?
+                    .get(1)?
+                    .as_str()
+                    .parse()
+                    .ok()
+            })
+    }
+
+    /// Find the story id in the value of the first story trailer of the message. The value may
+    /// be the story id itself (`1234` or `sc-1234`) or a reference to it, like its URL.
+    fn trailer_story_id(&self, message: &str) -> Option<StoryId> {
+        lazy_static! {
+            static ref TRAILER_STORY_ID_RE: Regex =
+                Regex::new(r"^(?:sc-)?(\d+)$").expect("Could not compile TRAILER_STORY_ID_RE");
+        };
+        let trailers = git2::message_trailers_strs(message).ok()?;
+        let story_id = trailers
+            .iter()
+            .filter(|(key, _value)| {
+                self.story_trailers
+                    .iter()
+                    .any(|story_trailer| story_trailer.eq_ignore_ascii_case(key))
+            })
+            .find_map(|(_key, value)| {
+                let value = value.trim();
+                TRAILER_STORY_ID_RE
+                    .captures(value)
+                    .and_then(|captures| captures[1].parse().ok())
+                    .or_else(|| find_story_id(value))
+            });
+        story_id
+    }
+}
+
+/// Remove the commits reverted by another unreleased commit, as well as the reverting commits.
+/// A revert is recognized by the `This reverts commit <sha>` line added by `git revert`, or
+/// failing that by a `Revert "<subject>"` subject. Returns the number of removed pairs.
+fn remove_reverted_commits(commits: &mut Vec<UnreleasedCommit>) -> usize {
+    lazy_static! {
+        static ref REVERTED_SHA_RE: Regex = Regex::new(r"This reverts commit ([0-9a-f]{40})")
+            .expect("Could not compile REVERTED_SHA_RE");
+        static ref REVERTED_SUBJECT_RE: Regex =
+            Regex::new(r#"^Revert "(.*)"$"#).expect("Could not compile REVERTED_SUBJECT_RE");
+    };
+    let subject = |commit: &UnreleasedCommit| {
+        commit
+            .message
+            .as_deref()
+            .and_then(|message| message.lines().next())
+            .map(str::to_owned)
+    };
+    let mut removed_ids = HashSet::new();
+    let mut pairs = 0;
+    for revert in commits.iter() {
+        if removed_ids.contains(&revert.id) {
+            continue;
+        }
+        let Some(message) = revert.message.as_deref() else {
+            continue;
+        };
+        let reverted_id = REVERTED_SHA_RE.captures(message).and_then(|captures| {
+            GitOid::from_str(&captures[1])
+                .ok()
+                .filter(|id| commits.iter().any(|commit| commit.id == *id))
+        });
+        let reverted_id = reverted_id.or_else(|| {
+            let reverted_subject = REVERTED_SUBJECT_RE
+                .captures(message.lines().next()?)?
+                .get(1)?
+                .as_str()
+                .to_owned();
+            commits
+                .iter()
+                .find(|commit| subject(commit).as_deref() == Some(reverted_subject.as_str()))
+                .map(|commit| commit.id)
+        });
+        if let Some(reverted_id) = reverted_id {
+            if reverted_id != revert.id && !removed_ids.contains(&reverted_id) {
+                debug!(revert_id = ?revert.id, ?reverted_id, "Removing reverted commit");
+                removed_ids.insert(revert.id);
+                removed_ids.insert(reverted_id);
+                pairs += 1;
+            }
+        }
+    }
+    commits.retain(|commit| !removed_ids.contains(&commit.id));
+    pairs
+}
+
+/// Group commits by the story they are linked to, excluding the given stories and the commits
+/// with a message matching one of the excluded patterns, along with commits linked to an epic
+/// instead and commits not linked to a story.
+pub fn parse_commits(
+    commits: RepoToCommits,
+    exclude_story_ids: &HashSet<StoryId>,
+    exclude_commit_patterns: &[Regex],
+    story_id_parser: &StoryIdParser,
+) -> Result<Commits> {
     let mut story_commits: HashMap<StoryId, RepoToCommits> = HashMap::new();
+    let mut epic_commits: HashMap<i64, RepoToCommits> = HashMap::new();
     let mut unparsed_commits: RepoToCommits = HashMap::new();
-    for (repo_name, commits) in commits {
+    let mut reverted_commit_pairs = 0;
+    let mut excluded_commits = 0;
+    for (repo_name, mut commits) in commits {
+        let commit_count = commits.len();
+        commits.retain(|commit| {
+            let message = commit.message.as_deref().unwrap_or_default();
+            !exclude_commit_patterns
+                .iter()
+                .any(|pattern| pattern.is_match(message))
+        });
+        excluded_commits += commit_count - commits.len();
+        reverted_commit_pairs += remove_reverted_commits(&mut commits);
         for commit in commits {
-            let maybe_story_id = commit
-                .message
-                .as_ref()
-                .and_then(|message| {
-                    SHORTCUT_RE.captures(message).map(|captures| {
-                        captures
-                            .get(3)
-                            .expect("Story id should be captured")
-                            .as_str()
-                    })
-                })
-                .map(|story_id| StoryId::from_str(story_id).expect("Should be parsed as number"));
+            let maybe_story_id = story_id_parser.commit_story_id(&repo_name, &commit);
             if let Some(story_id) = maybe_story_id {
                 if !exclude_story_ids.contains(&story_id) {
                     story_commits
@@ -94,6 +604,13 @@ pub fn parse_commits(
                         .or_default()
                         .push(commit);
                 }
+            } else if let Some(epic_id) = commit_epic_id(&commit) {
+                epic_commits
+                    .entry(epic_id)
+                    .or_default()
+                    .entry(repo_name.clone())
+                    .or_default()
+                    .push(commit);
             } else {
                 unparsed_commits
                     .entry(repo_name.clone())
@@ -102,43 +619,540 @@ pub fn parse_commits(
             }
         }
     }
+    for commits in story_commits
+        .values_mut()
+        .chain(epic_commits.values_mut())
+        .flat_map(|repo_to_commits| repo_to_commits.values_mut())
+        .chain(unparsed_commits.values_mut())
+    {
+        commits.sort_by_key(|commit| commit.committed_at);
+    }
     Ok(Commits {
         story_commits,
+        epic_commits,
         unparsed_commits,
+        reverted_commit_pairs,
+        excluded_commits,
     })
 }
 
+/// Options of the Shortcut client, set from the configuration file
+#[derive(Debug, Clone)]
+pub struct ShortcutOptions {
+    /// Maximum number of requests in flight
+    pub concurrency: NonZeroUsize,
+    /// Maximum number of attempts of each request, retrying transient failures
+    pub max_attempts: NonZeroU32,
+    /// Maximum number of requests per minute, with bursts of the same size
+    pub requests_per_minute: NonZeroU32,
+    /// Base URL of the API, instead of the public one
+    pub api_url: Option<String>,
+    /// URL of the proxy for all requests, instead of the one of the environment
+    pub proxy: Option<String>,
+    /// Whether the archived stories are part of the releases
+    pub archived: ArchivedPolicy,
+}
+
+/// Where the client reads the stories and epics from
+enum DataSource {
+    /// Shortcut, using the fresh cached responses that are still up to date
+    Shortcut,
+    /// The cache only, whatever the age of the responses
+    Cache,
+    /// Fixtures recorded by a previous run
+    Fixtures(Fixtures),
+}
+
 pub struct ShortcutClient {
     configuration: shortcut_cfg::Configuration,
     rate_limiter: RateLimiter<NotKeyed, InMemoryState, QuantaClock>,
+    /// Time until which requests are paused, after being rate limited by Shortcut
+    paused_until: Mutex<Option<Instant>>,
+    cache: ShortcutCache,
+    options: ShortcutOptions,
+    source: DataSource,
+    /// Stories and epics of all the releases, when recording them
+    recorded: Option<Mutex<Fixtures>>,
+    /// Members of the workspace, retrieved once
+    members: OnceCell<Vec<Member>>,
+    /// Groups (teams) of the workspace, retrieved once
+    groups: OnceCell<Vec<Group>>,
+    /// Workflows of the workspace, retrieved once
+    workflows: OnceCell<Vec<Workflow>>,
+    /// Definitions of the custom fields of the workspace, retrieved once
+    custom_fields: OnceCell<Vec<CustomField>>,
+    /// Projects of the workspace, retrieved once
+    projects: OnceCell<Vec<Project>>,
 }
 
 impl ShortcutClient {
-    pub fn new(api_key: &ShortcutApiKey) -> Self {
+    /// A client reading the stories and epics from Shortcut. Requests go through the proxy of
+    /// the options, or else the one of the `HTTPS_PROXY` environment variable, as reqwest does.
+    pub fn new(
+        api_key: &ShortcutApiKey,
+        options: ShortcutOptions,
+        cache: ShortcutCache,
+    ) -> Result<Self> {
         let mut configuration = shortcut_cfg::Configuration::new();
         configuration.api_key = Some(shortcut_cfg::ApiKey {
             key: api_key.to_string(),
             prefix: None,
         });
-        let shortcut_api_limit: std::num::NonZeroU32 =
-            NonZeroU32::new(200u32).expect("Should be non-zero");
-        let rate_limiter = RateLimiter::direct(Quota::per_minute(shortcut_api_limit));
+        if let Some(api_url) = &options.api_url {
+            configuration.base_path = api_url.trim_end_matches('/').to_owned();
+        }
+        if let Some(proxy) = &options.proxy {
+            let proxy =
+                Proxy::all(proxy).with_context(|| format!("Invalid proxy URL {proxy:?}"))?;
+            configuration.client = reqwest::Client::builder().proxy(proxy).build()?;
+        }
+        Ok(Self::with_source(
+            configuration,
+            options,
+            cache,
+            DataSource::Shortcut,
+        ))
+    }
+
+    /// A client without token, reading the stories and epics from the cache only and leaving out
+    /// the ones that aren't cached. The cache should be in
+    /// [`CacheMode::Offline`](crate::shortcut_cache::CacheMode::Offline).
+    pub fn offline(options: ShortcutOptions, cache: ShortcutCache) -> Self {
+        let configuration = shortcut_cfg::Configuration::new();
+        Self::with_source(configuration, options, cache, DataSource::Cache)
+    }
+
+    /// A client without token, reading the stories and epics from the fixtures only and leaving
+    /// out the ones that weren't recorded
+    pub fn replay(options: ShortcutOptions, cache: ShortcutCache, fixtures: Fixtures) -> Self {
+        let configuration = shortcut_cfg::Configuration::new();
+        Self::with_source(
+            configuration,
+            options,
+            cache,
+            DataSource::Fixtures(fixtures),
+        )
+    }
+
+    fn with_source(
+        configuration: shortcut_cfg::Configuration,
+        options: ShortcutOptions,
+        cache: ShortcutCache,
+        source: DataSource,
+    ) -> Self {
+        let rate_limiter = RateLimiter::direct(Quota::per_minute(options.requests_per_minute));
         Self {
             configuration,
             rate_limiter,
+            paused_until: Mutex::new(None),
+            cache,
+            options,
+            source,
+            recorded: None,
+            members: OnceCell::new(),
+            groups: OnceCell::new(),
+            workflows: OnceCell::new(),
+            custom_fields: OnceCell::new(),
+            projects: OnceCell::new(),
         }
     }
 
-    async fn get_shortcut_data<T: std::fmt::Debug + Unpin, E: std::fmt::Debug + Unpin>(
-        &self,
-        actions: impl Iterator<Item = impl Future<Output = Result<T, E>>>,
-    ) -> Result<Vec<T>> {
-        let items = actions
-            .collect::<FuturesUnordered<_>>()
-            .ratelimit_stream(&self.rate_limiter)
-            .collect::<Vec<_>>()
+    /// The member the token belongs to, checking that it's valid, or `None` when not reading from
+    /// Shortcut
+    pub async fn current_member(&self) -> Result<Option<MemberInfo>> {
+        if !matches!(self.source, DataSource::Shortcut) {
+            return Ok(None);
+        }
+        let result = self
+            .with_retries("member", 0, || {
+                shortcut_api::get_current_member_info(&self.configuration)
+            })
             .await;
-        let (items, errors): (Vec<_>, Vec<_>) = items.into_iter().partition(Result::is_ok);
+        match result {
+            Ok(member) => Ok(Some(member)),
+            Err(ApiError::ResponseError(response))
+                if response.status == StatusCode::UNAUTHORIZED
+                    || response.status == StatusCode::FORBIDDEN =>
+            {
+                Err(anyhow!("The Shortcut token is invalid"))
+            }
+            Err(err) => Err(anyhow!("Could not check the Shortcut token: {err:?}")),
+        }
+    }
+
+    /// The member the token belongs to like [`Self::current_member`], also checking that it
+    /// belongs to the Shortcut workspace with this URL slug when given
+    pub async fn check_token(&self, expected_url_slug: Option<&str>) -> Result<Option<MemberInfo>> {
+        let Some(member) = self.current_member().await? else {
+            return Ok(None);
+        };
+        let url_slug = &member.workspace2.url_slug;
+        if let Some(expected_url_slug) = expected_url_slug {
+            if url_slug != expected_url_slug {
+                bail!(
+                    "The token belongs to the Shortcut workspace {url_slug}, expected \
+                    {expected_url_slug}"
+                );
+            }
+        }
+        Ok(Some(member))
+    }
+
+    /// Check that the Shortcut API can be reached, whatever the response
+    pub async fn check_reachable(&self) -> Result<()> {
+        let base_path = &self.configuration.base_path;
+        self.configuration
+            .client
+            .get(base_path)
+            .send()
+            .await
+            .with_context(|| {
+                format!(
+                    "Could not reach {base_path}, check the network and the proxy configuration"
+                )
+            })?;
+        Ok(())
+    }
+
+    /// Record the stories and epics of the releases, before their stories are filtered
+    pub fn record(mut self) -> Self {
+        self.recorded = Some(Mutex::new(Fixtures::default()));
+        self
+    }
+
+    /// The stories and epics recorded so far, if recording them, starting over
+    pub fn take_recorded(&self) -> Option<Fixtures> {
+        self.recorded
+            .as_ref()
+            .map(|recorded| std::mem::take(&mut *recorded.lock().expect("Poisoned lock")))
+    }
+
+    /// The first of the workflow states named `state_names` in the workflow, if any
+    pub async fn find_workflow_state(
+        &self,
+        workflow_id: i64,
+        state_names: &[String],
+    ) -> Result<Option<&WorkflowState>> {
+        let workflows = self.get_workflows().await?;
+        let Some(workflow) = workflows.iter().find(|workflow| workflow.id == workflow_id) else {
+            return Ok(None);
+        };
+        let state = state_names
+            .iter()
+            .find_map(|name| workflow.states.iter().find(|state| state.name == *name));
+        Ok(state)
+    }
+
+    /// Move the story to the workflow state, and cache the updated story
+    pub async fn set_story_state(&self, story_id: i64, workflow_state_id: i64) -> Result<()> {
+        let update = UpdateStory {
+            workflow_state_id: Some(workflow_state_id),
+            ..UpdateStory::new()
+        };
+        let story = self
+            .with_retries("story", story_id, || {
+                shortcut_api::update_story(&self.configuration, story_id, update.clone())
+            })
+            .await
+            .map_err(|err| anyhow!("Error while moving story {story_id}: {err:?}"))?;
+        self.cache.put("story", story.id, &story);
+        Ok(())
+    }
+
+    /// The ids of the custom field named `field_name` and of its value `value`, if any
+    pub async fn find_custom_field_value(
+        &self,
+        field_name: &str,
+        value: &str,
+    ) -> Result<Option<(String, String)>> {
+        let custom_fields = self.get_custom_fields().await?;
+        let field_value = custom_fields
+            .iter()
+            .filter(|field| field.name == field_name)
+            .find_map(|field| {
+                let field_value = field
+                    .values
+                    .iter()
+                    .flatten()
+                    .find(|enum_value| enum_value.value == value)?;
+                Some((field.id.clone(), field_value.id.clone()))
+            });
+        Ok(field_value)
+    }
+
+    /// Set the custom field of the story to the value, keeping its other custom fields, and
+    /// cache the updated story
+    pub async fn set_story_custom_field(
+        &self,
+        story: &Story,
+        field_id: &str,
+        value_id: &str,
+    ) -> Result<()> {
+        let custom_fields = story
+            .custom_fields
+            .iter()
+            .flatten()
+            .filter(|story_field| story_field.field_id != field_id)
+            .map(|story_field| {
+                CustomFieldValueParams::new(
+                    story_field.field_id.clone(),
+                    story_field.value_id.clone(),
+                )
+            })
+            .chain([CustomFieldValueParams::new(
+                field_id.to_owned(),
+                value_id.to_owned(),
+            )])
+            .collect();
+        let update = UpdateStory {
+            custom_fields: Some(custom_fields),
+            ..UpdateStory::new()
+        };
+        let story = self
+            .with_retries("story", story.id, || {
+                shortcut_api::update_story(&self.configuration, story.id, update.clone())
+            })
+            .await
+            .map_err(|err| {
+                anyhow!(
+                    "Error while setting the custom field of story {}: {err:?}",
+                    story.id
+                )
+            })?;
+        self.cache.put("story", story.id, &story);
+        Ok(())
+    }
+
+    /// Find the epic or iteration named `name`, which isn't archived for an epic, returning its id
+    pub async fn find_container(
+        &self,
+        container: ReleaseContainer,
+        name: &str,
+    ) -> Result<Option<i64>> {
+        let id = match container {
+            ReleaseContainer::Epic => self
+                .with_retries("epics", 0, || {
+                    shortcut_api::list_epics(&self.configuration, None)
+                })
+                .await
+                .map_err(|err| anyhow!("Error while retrieving the epics: {err:?}"))?
+                .into_iter()
+                .find(|epic| epic.name == name && !epic.archived)
+                .map(|epic| epic.id),
+            ReleaseContainer::Iteration => self
+                .with_retries("iterations", 0, || {
+                    shortcut_api::list_iterations(&self.configuration)
+                })
+                .await
+                .map_err(|err| anyhow!("Error while retrieving the iterations: {err:?}"))?
+                .into_iter()
+                .find(|iteration| iteration.name == name)
+                .map(|iteration| iteration.id),
+        };
+        Ok(id)
+    }
+
+    /// Create the epic or iteration grouping the stories of the release, on the date of the
+    /// release for an iteration, returning its id. The request isn't retried, as a retry could
+    /// create a duplicate when the failed request went through.
+    pub async fn create_container(
+        &self,
+        container: ReleaseContainer,
+        name: &str,
+        date: &str,
+    ) -> Result<i64> {
+        self.until_ready().await;
+        let id = match container {
+            ReleaseContainer::Epic => {
+                shortcut_api::create_epic(&self.configuration, CreateEpic::new(name.to_owned()))
+                    .await
+                    .map_err(|err| anyhow!("Error while creating the epic {name}: {err:?}"))?
+                    .id
+            }
+            ReleaseContainer::Iteration => {
+                let iteration =
+                    CreateIteration::new(name.to_owned(), date.to_owned(), date.to_owned());
+                shortcut_api::create_iteration(&self.configuration, iteration)
+                    .await
+                    .map_err(|err| anyhow!("Error while creating the iteration {name}: {err:?}"))?
+                    .id
+            }
+        };
+        Ok(id)
+    }
+
+    /// Move the story, which has no epic or iteration, to the epic or iteration, and cache the
+    /// updated story
+    pub async fn set_story_container(
+        &self,
+        story_id: i64,
+        container: ReleaseContainer,
+        container_id: i64,
+    ) -> Result<()> {
+        let update = match container {
+            ReleaseContainer::Epic => UpdateStory {
+                epic_id: Some(container_id),
+                ..UpdateStory::new()
+            },
+            ReleaseContainer::Iteration => UpdateStory {
+                iteration_id: Some(container_id),
+                ..UpdateStory::new()
+            },
+        };
+        let story = self
+            .with_retries("story", story_id, || {
+                shortcut_api::update_story(&self.configuration, story_id, update.clone())
+            })
+            .await
+            .map_err(|err| {
+                anyhow!("Error while moving story {story_id} to the {container}: {err:?}")
+            })?;
+        self.cache.put("story", story.id, &story);
+        Ok(())
+    }
+
+    /// Update the content of the Doc titled `title`, or create it if there is none, returning
+    /// its URL. The creation isn't retried, as a retry could create a duplicate when the failed
+    /// request went through.
+    pub async fn publish_doc(&self, title: &str, markdown: &str) -> Result<String> {
+        let docs = self
+            .with_retries("docs", 0, || shortcut_api::list_docs(&self.configuration))
+            .await
+            .map_err(|err| anyhow!("Error while retrieving the docs: {err:?}"))?;
+        let existing_doc = docs.iter().find(|doc| doc.title.as_deref() == Some(title));
+        let doc = match existing_doc {
+            Some(existing_doc) => {
+                let update = UpdateDoc {
+                    content: Some(markdown.to_owned()),
+                    content_format: Some(DOC_CONTENT_FORMAT.to_owned()),
+                    ..UpdateDoc::new()
+                };
+                self.with_retries("doc", 0, || {
+                    shortcut_api::update_doc(&self.configuration, &existing_doc.id, update.clone())
+                })
+                .await
+                .map_err(|err| anyhow!("Error while updating the doc {title}: {err:?}"))?
+            }
+            None => {
+                let create = CreateDoc {
+                    content_format: Some(DOC_CONTENT_FORMAT.to_owned()),
+                    ..CreateDoc::new(title.to_owned(), markdown.to_owned())
+                };
+                self.until_ready().await;
+                shortcut_api::create_doc(&self.configuration, create)
+                    .await
+                    .map_err(|err| anyhow!("Error while creating the doc {title}: {err:?}"))?
+            }
+        };
+        Ok(doc.app_url)
+    }
+
+    /// Search the stories which may be the one of the commit, with the words of its subject and
+    /// then of its branch, up to `limit`. Returns none when not reading from Shortcut.
+    pub async fn suggest_stories(
+        &self,
+        commit: &UnreleasedCommit,
+        limit: usize,
+    ) -> Result<Vec<SuggestedStory>> {
+        if !matches!(self.source, DataSource::Shortcut) {
+            return Ok(Vec::new());
+        }
+        let subject = commit
+            .message
+            .as_deref()
+            .and_then(|message| message.lines().next());
+        let queries = subject
+            .into_iter()
+            .chain(commit.merged_branch.as_deref())
+            .map(search_words)
+            .filter(|query| !query.is_empty());
+        let mut suggestions: Vec<SuggestedStory> = Vec::new();
+        for query in queries {
+            if suggestions.len() >= limit {
+                break;
+            }
+            let results = self
+                .with_retries("story search", 0, || {
+                    shortcut_api::search_stories(
+                        &self.configuration,
+                        &query,
+                        Some((limit as i64).min(SEARCH_PAGE_SIZE)),
+                        Some("slim"),
+                        None,
+                        None,
+                    )
+                })
+                .await
+                .map_err(|err| anyhow!("Error while searching the stories {query:?}: {err:?}"))?;
+            for story in results.data {
+                if suggestions.len() < limit
+                    && !suggestions.iter().any(|other| other.id == story.id)
+                {
+                    suggestions.push(SuggestedStory {
+                        id: story.id,
+                        name: story.name,
+                        app_url: story.app_url,
+                    });
+                }
+            }
+        }
+        Ok(suggestions)
+    }
+
+    /// Comment on the story. The request isn't retried, as a retry could post the comment twice
+    /// when the failed request went through.
+    pub async fn comment_story(&self, story_id: i64, text: &str) -> Result<()> {
+        let comment = CreateStoryComment::new(text.to_owned());
+        self.until_ready().await;
+        shortcut_api::create_story_comment(&self.configuration, story_id, comment)
+            .await
+            .map_err(|err| anyhow!("Error while commenting on story {story_id}: {err:?}"))?;
+        Ok(())
+    }
+
+    /// Add the label to the story, keeping its other labels, and cache the updated story
+    pub async fn add_story_label(&self, story: &Story, label: &str) -> Result<()> {
+        let labels = story
+            .labels
+            .iter()
+            .map(|label| label.name.as_str())
+            .chain([label])
+            .map(|name| CreateLabelParams::new(name.to_owned()))
+            .collect();
+        let update = UpdateStory {
+            labels: Some(labels),
+            ..UpdateStory::new()
+        };
+        let story = self
+            .with_retries("story", story.id, || {
+                shortcut_api::update_story(&self.configuration, story.id, update.clone())
+            })
+            .await
+            .map_err(|err| anyhow!("Error while labelling story {}: {err:?}", story.id))?;
+        self.cache.put("story", story.id, &story);
+        Ok(())
+    }
+
+    /// Retrieve the `entity` (story or epic) of each id concurrently from Shortcut, up to the
+    /// concurrency limit and within the rate limit, and cache them
+    async fn get_shortcut_data<T, E: std::fmt::Debug, F>(
+        &self,
+        entity: &str,
+        ids: impl Iterator<Item = i64>,
+        request: impl Fn(i64) -> F,
+    ) -> Result<Vec<T>>
+    where
+        T: std::fmt::Debug + Serialize + DeserializeOwned,
+        F: Future<Output = Result<T, ApiError<E>>>,
+    {
+        let items = self
+            .try_get_shortcut_data(entity, ids, request)
+            .await
+            .into_iter()
+            .map(|(id, result)| {
+                result.map_err(|err| anyhow!("Error while retrieving {entity} {id}: {err:?}"))
+            });
+        let (items, errors): (Vec<_>, Vec<_>) = items.partition(Result::is_ok);
         let items = items.into_iter().map(Result::unwrap).collect::<Vec<_>>();
         let errors = errors
             .into_iter()
@@ -150,62 +1164,1939 @@ impl ShortcutClient {
         Ok(items)
     }
 
-    pub async fn get_release<'a>(
+    /// Retrieve the `entity` of each id like [`Self::get_shortcut_data`], returning the result
+    /// of the request of each id instead of failing
+    async fn try_get_shortcut_data<T, E, F>(
+        &self,
+        entity: &str,
+        ids: impl Iterator<Item = i64>,
+        request: impl Fn(i64) -> F,
+    ) -> Vec<(i64, Result<T, ApiError<E>>)>
+    where
+        T: Serialize,
+        F: Future<Output = Result<T, ApiError<E>>>,
+    {
+        let request = &request;
+        let ids = ids.collect::<Vec<_>>();
+        let progress = &Progress::new(format!("Fetching {entity} data"), ids.len());
+        stream::iter(ids.into_iter().map(|id| async move {
+            let result = self.with_retries(entity, id, || request(id)).await;
+            if let Ok(item) = &result {
+                self.cache.put(entity, id, item);
+            }
+            progress.inc();
+            (id, result)
+        }))
+        .buffer_unordered(self.options.concurrency.get())
+        .collect()
+        .await
+    }
+
+    /// Wait until a request can be sent, ie the requests aren't paused after being rate limited
+    /// and the rate limiter allows it
+    async fn until_ready(&self) {
+        loop {
+            let paused_until = *self.paused_until.lock().expect("Poisoned lock");
+            match paused_until {
+                Some(paused_until) if paused_until > Instant::now() => {
+                    tokio::time::sleep_until(paused_until.into()).await
+                }
+                _ => break,
+            }
+        }
+        self.rate_limiter.until_ready().await;
+    }
+
+    /// Pause all requests for the delay
+    fn pause(&self, delay: Duration) {
+        let mut paused_until = self.paused_until.lock().expect("Poisoned lock");
+        let until = Instant::now() + delay;
+        if !paused_until.is_some_and(|paused_until| paused_until >= until) {
+            *paused_until = Some(until);
+        }
+    }
+
+    /// Send the request, retrying transient failures (connection errors and server errors) with
+    /// a jittered exponential backoff
+    async fn with_retries<T, E, F>(
+        &self,
+        entity: &str,
+        id: i64,
+        request: impl Fn() -> F,
+    ) -> Result<T, ApiError<E>>
+    where
+        F: Future<Output = Result<T, ApiError<E>>>,
+    {
+        let mut attempt = 1;
+        let mut rate_limited_count = 0;
+        loop {
+            self.until_ready().await;
+            match request().await {
+                Err(ApiError::ResponseError(response))
+                    if response.status == StatusCode::TOO_MANY_REQUESTS
+                        && rate_limited_count < MAX_RATE_LIMITED_RETRIES =>
+                {
+                    let delay = response
+                        .retry_after
+                        .as_deref()
+                        .and_then(parse_retry_after)
+                        .unwrap_or(DEFAULT_RATE_LIMIT_DELAY);
+                    warn!(
+                        entity,
+                        id,
+                        ?delay,
+                        "Rate limited by Shortcut, pausing the requests"
+                    );
+                    self.pause(delay);
+                    rate_limited_count += 1;
+                }
+                Err(err) if attempt < self.options.max_attempts.get() && is_transient(&err) => {
+                    let delay = retry_delay(attempt);
+                    warn!(
+                        entity,
+                        id,
+                        attempt,
+                        ?delay,
+                        "Retrying Shortcut request: {err}"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub async fn get_release(
         &self,
-        commits: Commits,
-        story_label_filter: StoryLabelFilter<'a>,
+        mut commits: Commits,
+        story_filter: &StoryFilter<'_>,
     ) -> Result<ReleaseContent> {
-        let mut stories = self.get_stories(&commits).await?;
-        if !story_label_filter.is_empty() {
-            stories.retain(|story| story_label_filter.filter(story));
+        let (mut stories, mut missing_story_ids, story_updates) = match &self.source {
+            DataSource::Shortcut => self.get_stories(&commits).await?,
+            DataSource::Cache => {
+                let stories = known_data("story", commits.story_ids(), |story_id| {
+                    let story: Cached<Story> = self.cache.get("story", story_id)?;
+                    Some(story.value)
+                });
+                (stories, Vec::new(), None)
+            }
+            DataSource::Fixtures(fixtures) => {
+                let stories = known_data("story", commits.story_ids(), |story_id| {
+                    fixtures.stories.get(&story_id).cloned()
+                });
+                (stories, Vec::new(), None)
+            }
+        };
+        if let Some(recorded) = &self.recorded {
+            recorded
+                .lock()
+                .expect("Poisoned lock")
+                .record_stories(&stories);
+        }
+        stories.retain(|story| {
+            if !story.archived {
+                return true;
+            }
+            match self.options.archived {
+                ArchivedPolicy::Include => true,
+                ArchivedPolicy::Warn => {
+                    warn!("Including the archived story {}", story.id);
+                    true
+                }
+                ArchivedPolicy::Exclude => {
+                    missing_story_ids.push((story.id, MissingStoryReason::Archived));
+                    false
+                }
+            }
+        });
+        let mut missing_stories = missing_story_ids
+            .into_iter()
+            .map(|(id, reason)| MissingStory {
+                id,
+                // Set by the workspaces, the client not knowing which one it's for
+                workspace: WorkspaceName::default(),
+                reason,
+                commits: u32::try_from(id)
+                    .ok()
+                    .and_then(|story_id| commits.story_commits.get(&StoryId::from(story_id)))
+                    .cloned()
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        missing_stories.sort_by_key(|missing_story| missing_story.id);
+        let mut duplicate_ids = collapse_duplicates(&mut stories, &mut commits.story_commits);
+        let mut prefetched_iterations = None;
+        if !story_filter.is_empty() {
+            let iterations = if story_filter.has_iterations() {
+                let iteration_ids = stories
+                    .iter()
+                    .filter_map(|story| story.iteration_id)
+                    .collect::<HashSet<_>>();
+                prefetched_iterations
+                    .insert(self.release_iterations(iteration_ids).await?)
+                    .as_slice()
+            } else {
+                &[]
+            };
+            let iterations = iterations
+                .iter()
+                .map(|iteration| (iteration.id, iteration))
+                .collect::<HashMap<_, _>>();
+            let workflow_states = self.workflow_states(&stories).await?;
+            let groups = if story_filter.has_teams() {
+                let groups = self.get_groups().await?;
+                story_filter.check_teams(groups);
+                groups
+            } else {
+                &[]
+            };
+            let groups = groups
+                .iter()
+                .map(|group| (group.id.as_str(), group))
+                .collect::<HashMap<_, _>>();
+            let projects = if story_filter.has_projects() {
+                let projects = self.get_projects().await?;
+                story_filter.check_projects(projects);
+                projects
+            } else {
+                &[]
+            };
+            let projects = projects
+                .iter()
+                .map(|project| (project.id, project))
+                .collect::<HashMap<_, _>>();
+            stories.retain(|story| {
+                let refs = StoryRefs {
+                    workflow_state: workflow_states.get(&story.workflow_state_id).copied(),
+                    team: story
+                        .group_id
+                        .as_deref()
+                        .and_then(|group_id| groups.get(group_id))
+                        .copied(),
+                    project: story
+                        .project_id
+                        .and_then(|project_id| projects.get(&project_id))
+                        .copied(),
+                    iteration: story
+                        .iteration_id
+                        .and_then(|iteration_id| iterations.get(&iteration_id))
+                        .copied(),
+                };
+                story_filter.filter(story, &refs)
+            });
+        }
+        stories.sort_by_key(|story| story.id);
+        commits
+            .epic_commits
+            .retain(|epic_id, _| story_filter.filter_epic(*epic_id));
+        let epic_ids = stories
+            .iter()
+            .filter_map(|story| story.epic_id)
+            .chain(commits.epic_commits.keys().copied())
+            .collect::<HashSet<_>>();
+        let mut epics = match &self.source {
+            DataSource::Shortcut => self.get_epics(epic_ids, story_updates.as_ref()).await?,
+            DataSource::Cache => known_data("epic", epic_ids, |epic_id| {
+                let epic: Cached<Epic> = self.cache.get("epic", epic_id)?;
+                Some(epic.value)
+            }),
+            DataSource::Fixtures(fixtures) => known_data("epic", epic_ids, |epic_id| {
+                fixtures.epics.get(&epic_id).cloned()
+            }),
+        };
+        if let Some(recorded) = &self.recorded {
+            recorded.lock().expect("Poisoned lock").record_epics(&epics);
+        }
+        epics.sort_by_key(|epic| epic.id);
+        let objective_ids = epics
+            .iter()
+            .filter_map(|epic| epic.milestone_id)
+            .collect::<HashSet<_>>();
+        let mut objectives = match &self.source {
+            DataSource::Shortcut => self.get_objectives(objective_ids).await?,
+            DataSource::Cache => known_data("milestone", objective_ids, |objective_id| {
+                let objective: Cached<Milestone> = self.cache.get("milestone", objective_id)?;
+                Some(objective.value)
+            }),
+            DataSource::Fixtures(fixtures) => {
+                known_data("milestone", objective_ids, |objective_id| {
+                    fixtures.objectives.get(&objective_id).cloned()
+                })
+            }
+        };
+        if let Some(recorded) = &self.recorded {
+            recorded
+                .lock()
+                .expect("Poisoned lock")
+                .record_objectives(&objectives);
+        }
+        objectives.sort_by_key(|objective| (objective.position, objective.id));
+        let iteration_ids = stories
+            .iter()
+            .filter_map(|story| story.iteration_id)
+            .collect::<HashSet<_>>();
+        let iterations = match prefetched_iterations {
+            Some(mut iterations) => {
+                iterations.retain(|iteration| iteration_ids.contains(&iteration.id));
+                iterations
+            }
+            None => self.release_iterations(iteration_ids).await?,
+        };
+        let has_owners = stories.iter().any(|story| !story.owner_ids.is_empty())
+            || epics.iter().any(|epic| !epic.owner_ids.is_empty());
+        let members = if has_owners {
+            self.get_members().await?
+        } else {
+            &[]
+        };
+        let members = members
+            .iter()
+            .map(|member| (member.id.as_str(), member))
+            .collect::<HashMap<_, _>>();
+        let mut stories = self.release_stories(stories, &members, &iterations).await?;
+        let mut breaking_commits = RepoToCommits::new();
+        let mut add_breaking_commits = |repo_to_commits: &RepoToCommits| {
+            let mut has_breaking_commits = false;
+            for (repo_name, commits) in repo_to_commits {
+                for commit in commits.iter().filter(|commit| is_breaking(commit)) {
+                    breaking_commits
+                        .entry(repo_name.clone())
+                        .or_default()
+                        .push(commit.clone());
+                    has_breaking_commits = true;
+                }
+            }
+            has_breaking_commits
+        };
+        for story in &mut stories {
+            let story_commits = u32::try_from(story.story.id)
+                .ok()
+                .and_then(|story_id| commits.story_commits.remove(&StoryId::from(story_id)));
+            if let Some(story_commits) = story_commits {
+                story.breaking = add_breaking_commits(&story_commits);
+                story.repositories = story_commits.keys().cloned().collect();
+                story
+                    .repositories
+                    .sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+                story.commits = story_commits;
+            }
+            story.duplicate_ids = duplicate_ids.remove(&story.story.id).unwrap_or_default();
+        }
+        for repo_to_commits in commits.epic_commits.values() {
+            add_breaking_commits(repo_to_commits);
         }
-        let epics = self.get_epics(stories.iter()).await?;
+        add_breaking_commits(&commits.unparsed_commits);
+        for commits in breaking_commits.values_mut() {
+            commits.sort_by_key(|commit| commit.committed_at);
+        }
+        let epics = epics
+            .into_iter()
+            .map(|epic| {
+                let epic_commits = commits
+                    .epic_commits
+                    .get(&epic.id)
+                    .cloned()
+                    .unwrap_or_default();
+                ReleaseEpic::new(epic, &members, &stories, epic_commits)
+            })
+            .collect();
         let Commits {
-            unparsed_commits, ..
+            unparsed_commits,
+            reverted_commit_pairs,
+            excluded_commits,
+            ..
         } = commits;
         let release = ReleaseContent {
             stories,
+            breaking_commits,
+            missing_stories,
             epics,
+            objectives,
+            iterations,
             unparsed_commits,
+            reverted_commit_pairs,
+            excluded_commits,
         };
         Ok(release)
     }
 
-    async fn get_stories(&self, commits: &Commits) -> Result<Vec<Story>> {
-        let mut stories: Vec<Story> = self
-            .get_shortcut_data(commits.story_commits.keys().map(|story_id| {
-                let story_id: &u32 = story_id.as_ref();
-                shortcut_api::get_story(&self.configuration, *story_id as i64).map_err(move |err| {
-                    anyhow!("Error while retrieving story {}: {:?}", story_id, err)
+    /// Retrieve the iterations of the release from the source, by start date
+    async fn release_iterations(&self, iteration_ids: HashSet<i64>) -> Result<Vec<Iteration>> {
+        let mut iterations = match &self.source {
+            DataSource::Shortcut => self.get_iterations(iteration_ids).await?,
+            DataSource::Cache => known_data("iteration", iteration_ids, |iteration_id| {
+                let iteration: Cached<Iteration> = self.cache.get("iteration", iteration_id)?;
+                Some(iteration.value)
+            }),
+            DataSource::Fixtures(fixtures) => {
+                known_data("iteration", iteration_ids, |iteration_id| {
+                    fixtures.iterations.get(&iteration_id).cloned()
                 })
-            }))
-            .await?;
-        stories.sort_by_key(|story| story.id);
+            }
+        };
+        if let Some(recorded) = &self.recorded {
+            recorded
+                .lock()
+                .expect("Poisoned lock")
+                .record_iterations(&iterations);
+        }
+        iterations.sort_by(|a, b| (&a.start_date, a.id).cmp(&(&b.start_date, b.id)));
+        Ok(iterations)
+    }
+
+    /// Resolve the data referenced by the stories, like their owners and team
+    async fn release_stories(
+        &self,
+        stories: Vec<Story>,
+        members: &HashMap<&str, &Member>,
+        iterations: &[Iteration],
+    ) -> Result<Vec<ReleaseStory>> {
+        let iterations = iterations
+            .iter()
+            .map(|iteration| (iteration.id, iteration))
+            .collect::<HashMap<_, _>>();
+        let groups = if stories.iter().any(|story| story.group_id.is_some()) {
+            self.get_groups().await?
+        } else {
+            &[]
+        };
+        let groups = groups
+            .iter()
+            .map(|group| (group.id.as_str(), group))
+            .collect::<HashMap<_, _>>();
+        let workflow_states = self.workflow_states(&stories).await?;
+        let has_custom_fields = stories.iter().any(|story| {
+            story
+                .custom_fields
+                .as_ref()
+                .is_some_and(|fields| !fields.is_empty())
+        });
+        let custom_fields = if has_custom_fields {
+            self.get_custom_fields().await?
+        } else {
+            &[]
+        };
+        let custom_fields = custom_fields
+            .iter()
+            .map(|field| (field.id.as_str(), field))
+            .collect::<HashMap<_, _>>();
+        let stories = stories
+            .into_iter()
+            .map(|mut story| {
+                // The tasks are listed in the order of the story, as a checklist
+                story.tasks.sort_by_key(|task| task.position);
+                let tasks_done = story.tasks.iter().filter(|task| task.complete).count();
+                let owners = resolve_owners(&story.owner_ids, members);
+                let team = story
+                    .group_id
+                    .as_deref()
+                    .and_then(|group_id| groups.get(group_id))
+                    .map(|group| StoryTeam {
+                        id: group.id.clone(),
+                        name: group.name.clone(),
+                        mention_name: group.mention_name.clone(),
+                    });
+                let iteration = story
+                    .iteration_id
+                    .and_then(|iteration_id| iterations.get(&iteration_id))
+                    .map(|iteration| StoryIteration {
+                        id: iteration.id,
+                        name: iteration.name.clone(),
+                        start_date: iteration.start_date.clone(),
+                        end_date: iteration.end_date.clone(),
+                    });
+                let workflow_state = workflow_states.get(&story.workflow_state_id);
+                let story_custom_fields = story
+                    .custom_fields
+                    .iter()
+                    .flatten()
+                    .filter_map(|story_field| {
+                        let field = custom_fields.get(story_field.field_id.as_str())?;
+                        // The value of the story may be outdated when the option was renamed
+                        let value = field
+                            .values
+                            .iter()
+                            .flatten()
+                            .find(|value| value.id == story_field.value_id)
+                            .map_or(&story_field.value, |value| &value.value);
+                        Some((field.name.clone(), value.clone()))
+                    })
+                    .collect();
+                ReleaseStory {
+                    custom_fields: story_custom_fields,
+                    workflow_state_name: workflow_state.map(|state| state.name.clone()),
+                    workflow_state_done: workflow_state
+                        .is_some_and(|state| state.r#type == DONE_WORKFLOW_STATE_TYPE),
+                    tasks_done,
+                    overdue: false,
+                    breaking: false,
+                    unreleased_repositories: Vec::new(),
+                    // Set by the workspaces, the client not knowing which one it's for
+                    workspace: WorkspaceName::default(),
+                    repositories: Vec::new(),
+                    commits: RepoToCommits::new(),
+                    duplicate_ids: Vec::new(),
+                    story,
+                    owners,
+                    team,
+                    iteration,
+                }
+            })
+            .collect();
         Ok(stories)
     }
 
-    async fn get_epics(&self, stories: impl Iterator<Item = &Story>) -> Result<Vec<Epic>> {
-        let epic_ids = stories
-            .filter_map(|story| story.epic_id)
-            .collect::<HashSet<_>>();
-        let mut epics = self
-            .get_shortcut_data(epic_ids.into_iter().map(|epic_id| {
-                shortcut_api::get_epic(&self.configuration, epic_id).map_err(move |err| {
-                    anyhow!("Error while retrieving epic {}: {:?}", epic_id, err)
+    /// The workflow states of the workspace by id, retrieving its workflows only when there are
+    /// stories
+    async fn workflow_states(&self, stories: &[Story]) -> Result<HashMap<i64, &WorkflowState>> {
+        let workflows = if stories.is_empty() {
+            &[]
+        } else {
+            self.get_workflows().await?
+        };
+        let workflow_states = workflows
+            .iter()
+            .flat_map(|workflow| &workflow.states)
+            .map(|state| (state.id, state))
+            .collect();
+        Ok(workflow_states)
+    }
+
+    async fn get_members(&self) -> Result<&[Member]> {
+        let members = self
+            .members
+            .get_or_try_init(|| {
+                self.get_workspace_list("members", || {
+                    shortcut_api::list_members(&self.configuration, None)
                 })
-            }))
+            })
             .await?;
-        epics.sort_by_key(|epic| epic.id);
+        Ok(members)
+    }
+
+    async fn get_workflows(&self) -> Result<&[Workflow]> {
+        let workflows = self
+            .workflows
+            .get_or_try_init(|| {
+                self.get_workspace_list("workflows", || {
+                    shortcut_api::list_workflows(&self.configuration)
+                })
+            })
+            .await?;
+        Ok(workflows)
+    }
+
+    async fn get_custom_fields(&self) -> Result<&[CustomField]> {
+        let custom_fields = self
+            .custom_fields
+            .get_or_try_init(|| {
+                self.get_workspace_list("custom_fields", || {
+                    shortcut_api::list_custom_fields(&self.configuration)
+                })
+            })
+            .await?;
+        Ok(custom_fields)
+    }
+
+    async fn get_projects(&self) -> Result<&[Project]> {
+        let projects = self
+            .projects
+            .get_or_try_init(|| {
+                self.get_workspace_list("projects", || {
+                    shortcut_api::list_projects(&self.configuration)
+                })
+            })
+            .await?;
+        Ok(projects)
+    }
+
+    async fn get_groups(&self) -> Result<&[Group]> {
+        let groups = self
+            .groups
+            .get_or_try_init(|| {
+                self.get_workspace_list("groups", || shortcut_api::list_groups(&self.configuration))
+            })
+            .await?;
+        Ok(groups)
+    }
+
+    /// Retrieve the list of all the `entity` (members, groups...) of the workspace, from the cache
+    /// when fresh
+    async fn get_workspace_list<T, E: std::fmt::Debug, F>(
+        &self,
+        entity: &str,
+        request: impl Fn() -> F,
+    ) -> Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<Vec<T>, ApiError<E>>>,
+    {
+        let values = match &self.source {
+            DataSource::Shortcut => match self.cache.get_list(entity) {
+                Some(cached) => cached.value,
+                None => {
+                    let values = self
+                        .with_retries(entity, 0, request)
+                        .await
+                        .map_err(|err| anyhow!("Error while retrieving the {entity}: {err:?}"))?;
+                    self.cache.put_list(entity, &values);
+                    values
+                }
+            },
+            DataSource::Cache => self
+                .cache
+                .get_list(entity)
+                .map(|cached| cached.value)
+                .unwrap_or_default(),
+            DataSource::Fixtures(fixtures) => fixtures.list(entity)?,
+        };
+        if let Some(recorded) = &self.recorded {
+            recorded
+                .lock()
+                .expect("Poisoned lock")
+                .record_list(entity, &values)?;
+        }
+        Ok(values)
+    }
+
+    /// Retrieve the stories referenced by the commits, using the cached ones that weren't updated
+    /// since they were cached. Also returns the ids of the stories which couldn't be retrieved
+    /// because of the story itself, like a deleted one, and the updates used to revalidate the
+    /// cached stories, if any.
+    async fn get_stories(
+        &self,
+        commits: &Commits,
+    ) -> Result<(
+        Vec<Story>,
+        Vec<(i64, MissingStoryReason)>,
+        Option<StoryUpdates>,
+    )> {
+        let story_ids = commits.story_ids();
+        let cached_stories = story_ids
+            .iter()
+            .filter_map(|story_id| Some((*story_id, self.cache.get("story", *story_id)?)))
+            .collect::<HashMap<_, Cached<Story>>>();
+        let oldest_cached_story = cached_stories.values().map(|story| story.written_at).min();
+        let story_updates = match oldest_cached_story {
+            Some(since) => self.get_story_updates(since, cached_stories.len()).await?,
+            None => None,
+        };
+        let (mut stories, missing_ids) = partition_cached(story_ids, cached_stories, |story| {
+            story_updates
+                .as_ref()
+                .is_some_and(|updates| updates.is_story_up_to_date(&story.value))
+        });
+        // The revalidated stories are up to date as of now, so the next run only has to search
+        // the stories updated since then
+        for story in &stories {
+            self.cache.put("story", story.id, story);
+        }
+        debug!(
+            cached = stories.len(),
+            missing = missing_ids.len(),
+            "Revalidated the cached stories"
+        );
+        let results = self
+            .try_get_shortcut_data("story", missing_ids.into_iter(), |story_id| {
+                shortcut_api::get_story(&self.configuration, story_id)
+            })
+            .await;
+        let mut missing_story_ids = Vec::new();
+        let mut errors = Vec::new();
+        for (story_id, result) in results {
+            match result {
+                Ok(story) => stories.push(story),
+                Err(err) => match missing_story_reason(&err) {
+                    Some(reason) => missing_story_ids.push((story_id, reason)),
+                    None => {
+                        errors.push(anyhow!("Error while retrieving story {story_id}: {err:?}"))
+                    }
+                },
+            }
+        }
+        if !errors.is_empty() {
+            bail!("Got the following errors: {:?}", errors);
+        }
+        Ok((stories, missing_story_ids, story_updates))
+    }
+
+    /// Retrieve the epics, using the cached ones none of whose stories were updated since they
+    /// were cached
+    async fn get_epics(
+        &self,
+        epic_ids: HashSet<i64>,
+        story_updates: Option<&StoryUpdates>,
+    ) -> Result<Vec<Epic>> {
+        let cached_epics = epic_ids
+            .iter()
+            .filter_map(|epic_id| Some((*epic_id, self.cache.get("epic", *epic_id)?)))
+            .collect::<HashMap<_, Cached<Epic>>>();
+        let (mut epics, missing_ids) = partition_cached(epic_ids, cached_epics, |epic| {
+            story_updates.is_some_and(|updates| updates.is_epic_up_to_date(epic))
+        });
+        epics.extend(
+            self.get_shortcut_data("epic", missing_ids.into_iter(), |epic_id| {
+                shortcut_api::get_epic(&self.configuration, epic_id)
+            })
+            .await?,
+        );
         Ok(epics)
     }
+
+    /// Retrieve the iterations, using the fresh cached ones as iterations rarely change once
+    /// stories are shipped
+    async fn get_iterations(&self, iteration_ids: HashSet<i64>) -> Result<Vec<Iteration>> {
+        let cached_iterations = iteration_ids
+            .iter()
+            .filter_map(|iteration_id| {
+                Some((*iteration_id, self.cache.get("iteration", *iteration_id)?))
+            })
+            .collect::<HashMap<_, Cached<Iteration>>>();
+        let (mut iterations, missing_ids) =
+            partition_cached(iteration_ids, cached_iterations, |_| true);
+        iterations.extend(
+            self.get_shortcut_data("iteration", missing_ids.into_iter(), |iteration_id| {
+                shortcut_api::get_iteration(&self.configuration, iteration_id)
+            })
+            .await?,
+        );
+        Ok(iterations)
+    }
+
+    /// Retrieve the objectives (milestones) of the epics, using the fresh cached ones
+    async fn get_objectives(&self, objective_ids: HashSet<i64>) -> Result<Vec<Milestone>> {
+        let cached_objectives = objective_ids
+            .iter()
+            .filter_map(|objective_id| {
+                Some((*objective_id, self.cache.get("milestone", *objective_id)?))
+            })
+            .collect::<HashMap<_, Cached<Milestone>>>();
+        let (mut objectives, missing_ids) =
+            partition_cached(objective_ids, cached_objectives, |_| true);
+        objectives.extend(
+            self.get_shortcut_data("milestone", missing_ids.into_iter(), |objective_id| {
+                shortcut_api::get_milestone(&self.configuration, objective_id)
+            })
+            .await?,
+        );
+        Ok(objectives)
+    }
+
+    /// Search the stories updated since `since`, or `None` when the search would take more
+    /// requests than retrieving the `cached_count` cached stories again
+    async fn get_story_updates(
+        &self,
+        since: SystemTime,
+        cached_count: usize,
+    ) -> Result<Option<StoryUpdates>> {
+        // The search only filters by day, in a time zone that isn't necessarily UTC, so it
+        // starts a day earlier to cover the whole period
+        let since_date = DateTime::<Utc>::from(since).date_naive().pred_opt();
+        let Some(since_date) = since_date else {
+            return Ok(None);
+        };
+        let query = format!("updated:{}..*", since_date.format("%Y-%m-%d"));
+        let mut updates = StoryUpdates {
+            since: since_date
+                .and_hms_opt(0, 0, 0)
+                .expect("Midnight should be valid")
+                .and_utc()
+                .into(),
+            updated_at: HashMap::new(),
+            epic_ids: HashSet::new(),
+        };
+        let mut next = None;
+        for page in 0.. {
+            let results = self
+                .with_retries("story search", page, || {
+                    shortcut_api::search_stories(
+                        &self.configuration,
+                        &query,
+                        Some(SEARCH_PAGE_SIZE),
+                        Some("slim"),
+                        next.as_deref(),
+                        None,
+                    )
+                })
+                .await
+                .map_err(|err| anyhow!("Error while searching the updated stories: {err:?}"))?;
+            if page == 0 && results.total > cached_count as i64 * SEARCH_PAGE_SIZE {
+                debug!(
+                    total = results.total,
+                    cached_count, "Too many updated stories to revalidate the cache"
+                );
+                return Ok(None);
+            }
+            for story in &results.data {
+                updates
+                    .updated_at
+                    .insert(story.id, story.updated_at.clone());
+                updates.epic_ids.extend(story.epic_id);
+            }
+            next = match results.next.as_deref() {
+                Some(next) if !results.data.is_empty() => {
+                    Some(next_page_token(&self.configuration.base_path, next)?)
+                }
+                _ => break,
+            };
+        }
+        Ok(Some(updates))
+    }
+}
+
+/// Stories updated since a given time, to revalidate the cached responses
+#[derive(Debug)]
+struct StoryUpdates {
+    since: SystemTime,
+    /// Date of the last update of each updated story
+    updated_at: HashMap<i64, Option<String>>,
+    /// Ids of the epics of the updated stories
+    epic_ids: HashSet<i64>,
+}
+
+impl StoryUpdates {
+    fn is_story_up_to_date(&self, story: &Story) -> bool {
+        match self.updated_at.get(&story.id) {
+            Some(updated_at) => *updated_at == story.updated_at,
+            None => true,
+        }
+    }
+
+    /// Whether the epic was cached within the searched period, and none of its stories were
+    /// updated since then. Updates of the epic alone aren't detected.
+    fn is_epic_up_to_date(&self, epic: &Cached<Epic>) -> bool {
+        epic.written_at >= self.since && !self.epic_ids.contains(&epic.value.id)
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, AsRef, FromStr, Display, Into)]
+/// The owners of each id, in the same order, leaving out the ones which aren't members of the
+/// workspace
+fn resolve_owners(owner_ids: &[String], members: &HashMap<&str, &Member>) -> Vec<StoryOwner> {
+    owner_ids
+        .iter()
+        .filter_map(|owner_id| {
+            let profile = &members.get(owner_id.as_str())?.profile;
+            Some(StoryOwner {
+                id: owner_id.clone(),
+                name: profile
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| profile.mention_name.clone()),
+                mention_name: profile.mention_name.clone(),
+                email: profile.email_address.clone(),
+            })
+        })
+        .collect()
+}
+
+/// The words of the text as a search query, leaving out the punctuation which Shortcut could read
+/// as search operators, like `type:` in a Conventional Commits subject
+fn search_words(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 1)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Remove the stories duplicating another story of the release according to their story links,
+/// moving their commits to the story they duplicate (following chains of duplicates). Returns
+/// the ids of the removed stories, by id of the story they were collapsed into.
+fn collapse_duplicates(
+    stories: &mut Vec<Story>,
+    story_commits: &mut HashMap<StoryId, RepoToCommits>,
+) -> HashMap<i64, Vec<i64>> {
+    let release_ids = stories.iter().map(|story| story.id).collect::<HashSet<_>>();
+    let duplicated_ids = stories
+        .iter()
+        .filter_map(|story| {
+            let link = story.story_links.iter().find(|link| {
+                link.verb == DUPLICATES_VERB
+                    && link.subject_id == story.id
+                    && release_ids.contains(&link.object_id)
+            })?;
+            Some((story.id, link.object_id))
+        })
+        .collect::<HashMap<_, _>>();
+    let canonical_id = |mut story_id: i64| {
+        let mut visited_ids = HashSet::new();
+        while let Some(&duplicated_id) = duplicated_ids.get(&story_id) {
+            // Stories duplicating each other are all kept
+            if !visited_ids.insert(story_id) {
+                break;
+            }
+            story_id = duplicated_id;
+        }
+        story_id
+    };
+    let mut duplicate_ids: HashMap<i64, Vec<i64>> = HashMap::new();
+    stories.retain(|story| {
+        let canonical_id = canonical_id(story.id);
+        if canonical_id != story.id {
+            debug!(
+                story_id = story.id,
+                canonical_id, "Collapsing duplicate story"
+            );
+            duplicate_ids
+                .entry(canonical_id)
+                .or_default()
+                .push(story.id);
+        }
+        canonical_id == story.id
+    });
+    let story_key = |story_id: i64| u32::try_from(story_id).ok().map(StoryId::from);
+    for (canonical_id, ids) in &mut duplicate_ids {
+        ids.sort_unstable();
+        let Some(canonical_key) = story_key(*canonical_id) else {
+            continue;
+        };
+        let mut merged_commits = story_commits.remove(&canonical_key).unwrap_or_default();
+        for story_id in ids.iter() {
+            let duplicate_commits = story_key(*story_id)
+                .and_then(|story_key| story_commits.remove(&story_key))
+                .unwrap_or_default();
+            for (repo_name, commits) in duplicate_commits {
+                merged_commits.entry(repo_name).or_default().extend(commits);
+            }
+        }
+        for commits in merged_commits.values_mut() {
+            commits.sort_by_key(|commit| commit.committed_at);
+        }
+        story_commits.insert(canonical_key, merged_commits);
+    }
+    duplicate_ids
+}
+
+/// The GitHub repository, as a lowercase `<owner>/<name>`, of the URL of a pull request or commit
+fn github_url_repository(url: &str) -> Option<String> {
+    lazy_static! {
+        static ref GITHUB_URL_RE: Regex =
+            Regex::new(r"^https://github\.com/([^/]+/[^/]+)/(?:pull|commit)/")
+                .expect("Could not compile GITHUB_URL_RE");
+    };
+    Some(GITHUB_URL_RE.captures(url)?[1].to_lowercase())
+}
+
+/// The known `entity` (story or epic) of each id, leaving out the unknown ones
+fn known_data<T>(
+    entity: &str,
+    ids: impl IntoIterator<Item = i64>,
+    get: impl Fn(i64) -> Option<T>,
+) -> Vec<T> {
+    let mut unknown_count = 0;
+    let items = ids
+        .into_iter()
+        .filter_map(|id| {
+            let item = get(id);
+            unknown_count += usize::from(item.is_none());
+            item
+        })
+        .collect();
+    if unknown_count > 0 {
+        warn!("Leaving out {unknown_count} unknown {entity} ids");
+    }
+    items
+}
+
+/// Split the ids into the cached responses that are up to date, and the ids to retrieve
+fn partition_cached<T>(
+    ids: impl IntoIterator<Item = i64>,
+    mut cached: HashMap<i64, Cached<T>>,
+    is_up_to_date: impl Fn(&Cached<T>) -> bool,
+) -> (Vec<T>, Vec<i64>) {
+    let mut items = Vec::new();
+    let mut missing_ids = Vec::new();
+    for id in ids {
+        match cached.remove(&id) {
+            Some(item) if is_up_to_date(&item) => items.push(item.value),
+            _ => missing_ids.push(id),
+        }
+    }
+    (items, missing_ids)
+}
+
+/// Token of the next page of search results, which Shortcut gives as the path of that page
+fn next_page_token(base_path: &str, next: &str) -> Result<String> {
+    let url = Url::parse(base_path)?.join(next)?;
+    url.query_pairs()
+        .find(|(key, _)| key == "next")
+        .map(|(_, token)| token.into_owned())
+        .ok_or_else(|| anyhow!("No token in the next page {next:?} of the search"))
+}
+
+/// Whether the request may succeed when retried, ie it failed to connect or with a server error
+fn is_transient<E>(err: &ApiError<E>) -> bool {
+    match err {
+        ApiError::Reqwest(err) => !err.is_builder() && !err.is_status(),
+        ApiError::Io(_) => true,
+        ApiError::ResponseError(response) => response.status.is_server_error(),
+        ApiError::Serde(_) => false,
+    }
+}
+
+/// Why the story couldn't be retrieved, when the request failed because of the story itself
+fn missing_story_reason<E>(err: &ApiError<E>) -> Option<MissingStoryReason> {
+    match err {
+        ApiError::ResponseError(response) if response.status == StatusCode::NOT_FOUND => {
+            Some(MissingStoryReason::NotFound)
+        }
+        ApiError::ResponseError(response) if response.status == StatusCode::FORBIDDEN => {
+            Some(MissingStoryReason::PermissionDenied)
+        }
+        _ => None,
+    }
+}
+
+/// Delay requested by a `Retry-After` header, either as a number of seconds or an HTTP date
+fn parse_retry_after(retry_after: &str) -> Option<Duration> {
+    if let Ok(seconds) = retry_after.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(retry_after).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// Delay before retrying a request for the `attempt`-th time, doubling from
+/// [`INITIAL_RETRY_DELAY`] up to [`MAX_RETRY_DELAY`], of which a random half is waited so that
+/// concurrent requests don't retry at the same time
+fn retry_delay(attempt: u32) -> Duration {
+    let delay = INITIAL_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(MAX_RETRY_DELAY);
+    delay / 2 + delay.mul_f64(rand::thread_rng().gen_range(0.0..0.5))
+}
+
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    AsRef,
+    FromStr,
+    Display,
+    From,
+    Into,
+    Deserialize,
+    Serialize,
+)]
+#[serde(transparent)]
 pub struct StoryId(u32);
 
-#[derive(Debug, Serialize)]
+/// Verb of the story links from a story to the one it duplicates
+const DUPLICATES_VERB: &str = "duplicates";
+
+/// Type of the workflow states of done stories, the others being `unstarted` and `started`
+const DONE_WORKFLOW_STATE_TYPE: &str = "done";
+
+/// Key grouping the stories without a team
+pub const NO_TEAM: &str = "unassigned";
+
+/// Key grouping the stories without an iteration
+pub const NO_ITERATION: &str = "unscheduled";
+
+/// A story, along with the data it references by id
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReleaseStory {
+    /// The story, whose `custom_fields` are replaced by the resolved ones
+    #[serde(flatten, serialize_with = "serialize_without_custom_fields")]
+    pub story: Story,
+    /// Workspace the story belongs to, as configured
+    pub workspace: WorkspaceName,
+    /// Repositories with commits of the story, by name
+    pub repositories: Vec<RepositoryName>,
+    /// Commits of the story, including the ones of its duplicates, by repository
+    #[serde(skip)]
+    pub commits: RepoToCommits,
+    /// Ids of the stories of the release marked as duplicating this one, which were collapsed
+    /// into it along with their commits
+    pub duplicate_ids: Vec<i64>,
+    /// Owners of the story, in the order of `owner_ids`, leaving out the ones which aren't
+    /// members of the workspace
+    pub owners: Vec<StoryOwner>,
+    /// Group owning the story, if any
+    pub team: Option<StoryTeam>,
+    /// Iteration (sprint) of the story, if any
+    pub iteration: Option<StoryIteration>,
+    /// Values of the custom fields of the story, by field name
+    pub custom_fields: BTreeMap<String, String>,
+    /// Name of the workflow state of the story, eg `In Review`
+    pub workflow_state_name: Option<String>,
+    /// Whether the workflow state of the story is a done one
+    pub workflow_state_done: bool,
+    /// Number of complete tasks of the story
+    pub tasks_done: usize,
+    /// Whether the deadline of the story is before the date of the release, see
+    /// [`ReleaseContent::flag_overdue`]
+    pub overdue: bool,
+    /// Whether the story is a breaking change, having the breaking label or a breaking commit
+    pub breaking: bool,
+    /// Repositories where Shortcut links merged pull requests or commits to the story, but
+    /// without commits of the story in the release, see
+    /// [`ReleaseContent::flag_unreleased_repositories`]
+    pub unreleased_repositories: Vec<RepositoryName>,
+}
+
+/// Serialize the story without its unresolved `custom_fields`, referencing the fields and their
+/// values by id
+fn serialize_without_custom_fields<S: Serializer>(
+    story: &Story,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut story = match serde_json::to_value(story).map_err(S::Error::custom)? {
+        serde_json::Value::Object(story) => story,
+        _ => {
+            return Err(S::Error::custom(
+                "A story should be serialized as an object",
+            ))
+        }
+    };
+    story.remove("custom_fields");
+    story.serialize(serializer)
+}
+
+/// A group (team) of the workspace owning a story
+#[derive(Debug, Clone, Serialize)]
+pub struct StoryTeam {
+    pub id: String,
+    pub name: String,
+    pub mention_name: String,
+}
+
+/// An iteration (sprint) of the workspace a story belongs to
+#[derive(Debug, Clone, Serialize)]
+pub struct StoryIteration {
+    pub id: i64,
+    pub name: String,
+    pub start_date: String,
+    pub end_date: String,
+}
+
+/// A member of the workspace owning a story or an epic
+#[derive(Debug, Clone, Serialize)]
+pub struct StoryOwner {
+    pub id: String,
+    /// Display name of the member, or its mention name without one
+    pub name: String,
+    pub mention_name: String,
+    pub email: Option<String>,
+}
+
+/// Why a story referenced by commits is missing from the release
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingStoryReason {
+    #[display(fmt = "not found")]
+    NotFound,
+    #[display(fmt = "archived")]
+    Archived,
+    #[display(fmt = "permission denied")]
+    PermissionDenied,
+}
+
+/// A story referenced by commits which couldn't be added to the release, eg because of a typo
+/// in its id
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingStory {
+    pub id: i64,
+    /// Workspace the story was retrieved from, as configured
+    pub workspace: WorkspaceName,
+    pub reason: MissingStoryReason,
+    /// Commits referencing the story, by repository
+    pub commits: RepoToCommits,
+}
+
+#[derive(Debug, Default, Serialize)]
 pub struct ReleaseContent {
-    pub stories: Vec<Story>,
-    pub epics: Vec<Epic>,
+    pub stories: Vec<ReleaseStory>,
+    /// Stories referenced by commits which don't exist, are archived or can't be accessed, by id
+    pub missing_stories: Vec<MissingStory>,
+    /// Commits marked as breaking changes, whether they reference a story or not, by repository
+    pub breaking_commits: RepoToCommits,
+    pub epics: Vec<ReleaseEpic>,
+    /// Objectives (milestones) of the epics, in the order of the workspace
+    pub objectives: Vec<Milestone>,
+    /// Iterations of the stories, by start date
+    pub iterations: Vec<Iteration>,
     pub unparsed_commits: RepoToCommits,
+    /// Number of commit and revert pairs removed from the commits
+    pub reverted_commit_pairs: usize,
+    /// Number of commits excluded by their message
+    pub excluded_commits: usize,
+}
+
+/// Group the stories by the name of their team, the stories without a team being under
+/// [`NO_TEAM`]
+pub fn group_by_team(stories: &[ReleaseStory]) -> BTreeMap<String, Vec<ReleaseStory>> {
+    let mut stories_by_team: BTreeMap<String, Vec<ReleaseStory>> = BTreeMap::new();
+    for story in stories {
+        stories_by_team
+            .entry(team_name(story).to_string())
+            .or_default()
+            .push(story.clone());
+    }
+    stories_by_team
+}
+
+/// Sum the estimates of the stories by the name of their team, the stories without a team being
+/// under [`NO_TEAM`]
+pub fn points_by_team(stories: &[ReleaseStory]) -> BTreeMap<String, i64> {
+    let mut points_by_team: BTreeMap<String, i64> = BTreeMap::new();
+    for story in stories {
+        *points_by_team
+            .entry(team_name(story).to_string())
+            .or_default() += story.story.estimate.unwrap_or_default();
+    }
+    points_by_team
+}
+
+fn team_name(story: &ReleaseStory) -> &str {
+    story
+        .team
+        .as_ref()
+        .map_or(NO_TEAM, |team| team.name.as_str())
+}
+
+/// Sum of the estimates of the stories, the unestimated ones counting for none
+pub fn total_points<'a>(stories: impl IntoIterator<Item = &'a ReleaseStory>) -> i64 {
+    stories
+        .into_iter()
+        .filter_map(|story| story.story.estimate)
+        .sum()
+}
+
+/// Group the stories by the name of their iteration, the stories without an iteration being
+/// under [`NO_ITERATION`]
+pub fn group_by_iteration(stories: &[ReleaseStory]) -> BTreeMap<String, Vec<ReleaseStory>> {
+    let mut stories_by_iteration: BTreeMap<String, Vec<ReleaseStory>> = BTreeMap::new();
+    for story in stories {
+        let iteration_name = story
+            .iteration
+            .as_ref()
+            .map_or(NO_ITERATION, |iteration| iteration.name.as_str());
+        stories_by_iteration
+            .entry(iteration_name.to_string())
+            .or_default()
+            .push(story.clone());
+    }
+    stories_by_iteration
+}
+
+/// Split the stories into the ones with the label and the others
+pub fn split_by_label(
+    stories: &[ReleaseStory],
+    label: &str,
+) -> (Vec<ReleaseStory>, Vec<ReleaseStory>) {
+    stories
+        .iter()
+        .cloned()
+        .partition(|story| has_label(story, label))
+}
+
+/// Whether the story has the label
+pub fn has_label(story: &ReleaseStory, label: &str) -> bool {
+    story
+        .story
+        .labels
+        .iter()
+        .any(|story_label| story_label.name == label)
+}
+
+/// A section of the release notes, along with its stories
+#[derive(Debug, Serialize)]
+pub struct ReleaseSection {
+    pub heading: String,
+    pub label: String,
+    pub stories: Vec<ReleaseStory>,
+}
+
+/// Group the stories in the first of the sections whose label they have, leaving out the
+/// sections without stories. Also returns the stories without the label of any section.
+pub fn group_by_section(
+    sections: &[SectionConfiguration],
+    stories: &[ReleaseStory],
+) -> (Vec<ReleaseSection>, Vec<ReleaseStory>) {
+    let mut section_stories = vec![Vec::new(); sections.len()];
+    let mut unsectioned_stories = Vec::new();
+    for story in stories {
+        let section_index = sections
+            .iter()
+            .position(|section| has_label(story, &section.label));
+        match section_index {
+            Some(section_index) => section_stories[section_index].push(story.clone()),
+            None => unsectioned_stories.push(story.clone()),
+        }
+    }
+    let sections = sections
+        .iter()
+        .zip(section_stories)
+        .filter(|(_, stories)| !stories.is_empty())
+        .map(|(section, stories)| ReleaseSection {
+            heading: section.heading.clone(),
+            label: section.label.clone(),
+            stories,
+        })
+        .collect();
+    (sections, unsectioned_stories)
+}
+
+/// An epic, along with its owners and progress
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseEpic {
+    #[serde(flatten)]
+    pub epic: Epic,
+    /// Owners of the epic, in the order of `owner_ids`, leaving out the ones which aren't
+    /// members of the workspace
+    pub owners: Vec<StoryOwner>,
+    pub progress: EpicProgress,
+    /// Number of stories of the release in the epic
+    pub release_stories: usize,
+    /// Sum of the estimates of the stories of the release in the epic
+    pub release_points: i64,
+    /// Commits referencing the epic itself rather than one of its stories, by repository
+    pub commits: RepoToCommits,
+}
+
+impl ReleaseEpic {
+    fn new(
+        epic: Epic,
+        members: &HashMap<&str, &Member>,
+        stories: &[ReleaseStory],
+        commits: RepoToCommits,
+    ) -> Self {
+        let stats = &epic.stats;
+        let progress = EpicProgress {
+            stories_total: stats.num_stories_total,
+            stories_started: stats.num_stories_started,
+            stories_done: stats.num_stories_done,
+            points_total: stats.num_points,
+            points_started: stats.num_points_started,
+            points_done: stats.num_points_done,
+            percent_done: match stats.num_stories_total {
+                0 => 0,
+                total => stats.num_stories_done * 100 / total,
+            },
+            complete: stats.num_stories_done == stats.num_stories_total,
+        };
+        let epic_stories = stories
+            .iter()
+            .filter(|story| story.story.epic_id == Some(epic.id))
+            .collect::<Vec<_>>();
+        Self {
+            owners: resolve_owners(&epic.owner_ids, members),
+            release_stories: epic_stories.len(),
+            release_points: total_points(epic_stories),
+            commits,
+            epic,
+            progress,
+        }
+    }
+}
+
+/// Progress of an epic, counting all its stories rather than the ones of the release
+#[derive(Debug, Clone, Serialize)]
+pub struct EpicProgress {
+    pub stories_total: i64,
+    pub stories_started: i64,
+    pub stories_done: i64,
+    pub points_total: i64,
+    pub points_started: i64,
+    pub points_done: i64,
+    /// Percentage of the stories which are done, rounded down
+    pub percent_done: i64,
+    /// Whether all the stories of the epic are done
+    pub complete: bool,
+}
+
+/// An objective (milestone), along with its epics containing stories of the release
+#[derive(Debug, Serialize)]
+pub struct ReleaseObjective {
+    #[serde(flatten)]
+    pub objective: Milestone,
+    pub epics: Vec<ObjectiveEpic>,
+}
+
+/// An epic of an objective, along with its stories of the release
+#[derive(Debug, Serialize)]
+pub struct ObjectiveEpic {
+    #[serde(flatten)]
+    pub epic: ReleaseEpic,
+    pub stories: Vec<ReleaseStory>,
+}
+
+/// Nest the stories under their epic and the epics under their objective, leaving out the epics
+/// without objective and the stories without epic
+pub fn group_by_objective(
+    objectives: &[Milestone],
+    epics: &[ReleaseEpic],
+    stories: &[ReleaseStory],
+) -> Vec<ReleaseObjective> {
+    objectives
+        .iter()
+        .map(|objective| ReleaseObjective {
+            objective: objective.clone(),
+            epics: epics
+                .iter()
+                .filter(|epic| epic.epic.milestone_id == Some(objective.id))
+                .map(|epic| ObjectiveEpic {
+                    epic: epic.clone(),
+                    stories: stories
+                        .iter()
+                        .filter(|story| story.story.epic_id == Some(epic.epic.id))
+                        .cloned()
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+impl ReleaseContent {
+    /// Remove the stories with these ids, along with the epics left without story nor commit
+    pub fn exclude_stories(&mut self, story_ids: &HashSet<i64>) {
+        self.stories
+            .retain(|story| !story_ids.contains(&story.story.id));
+        for epic in &mut self.epics {
+            let epic_stories = self
+                .stories
+                .iter()
+                .filter(|story| story.story.epic_id == Some(epic.epic.id))
+                .collect::<Vec<_>>();
+            epic.release_stories = epic_stories.len();
+            epic.release_points = total_points(epic_stories);
+        }
+        self.epics
+            .retain(|epic| epic.release_stories > 0 || !epic.commits.is_empty());
+    }
+
+    /// Flag the stories whose deadline is before the date of the release. Stories with an
+    /// invalid deadline are never overdue.
+    pub fn flag_overdue(&mut self, release_date: NaiveDate) {
+        for story in &mut self.stories {
+            story.overdue = story.story.deadline.as_deref().is_some_and(|deadline| {
+                DateTime::parse_from_rfc3339(deadline)
+                    .is_ok_and(|deadline| deadline.date_naive() < release_date)
+            });
+        }
+    }
+
+    /// Flag the stories with the label as breaking changes, on top of the ones with a breaking
+    /// commit
+    pub fn flag_breaking(&mut self, breaking_label: &str) {
+        for story in &mut self.stories {
+            story.breaking |= has_label(story, breaking_label);
+        }
+    }
+
+    /// Flag the repositories, among the ones with a GitHub repository, where Shortcut links merged
+    /// pull requests or commits to each story but without any of its commits in the release, eg
+    /// when the story is done but its code wasn't merged to the next branch
+    pub fn flag_unreleased_repositories(
+        &mut self,
+        github_repositories: &HashMap<RepositoryName, GithubRepository>,
+    ) {
+        for story in &mut self.stories {
+            let linked_repositories = story
+                .story
+                .pull_requests
+                .iter()
+                .filter(|pull_request| pull_request.merged)
+                .map(|pull_request| pull_request.url.as_str())
+                .chain(story.story.commits.iter().map(|commit| commit.url.as_str()))
+                .filter_map(github_url_repository)
+                .collect::<HashSet<_>>();
+            story.unreleased_repositories = github_repositories
+                .iter()
+                .filter(|(repo_name, github_repository)| {
+                    linked_repositories.contains(&github_repository.as_ref().to_lowercase())
+                        && !story.repositories.contains(repo_name)
+                })
+                .map(|(repo_name, _)| repo_name.clone())
+                .collect();
+            story
+                .unreleased_repositories
+                .sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        }
+    }
+
+    /// Add the content of a release of other repositories, eg from another workspace
+    pub fn merge(&mut self, other: ReleaseContent) {
+        self.stories.extend(other.stories);
+        self.stories.sort_by_key(|story| story.story.id);
+        self.missing_stories.extend(other.missing_stories);
+        self.missing_stories
+            .sort_by_key(|missing_story| missing_story.id);
+        self.epics.extend(other.epics);
+        self.epics.sort_by_key(|epic| epic.epic.id);
+        self.objectives.extend(other.objectives);
+        self.objectives
+            .sort_by_key(|objective| (objective.position, objective.id));
+        self.iterations.extend(other.iterations);
+        self.iterations
+            .sort_by(|a, b| (&a.start_date, a.id).cmp(&(&b.start_date, b.id)));
+        self.unparsed_commits.extend(other.unparsed_commits);
+        self.breaking_commits.extend(other.breaking_commits);
+        self.reverted_commit_pairs += other.reverted_commit_pairs;
+        self.excluded_commits += other.excluded_commits;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Timelike;
+    use shortcut_client::models::{LabelSlim, TypedStoryLink};
+
+    use super::*;
+    use crate::types::{test_commit, UnreleasedCommit};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn story(story_type: &str, labels: &[&str], epic_id: Option<i64>) -> Story {
+        Story {
+            story_type: story_type.to_string(),
+            labels: labels
+                .iter()
+                .map(|name| LabelSlim {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            epic_id,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_filter_keeps_all() {
+        let story_filter = StoryFilter::new(&[], &[]);
+        assert!(story_filter.is_empty());
+        assert!(story_filter.filter(&story("bug", &[], None), &StoryRefs::default()));
+        assert!(story_filter.filter_epic(1));
+    }
+
+    #[test]
+    fn filter_labels() {
+        let excluded = strings(&["internal"]);
+        let included = strings(&["backend", "api"]);
+        let story_filter = StoryFilter::new(&excluded, &included);
+        let refs = StoryRefs::default();
+        assert!(story_filter.filter(&story("bug", &["api", "backend"], None), &refs));
+        // All the included labels are required
+        assert!(!story_filter.filter(&story("bug", &["api"], None), &refs));
+        assert!(!story_filter.filter(&story("bug", &[], None), &refs));
+        assert!(!story_filter.filter(&story("bug", &["api", "backend", "internal"], None), &refs));
+    }
+
+    fn filter_state(story_filter: &StoryFilter<'_>, state: Option<(&str, &str)>) -> bool {
+        let workflow_state = state.map(|(name, r#type)| WorkflowState {
+            name: name.to_string(),
+            r#type: r#type.to_string(),
+            ..Default::default()
+        });
+        story_filter.filter(
+            &story("bug", &[], None),
+            &StoryRefs {
+                workflow_state: workflow_state.as_ref(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn filter_states() {
+        let excluded = strings(&["Abandoned"]);
+        let story_filter = StoryFilter::new(&[], &[]).with_states(&excluded, &[], true);
+        assert!(filter_state(&story_filter, Some(("Released", "done"))));
+        assert!(!filter_state(&story_filter, Some(("Abandoned", "done"))));
+        // Only the stories known to be done are kept
+        assert!(!filter_state(&story_filter, Some(("In review", "started"))));
+        assert!(!filter_state(&story_filter, None));
+        let included = strings(&["Released", "Merged"]);
+        let story_filter = StoryFilter::new(&[], &[]).with_states(&[], &included, false);
+        assert!(filter_state(&story_filter, Some(("Merged", "started"))));
+        assert!(!filter_state(&story_filter, Some(("Backlog", "unstarted"))));
+        assert!(!filter_state(&story_filter, None));
+    }
+
+    #[test]
+    fn filter_types_and_epics() {
+        let excluded_types = strings(&["chore"]);
+        let story_filter = StoryFilter::new(&[], &[])
+            .with_types(&excluded_types, &[])
+            .with_epics(&[2], &[1, 3]);
+        let refs = StoryRefs::default();
+        assert!(story_filter.filter(&story("feature", &[], Some(1)), &refs));
+        assert!(!story_filter.filter(&story("chore", &[], Some(1)), &refs));
+        assert!(!story_filter.filter(&story("feature", &[], Some(2)), &refs));
+        assert!(!story_filter.filter(&story("feature", &[], None), &refs));
+        assert!(story_filter.filter_epic(3));
+        assert!(!story_filter.filter_epic(2));
+        assert!(!story_filter.filter_epic(4));
+    }
+
+    #[test]
+    fn filter_teams_projects_and_iterations() {
+        let included_teams = strings(&["backend"]);
+        let excluded_projects = strings(&["7", "Legacy"]);
+        let included_iterations = strings(&["Sprint 12"]);
+        let story_filter = StoryFilter::new(&[], &[])
+            .with_teams(&[], &included_teams)
+            .with_projects(&excluded_projects, &[])
+            .with_iterations(&included_iterations);
+        let team = Group {
+            name: "Backend team".to_string(),
+            mention_name: "backend".to_string(),
+            ..Default::default()
+        };
+        let project = Project {
+            name: "Web".to_string(),
+            ..Default::default()
+        };
+        let legacy_project = Project {
+            name: "Legacy".to_string(),
+            ..Default::default()
+        };
+        let iteration = Iteration {
+            name: "Sprint 12".to_string(),
+            ..Default::default()
+        };
+        let refs = StoryRefs {
+            team: Some(&team),
+            project: Some(&project),
+            iteration: Some(&iteration),
+            ..Default::default()
+        };
+        let mut kept_story = story("bug", &[], None);
+        kept_story.project_id = Some(8);
+        assert!(story_filter.filter(&kept_story, &refs));
+        // Projects are matched by id or by name
+        let mut excluded_story = kept_story.clone();
+        excluded_story.project_id = Some(7);
+        assert!(!story_filter.filter(&excluded_story, &refs));
+        assert!(!story_filter.filter(
+            &kept_story,
+            &StoryRefs {
+                project: Some(&legacy_project),
+                ..refs
+            }
+        ));
+        assert!(!story_filter.filter(&kept_story, &StoryRefs { team: None, ..refs }));
+        assert!(!story_filter.filter(
+            &kept_story,
+            &StoryRefs {
+                iteration: None,
+                ..refs
+            }
+        ));
+    }
+
+    /// Story duplicating the stories of `duplicated_ids`
+    fn duplicate_story(id: i64, duplicated_ids: &[i64]) -> Story {
+        Story {
+            id,
+            story_links: duplicated_ids
+                .iter()
+                .map(|duplicated_id| TypedStoryLink {
+                    verb: DUPLICATES_VERB.to_string(),
+                    subject_id: id,
+                    object_id: *duplicated_id,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Commits of the story in the `dev` repository, committed `minute` minutes after midnight
+    fn story_commits(minute: u32) -> RepoToCommits {
+        let commit = test_commit(minute as u8, minute, None);
+        HashMap::from([(RepositoryName::new("dev".to_string()), vec![commit])])
+    }
+
+    fn commit_ids(commits: &[UnreleasedCommit]) -> Vec<GitOid> {
+        commits.iter().map(|commit| commit.id).collect()
+    }
+
+    #[test]
+    fn remove_reverted_commits_by_sha() {
+        let reverted = test_commit(1, 1, Some("Add the endpoint"));
+        let revert_message = format!(
+            "Revert the endpoint\n\nThis reverts commit {}.",
+            reverted.id
+        );
+        let mut commits = vec![
+            test_commit(3, 3, Some("Fix the build")),
+            test_commit(2, 2, Some(&revert_message)),
+            reverted,
+        ];
+        assert_eq!(remove_reverted_commits(&mut commits), 1);
+        assert_eq!(commit_ids(&commits), [test_commit(3, 3, None).id]);
+        // The reverted commit isn't unreleased, so the revert is kept
+        let mut commits = vec![test_commit(2, 2, Some(&revert_message))];
+        assert_eq!(remove_reverted_commits(&mut commits), 0);
+        assert_eq!(commits.len(), 1);
+    }
+
+    #[test]
+    fn remove_reverted_commits_by_subject() {
+        let mut commits = vec![
+            test_commit(3, 3, Some("Revert \"Add the endpoint\"\n\nBroke the build")),
+            test_commit(2, 2, Some("Add the endpoint")),
+            test_commit(1, 1, Some("Add the endpoints")),
+        ];
+        assert_eq!(remove_reverted_commits(&mut commits), 1);
+        assert_eq!(commit_ids(&commits), [test_commit(1, 1, None).id]);
+        // A revert referencing itself isn't a pair
+        let mut commits = vec![test_commit(1, 1, None)];
+        commits[0].message = Some(format!(
+            "Revert \"Revert\"\n\nThis reverts commit {}.",
+            commits[0].id
+        ));
+        assert_eq!(remove_reverted_commits(&mut commits), 0);
+        assert_eq!(remove_reverted_commits(&mut Vec::new()), 0);
+    }
+
+    #[test]
+    fn remove_reverted_reverts() {
+        let original = test_commit(1, 1, Some("Add the endpoint"));
+        let revert = test_commit(
+            2,
+            2,
+            Some(&format!(
+                "Revert \"Add the endpoint\"\n\nThis reverts commit {}.",
+                original.id
+            )),
+        );
+        let revert_of_revert = test_commit(
+            3,
+            3,
+            Some(&format!(
+                "Revert \"Revert \"Add the endpoint\"\"\n\nThis reverts commit {}.",
+                revert.id
+            )),
+        );
+        let original_id = original.id;
+        let mut commits = vec![revert_of_revert.clone(), revert.clone(), original.clone()];
+        assert_eq!(remove_reverted_commits(&mut commits), 1);
+        assert_eq!(commit_ids(&commits), [original_id]);
+        // Same with the subjects only
+        let mut commits = [revert_of_revert, revert, original]
+            .map(|mut commit| {
+                let subject = commit.message.as_deref().unwrap().lines().next().unwrap();
+                commit.message = Some(subject.to_owned());
+                commit
+            })
+            .to_vec();
+        assert_eq!(remove_reverted_commits(&mut commits), 1);
+        assert_eq!(commit_ids(&commits), [original_id]);
+    }
+
+    #[test]
+    fn collapse_duplicates_without_links() {
+        let mut stories = vec![duplicate_story(1, &[]), duplicate_story(2, &[])];
+        let mut commits = HashMap::from([(StoryId::from(1), story_commits(1))]);
+        assert!(collapse_duplicates(&mut stories, &mut commits).is_empty());
+        assert_eq!(stories.len(), 2);
+        assert_eq!(commits.len(), 1);
+        assert!(collapse_duplicates(&mut Vec::new(), &mut HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn collapse_duplicate_chains() {
+        // 3 duplicates 2 which duplicates 1, 4 duplicates a story outside of the release
+        let mut stories = vec![
+            duplicate_story(1, &[]),
+            duplicate_story(2, &[1]),
+            duplicate_story(3, &[2]),
+            duplicate_story(4, &[10]),
+        ];
+        let mut commits = HashMap::from([
+            (StoryId::from(1), story_commits(3)),
+            (StoryId::from(2), story_commits(1)),
+            (StoryId::from(3), story_commits(2)),
+        ]);
+        let duplicate_ids = collapse_duplicates(&mut stories, &mut commits);
+        assert_eq!(duplicate_ids, HashMap::from([(1, vec![2, 3])]));
+        assert_eq!(
+            stories.iter().map(|story| story.id).collect::<Vec<_>>(),
+            [1, 4]
+        );
+        assert_eq!(commits.len(), 1);
+        // The commits of the duplicates are merged, from the oldest
+        assert_eq!(
+            commits[&StoryId::from(1)][&RepositoryName::new("dev".to_string())]
+                .iter()
+                .map(|commit| commit.committed_at.minute())
+                .collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn collapse_duplicate_cycles() {
+        let mut stories = vec![duplicate_story(1, &[2]), duplicate_story(2, &[1])];
+        assert!(collapse_duplicates(&mut stories, &mut HashMap::new()).is_empty());
+        assert_eq!(stories.len(), 2);
+    }
+
+    #[test]
+    fn github_url_repositories() {
+        assert_eq!(
+            github_url_repository("https://github.com/Impero-com/Shortcut_Release_Helper/pull/12")
+                .as_deref(),
+            Some("impero-com/shortcut_release_helper")
+        );
+        assert_eq!(
+            github_url_repository("https://github.com/impero-com/dev/commit/1a2b3c4").as_deref(),
+            Some("impero-com/dev")
+        );
+        for url in [
+            "",
+            "https://github.com/impero-com/dev",
+            "https://github.com/impero-com/dev/issues/12",
+            "https://github.com/impero-com/pull/12",
+            "https://gitlab.com/impero-com/dev/pull/12",
+            "http://github.com/impero-com/dev/pull/12",
+        ] {
+            assert_eq!(github_url_repository(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn search_words_without_operators() {
+        assert_eq!(search_words(""), "");
+        assert_eq!(search_words(" :- "), "");
+        assert_eq!(
+            search_words("fix(api): Don't retry a 4xx, sc-12"),
+            "fix api Don retry 4xx sc 12"
+        );
+        assert_eq!(search_words("type:bug -label:\"ui\""), "type bug label ui");
+    }
+
+    #[test]
+    fn trailer_story_ids() {
+        let story_trailers = strings(&["Shortcut-Story", "Story"]);
+        let parser = StoryIdParser::new("(\\d+)", &story_trailers, &HashMap::new()).unwrap();
+        let story_id = |trailers: &str| parser.trailer_story_id(&format!("Fix\n\n{trailers}"));
+        assert_eq!(story_id("shortcut-story: sc-12"), Some(StoryId::from(12)));
+        assert_eq!(story_id("STORY: 34"), Some(StoryId::from(34)));
+        assert_eq!(
+            story_id("Shortcut-Story: https://app.shortcut.com/org/story/56/add-the-endpoint"),
+            Some(StoryId::from(56))
+        );
+        // The first trailer with a story id is used
+        assert_eq!(
+            story_id("Story: soon\nShortcut-Story: 78\nStory: 90"),
+            Some(StoryId::from(78))
+        );
+        assert_eq!(story_id("Shortcut-Story: sc-"), None);
+        assert_eq!(story_id("Reviewed-by: 12"), None);
+        assert_eq!(parser.trailer_story_id("Fix\n\nStory: 12\n\nBody"), None);
+        assert_eq!(parser.trailer_story_id(""), None);
+    }
+
+    /// Repositories configured in TOML, with only the required keys, plus the given ones
+    fn repositories(
+        names_and_keys: &[(&str, &str)],
+    ) -> HashMap<RepositoryName, RepositoryConfiguration> {
+        let config = names_and_keys
+            .iter()
+            .map(|(name, keys)| {
+                format!(
+                    "[\"{name}\"]\nlocation = \"/src\"\nrelease_branch = \"master\"\n\
+                    next_branch = \"next\"\n{keys}\n"
+                )
+            })
+            .collect::<String>();
+        toml::from_str(&config).unwrap()
+    }
+
+    #[test]
+    fn text_story_ids() {
+        let repositories = repositories(&[
+            ("app", r"story_id_patterns = ['JIRA-(\d+)', '#(\d+)']"),
+            ("other", ""),
+        ]);
+        let parser = StoryIdParser::new("(\\d+)", &[], &repositories).unwrap();
+        let story_id = |repo_name: &str, text: &str| {
+            parser.text_story_id(&RepositoryName::new(repo_name.to_owned()), text)
+        };
+        assert_eq!(
+            story_id("app", "Fix JIRA-12 and #34"),
+            Some(StoryId::from(12))
+        );
+        assert_eq!(story_id("app", "Fix #34"), Some(StoryId::from(34)));
+        // The patterns of the repository replace the default ones
+        assert_eq!(story_id("app", "Fix [sc-56]"), None);
+        // Submodules use the patterns of their repository
+        assert_eq!(
+            story_id("app/vendor/lib", "Fix JIRA-12"),
+            Some(StoryId::from(12))
+        );
+        assert_eq!(story_id("app/vendor/lib", "Fix [sc-56]"), None);
+        // Other repositories fall back to the default references
+        for repo_name in ["apps", "other", "unknown"] {
+            assert_eq!(story_id(repo_name, "Fix JIRA-12"), None, "{repo_name}");
+            assert_eq!(
+                story_id(repo_name, "Fix [sc-56]"),
+                Some(StoryId::from(56)),
+                "{repo_name}"
+            );
+        }
+        assert_eq!(
+            story_id("other", "Fix https://app.shortcut.com/org/story/78"),
+            Some(StoryId::from(78))
+        );
+        assert_eq!(story_id("other", ""), None);
+    }
+
+    #[test]
+    fn story_id_patterns_need_a_group() {
+        let repositories = repositories(&[("app", r"story_id_patterns = ['JIRA-\d+']")]);
+        let err = StoryIdParser::new("(\\d+)", &[], &repositories).unwrap_err();
+        assert!(err.to_string().contains("has no capture group"), "{err}");
+    }
+
+    #[test]
+    fn retry_delay_doubles() {
+        for _ in 0..100 {
+            let delay = retry_delay(1);
+            assert!(delay >= INITIAL_RETRY_DELAY / 2 && delay < INITIAL_RETRY_DELAY);
+            let delay = retry_delay(3);
+            assert!(delay >= INITIAL_RETRY_DELAY * 2 && delay < INITIAL_RETRY_DELAY * 4);
+        }
+    }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        for attempt in [7, 8, 32, 100, u32::MAX] {
+            let delay = retry_delay(attempt);
+            assert!(
+                delay >= MAX_RETRY_DELAY / 2 && delay < MAX_RETRY_DELAY,
+                "{attempt}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_retry_after_seconds() {
+        assert_eq!(parse_retry_after("0"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("1.5"), None);
+        assert_eq!(parse_retry_after(""), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn parse_retry_after_date() {
+        let in_a_minute = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let delay = parse_retry_after(&in_a_minute).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+        // A date in the past doesn't delay the requests
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
 }
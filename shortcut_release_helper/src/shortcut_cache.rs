@@ -0,0 +1,123 @@
+//! This module caches the stories and epics retrieved from Shortcut on disk, so that repeated
+//! runs don't retrieve them again
+//!
+//! Each response is stored as `<cache dir>/shortcut/<workspace>/<entity>/<id>.json` (or
+//! `all.json` for the lists of the workspace, like its members), and is fresh for the configured
+//! TTL after it was written. Fresh stories and epics are still revalidated against Shortcut before
+//! being used, see [`crate::shortcut::ShortcutClient`].
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{debug, warn};
+
+use crate::{config::cache_dir, types::WorkspaceName};
+
+/// How the cache is used, set from the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Read fresh responses from the cache, and write the retrieved ones to it
+    #[default]
+    Enabled,
+    /// Retrieve all responses, and write them to the cache
+    Refresh,
+    /// Neither read nor write the cache
+    Disabled,
+    /// Read all cached responses whatever their age, without writing the cache
+    Offline,
+}
+
+/// A cached response, with the time it was written at
+pub struct Cached<T> {
+    pub value: T,
+    pub written_at: SystemTime,
+}
+
+pub struct ShortcutCache {
+    directory: PathBuf,
+    mode: CacheMode,
+    ttl: Duration,
+}
+
+impl ShortcutCache {
+    pub fn new(workspace: &WorkspaceName, mode: CacheMode, ttl: Duration) -> Result<Self> {
+        Ok(Self {
+            directory: cache_dir()?.join("shortcut").join(workspace.as_ref()),
+            mode,
+            ttl,
+        })
+    }
+
+    fn path(&self, entity: &str, key: &str) -> PathBuf {
+        self.directory.join(entity).join(format!("{key}.json"))
+    }
+
+    /// The cached response of the `entity` (story or epic) with this id, if it is fresh or the
+    /// cache is offline
+    pub fn get<T: DeserializeOwned>(&self, entity: &str, id: i64) -> Option<Cached<T>> {
+        self.read(entity, &id.to_string())
+    }
+
+    /// The cached list of all the `entity` (members, groups...) of the workspace, if it is fresh
+    /// or the cache is offline
+    pub fn get_list<T: DeserializeOwned>(&self, entity: &str) -> Option<Cached<Vec<T>>> {
+        self.read(entity, "all")
+    }
+
+    pub fn put<T: Serialize>(&self, entity: &str, id: i64, value: &T) {
+        self.write(entity, &id.to_string(), value)
+    }
+
+    pub fn put_list<T: Serialize>(&self, entity: &str, values: &[T]) {
+        self.write(entity, "all", &values)
+    }
+
+    /// Unreadable entries are ignored, as they are overwritten once retrieved again
+    fn read<T: DeserializeOwned>(&self, entity: &str, key: &str) -> Option<Cached<T>> {
+        if matches!(self.mode, CacheMode::Refresh | CacheMode::Disabled) {
+            return None;
+        }
+        let path = self.path(entity, key);
+        let written_at = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()?;
+        if self.mode == CacheMode::Enabled {
+            let age = SystemTime::now().duration_since(written_at).ok()?;
+            if age > self.ttl {
+                debug!(entity, key, ?age, "Cached response expired");
+                return None;
+            }
+        }
+        let content = fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(value) => Some(Cached { value, written_at }),
+            Err(err) => {
+                debug!(entity, key, "Ignoring invalid cached response: {err}");
+                None
+            }
+        }
+    }
+
+    /// Failures are only logged, as the cache is an optimization
+    fn write<T: Serialize>(&self, entity: &str, key: &str, value: &T) {
+        if matches!(self.mode, CacheMode::Disabled | CacheMode::Offline) {
+            return;
+        }
+        let path = self.path(entity, key);
+        if let Err(err) = write_json(&path, value) {
+            warn!(entity, key, ?path, "Could not cache the response: {err}");
+        }
+    }
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(path, serde_json::to_string(value)?)?;
+    Ok(())
+}
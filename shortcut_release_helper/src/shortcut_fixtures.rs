@@ -0,0 +1,79 @@
+//! This module records the stories and epics retrieved from Shortcut to a fixtures file, so that
+//! a later run can replay them without reaching Shortcut, eg to iterate on a template
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shortcut_client::models::{Epic, Iteration, Milestone, Story};
+
+use crate::types::WorkspaceName;
+
+/// Fixtures of each workspace, written as JSON
+pub type WorkspaceFixtures = BTreeMap<WorkspaceName, Fixtures>;
+
+/// Stories, epics, objectives and iterations of a workspace, by id
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Fixtures {
+    pub stories: BTreeMap<i64, Story>,
+    pub epics: BTreeMap<i64, Epic>,
+    #[serde(default)]
+    pub objectives: BTreeMap<i64, Milestone>,
+    #[serde(default)]
+    pub iterations: BTreeMap<i64, Iteration>,
+    /// Lists of the workspace, like its members, by entity
+    #[serde(default)]
+    pub lists: BTreeMap<String, serde_json::Value>,
+}
+
+pub fn read_fixtures(path: &Path) -> Result<WorkspaceFixtures> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Could not read the fixtures {path:?}"))?;
+    serde_json::from_str(&content).with_context(|| format!("Invalid fixtures {path:?}"))
+}
+
+pub fn write_fixtures(fixtures: &WorkspaceFixtures, path: &Path) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(fixtures)?)
+        .with_context(|| format!("Could not write the fixtures {path:?}"))
+}
+
+impl Fixtures {
+    pub fn record_stories(&mut self, stories: &[Story]) {
+        self.stories
+            .extend(stories.iter().map(|story| (story.id, story.clone())));
+    }
+
+    pub fn record_epics(&mut self, epics: &[Epic]) {
+        self.epics
+            .extend(epics.iter().map(|epic| (epic.id, epic.clone())));
+    }
+
+    pub fn record_objectives(&mut self, objectives: &[Milestone]) {
+        self.objectives.extend(
+            objectives
+                .iter()
+                .map(|objective| (objective.id, objective.clone())),
+        );
+    }
+
+    pub fn record_iterations(&mut self, iterations: &[Iteration]) {
+        self.iterations.extend(
+            iterations
+                .iter()
+                .map(|iteration| (iteration.id, iteration.clone())),
+        );
+    }
+
+    pub fn record_list<T: Serialize>(&mut self, entity: &str, values: &[T]) -> Result<()> {
+        self.lists
+            .insert(entity.to_string(), serde_json::to_value(values)?);
+        Ok(())
+    }
+
+    /// The recorded list of all the `entity` of the workspace, empty if it wasn't recorded
+    pub fn list<T: DeserializeOwned>(&self, entity: &str) -> Result<Vec<T>> {
+        match self.lists.get(entity) {
+            Some(values) => Ok(Vec::<T>::deserialize(values)?),
+            None => Ok(Vec::new()),
+        }
+    }
+}
@@ -0,0 +1,434 @@
+//! This module retrieves the stories referenced by the commits of each repository from the
+//! Shortcut workspace the repository is assigned to
+//!
+//! Story ids are only unique within a workspace, so the commits are parsed and their stories
+//! retrieved for each workspace separately, before merging the releases.
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use tracing::{debug, info, warn};
+
+use crate::{
+    shortcut::{Commits, ReleaseContent, ReleaseStory, ShortcutClient, StoryFilter},
+    shortcut_fixtures::WorkspaceFixtures,
+    types::{
+        ReleaseContainer, RepoToCommits, RepositoryName, WorkspaceConfiguration, WorkspaceName,
+    },
+};
+
+pub struct ShortcutWorkspaces {
+    clients: HashMap<WorkspaceName, ShortcutClient>,
+    /// Workspace of each repository, the repositories missing from it using the default one
+    repository_workspaces: HashMap<RepositoryName, WorkspaceName>,
+    default_workspace: WorkspaceName,
+}
+
+impl ShortcutWorkspaces {
+    pub fn new(
+        clients: HashMap<WorkspaceName, ShortcutClient>,
+        repository_workspaces: HashMap<RepositoryName, WorkspaceName>,
+    ) -> Self {
+        Self {
+            clients,
+            repository_workspaces,
+            default_workspace: WorkspaceName::default(),
+        }
+    }
+
+    /// Retrieve the release of the commits of all repositories, parsing the commits of each
+    /// workspace with `parse_commits`
+    pub async fn get_release(
+        &self,
+        commits: RepoToCommits,
+        parse_commits: impl Fn(RepoToCommits) -> Result<Commits>,
+        story_filter: &StoryFilter<'_>,
+    ) -> Result<ReleaseContent> {
+        let mut workspace_commits: HashMap<&WorkspaceName, RepoToCommits> = HashMap::new();
+        for (repo_name, commits) in commits {
+            let workspace = self.workspace(&repo_name);
+            workspace_commits
+                .entry(workspace)
+                .or_default()
+                .insert(repo_name, commits);
+        }
+        let mut release = ReleaseContent::default();
+        for (workspace, commits) in workspace_commits {
+            let client = self.client(workspace)?;
+            let mut workspace_release = client
+                .get_release(parse_commits(commits)?, story_filter)
+                .await?;
+            for story in &mut workspace_release.stories {
+                story.workspace = workspace.clone();
+            }
+            for missing_story in &mut workspace_release.missing_stories {
+                missing_story.workspace = workspace.clone();
+            }
+            release.merge(workspace_release);
+        }
+        Ok(release)
+    }
+
+    fn workspace(&self, repo_name: &RepositoryName) -> &WorkspaceName {
+        self.repository_workspaces
+            .get(repo_name)
+            .unwrap_or(&self.default_workspace)
+    }
+
+    /// Check that the token of each workspace is valid, and belongs to the expected Shortcut
+    /// workspace when configured
+    pub async fn check_tokens(
+        &self,
+        workspace_configs: &HashMap<WorkspaceName, WorkspaceConfiguration>,
+    ) -> Result<()> {
+        for (workspace, client) in &self.clients {
+            let expected_url_slug = workspace_configs
+                .get(workspace)
+                .and_then(|workspace_config| workspace_config.url_slug.as_deref());
+            let member = client
+                .check_token(expected_url_slug)
+                .await
+                .with_context(|| format!("Could not authenticate to the {workspace} workspace"))?;
+            let Some(member) = member else {
+                continue;
+            };
+            info!(
+                "Authenticated to the Shortcut workspace {} as {} (@{}) for the {workspace} \
+                workspace",
+                member.workspace2.url_slug, member.name, member.mention_name
+            );
+        }
+        Ok(())
+    }
+
+    /// The stories and epics recorded by the client of each workspace, if recording them
+    pub fn take_recorded(&self) -> Option<WorkspaceFixtures> {
+        self.clients
+            .iter()
+            .map(|(workspace, client)| Some((workspace.clone(), client.take_recorded()?)))
+            .collect()
+    }
+
+    fn client(&self, workspace: &WorkspaceName) -> Result<&ShortcutClient> {
+        self.clients
+            .get(workspace)
+            .ok_or_else(|| anyhow!("No Shortcut client for the workspace {workspace}"))
+    }
+
+    /// Add the label to the stories which don't have it yet, or only log them with `dry_run`.
+    /// Failures are logged, so that a story failing doesn't prevent labelling the others.
+    pub async fn apply_label(
+        &self,
+        stories: &[ReleaseStory],
+        label: &str,
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut failed_count = 0;
+        for story in stories {
+            if story.story.labels.iter().any(|other| other.name == label) {
+                continue;
+            }
+            let story_id = story.story.id;
+            if dry_run {
+                message!("Would add the label {label} to story {story_id}");
+                continue;
+            }
+            let result = self
+                .client(&story.workspace)?
+                .add_story_label(&story.story, label)
+                .await;
+            match result {
+                Ok(()) => info!("Added the label {label} to story {story_id}"),
+                Err(err) => {
+                    warn!("Could not add the label {label} to story {story_id}: {err:?}");
+                    failed_count += 1;
+                }
+            }
+        }
+        if failed_count > 0 {
+            bail!("Could not add the label {label} to {failed_count} stories");
+        }
+        Ok(())
+    }
+
+    /// Move the stories to the first of the workflow states named `state_names` in their
+    /// workflow, or only print the moves with `dry_run`. The stories which couldn't be moved are
+    /// printed, only failing once the other stories are moved when the request failed.
+    pub async fn move_stories(
+        &self,
+        stories: &[ReleaseStory],
+        state_names: &[String],
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut unmoved = Vec::new();
+        let mut failed_count = 0;
+        for story in stories {
+            let story_id = story.story.id;
+            let client = self.client(&story.workspace)?;
+            let state = client
+                .find_workflow_state(story.story.workflow_id, state_names)
+                .await?;
+            let Some(state) = state else {
+                unmoved.push(format!("{story_id} (no such state in its workflow)"));
+                continue;
+            };
+            if story.story.workflow_state_id == state.id {
+                continue;
+            }
+            if dry_run {
+                message!("Would move story {story_id} to {}", state.name);
+                continue;
+            }
+            match client.set_story_state(story_id, state.id).await {
+                Ok(()) => info!("Moved story {story_id} to {}", state.name),
+                Err(err) => {
+                    warn!("Could not move story {story_id} to {}: {err:?}", state.name);
+                    unmoved.push(format!("{story_id} (request failed)"));
+                    failed_count += 1;
+                }
+            }
+        }
+        if !unmoved.is_empty() {
+            message!(
+                "\nStories not moved to {}: {}",
+                state_names.join(" or "),
+                unmoved.join(", ")
+            );
+        }
+        if failed_count > 0 {
+            bail!("Could not move {failed_count} stories");
+        }
+        Ok(())
+    }
+
+    /// Comment on each story with the text given by `comment`, or only print the comments with
+    /// `dry_run`. The stories which already have a comment with this text, or starting with the
+    /// `marker`, are skipped. Failures are logged, so that a story failing doesn't prevent
+    /// commenting on the others.
+    pub async fn comment_stories(
+        &self,
+        stories: &[ReleaseStory],
+        comment: impl Fn(&ReleaseStory) -> String,
+        marker: Option<&str>,
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut failed_count = 0;
+        for story in stories {
+            let story_id = story.story.id;
+            let text = comment(story);
+            let is_commented = story
+                .story
+                .comments
+                .iter()
+                .filter(|comment| !comment.deleted)
+                .filter_map(|comment| comment.text.as_deref())
+                .any(|comment| {
+                    comment == text || marker.is_some_and(|marker| comment.starts_with(marker))
+                });
+            if is_commented {
+                debug!("Story {story_id} is already commented on");
+                continue;
+            }
+            if dry_run {
+                message!("Would comment on story {story_id}: {text}");
+                continue;
+            }
+            let result = self
+                .client(&story.workspace)?
+                .comment_story(story_id, &text)
+                .await;
+            match result {
+                Ok(()) => info!("Commented on story {story_id}"),
+                Err(err) => {
+                    warn!("Could not comment on story {story_id}: {err:?}");
+                    failed_count += 1;
+                }
+            }
+        }
+        if failed_count > 0 {
+            bail!("Could not comment on {failed_count} stories");
+        }
+        Ok(())
+    }
+
+    /// Set the custom field named `field_name` of the stories to `value`, or only print the
+    /// changes with `dry_run`. The value must be one of the field in the workspace of each story,
+    /// the stories of a workspace without it failing.
+    pub async fn set_custom_field(
+        &self,
+        stories: &[ReleaseStory],
+        field_name: &str,
+        value: &str,
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut workspace_stories: HashMap<&WorkspaceName, Vec<&ReleaseStory>> = HashMap::new();
+        for story in stories {
+            workspace_stories
+                .entry(&story.workspace)
+                .or_default()
+                .push(story);
+        }
+        let mut failed_count = 0;
+        for (workspace, stories) in workspace_stories {
+            let client = self.client(workspace)?;
+            let field_value = client
+                .find_custom_field_value(field_name, value)
+                .await
+                .and_then(|field_value| {
+                    field_value
+                        .ok_or_else(|| anyhow!("No value {value} of the custom field {field_name}"))
+                });
+            let (field_id, value_id) = match field_value {
+                Ok(field_value) => field_value,
+                Err(err) => {
+                    warn!(
+                        "Could not set {field_name} on the {} stories of the {workspace} \
+                        workspace: {err:?}",
+                        stories.len()
+                    );
+                    failed_count += stories.len();
+                    continue;
+                }
+            };
+            for story in stories {
+                let story_id = story.story.id;
+                let is_set = story
+                    .story
+                    .custom_fields
+                    .iter()
+                    .flatten()
+                    .any(|story_field| {
+                        story_field.field_id == field_id && story_field.value_id == value_id
+                    });
+                if is_set {
+                    continue;
+                }
+                if dry_run {
+                    message!("Would set {field_name} to {value} on story {story_id}");
+                    continue;
+                }
+                let result = client
+                    .set_story_custom_field(&story.story, &field_id, &value_id)
+                    .await;
+                match result {
+                    Ok(()) => info!("Set {field_name} to {value} on story {story_id}"),
+                    Err(err) => {
+                        warn!("Could not set {field_name} on story {story_id}: {err:?}");
+                        failed_count += 1;
+                    }
+                }
+            }
+        }
+        if failed_count > 0 {
+            bail!("Could not set {field_name} on {failed_count} stories");
+        }
+        Ok(())
+    }
+
+    /// Find or create the epic or iteration named `name` in each workspace with stories, and move
+    /// the stories without an epic or iteration to it, or only print the changes with `dry_run`.
+    /// The stories already in another one are left in it.
+    pub async fn publish_container(
+        &self,
+        stories: &[ReleaseStory],
+        container: ReleaseContainer,
+        name: &str,
+        date: &str,
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut workspace_stories: HashMap<&WorkspaceName, Vec<&ReleaseStory>> = HashMap::new();
+        for story in stories {
+            workspace_stories
+                .entry(&story.workspace)
+                .or_default()
+                .push(story);
+        }
+        let mut failed_count = 0;
+        for (workspace, stories) in workspace_stories {
+            let client = self.client(workspace)?;
+            let existing_id = client.find_container(container, name).await?;
+            let mut stories_to_move = Vec::new();
+            for story in stories {
+                let current_id = match container {
+                    ReleaseContainer::Epic => story.story.epic_id,
+                    ReleaseContainer::Iteration => story.story.iteration_id,
+                };
+                match current_id {
+                    None => stories_to_move.push(story.story.id),
+                    Some(current_id) if Some(current_id) == existing_id => {}
+                    Some(current_id) => info!(
+                        "Leaving story {} in its {container} {current_id}",
+                        story.story.id
+                    ),
+                }
+            }
+            if dry_run {
+                let action = match existing_id {
+                    Some(_) => "use the existing",
+                    None => "create the",
+                };
+                message!(
+                    "Would {action} {container} {name} in the {workspace} workspace, and move {} \
+                    stories to it",
+                    stories_to_move.len()
+                );
+                continue;
+            }
+            let container_id = match existing_id {
+                Some(container_id) => {
+                    info!("Found the {container} {name} in the {workspace} workspace");
+                    container_id
+                }
+                None if stories_to_move.is_empty() => continue,
+                None => {
+                    let container_id = client.create_container(container, name, date).await?;
+                    info!("Created the {container} {name} in the {workspace} workspace");
+                    container_id
+                }
+            };
+            for story_id in stories_to_move {
+                let result = client
+                    .set_story_container(story_id, container, container_id)
+                    .await;
+                if let Err(err) = result {
+                    warn!("Could not move story {story_id} to the {container} {name}: {err:?}");
+                    failed_count += 1;
+                }
+            }
+        }
+        if failed_count > 0 {
+            bail!("Could not move {failed_count} stories to the {container} {name}");
+        }
+        Ok(())
+    }
+
+    /// Publish the release notes as the Doc titled `title` of the default workspace, or only print
+    /// it with `dry_run`
+    pub async fn publish_doc(&self, title: &str, release_notes: &str, dry_run: bool) -> Result<()> {
+        if dry_run {
+            message!("Would publish the release notes as the doc {title}");
+            return Ok(());
+        }
+        let url = self
+            .client(&self.default_workspace)?
+            .publish_doc(title, release_notes)
+            .await?;
+        message!("Published the release notes as the doc {title}: {url}");
+        Ok(())
+    }
+
+    /// Attach up to `limit` suggested stories to each of the commits not referencing any, by
+    /// searching the workspace of its repository
+    pub async fn suggest_stories(
+        &self,
+        unparsed_commits: &mut RepoToCommits,
+        limit: usize,
+    ) -> Result<()> {
+        for (repo_name, commits) in unparsed_commits {
+            let client = self.client(self.workspace(repo_name))?;
+            for commit in commits {
+                commit.suggested_stories = client.suggest_stories(commit, limit).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,5 @@
 mod utils;
 
-use std::{fs, path::Path};
-
 use anyhow::Result;
 use chrono::offset::Utc;
 use lazy_static::lazy_static;
@@ -34,9 +32,12 @@ impl<'a> FileTemplate<'a> {
         environment.add_filter("split_by_label", Self::split_by_label);
         environment.add_filter("split_by_epic", Self::split_by_epic);
         environment.add_filter("has_label", Self::has_label);
+        environment.add_filter("group_by_author", Self::group_by_author);
         environment.add_filter("story_emoji", Self::story_emoji);
         environment.add_filter("indent", Self::indent);
         environment.add_filter("escape", Self::escape);
+        environment.add_filter("first_paragraph", Self::first_paragraph);
+        environment.add_filter("truncate", Self::truncate);
 
         environment.add_function("today", Self::today);
         environment.add_function("epic_emoji", Self::epic_emoji);
@@ -101,6 +102,41 @@ impl<'a> FileTemplate<'a> {
         Ok(Value::from(v))
     }
 
+    /// Keep the first paragraph of the text, up to the first blank line - useful for story
+    /// descriptions
+    fn first_paragraph(_state: &State, text: String) -> Result<Value, minijinja::Error> {
+        let paragraph = text
+            .trim_start()
+            .lines()
+            .take_while(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(Value::from(paragraph))
+    }
+
+    /// Truncate the text to at most the amount of characters indicated, cutting it at the last
+    /// whitespace before the limit if any, and ending it with an ellipsis.
+    fn truncate(_state: &State, text: String, length: usize) -> Result<Value, minijinja::Error> {
+        if text.chars().count() <= length {
+            return Ok(Value::from(text));
+        }
+        // Leave room for the ellipsis
+        let end = text
+            .char_indices()
+            .nth(length.saturating_sub(1))
+            .map_or(text.len(), |(index, _)| index);
+        let truncated = &text[..end];
+        // Cut at the previous whitespace, unless the cut already is right before one
+        let truncated = if text[end..].starts_with(char::is_whitespace) {
+            truncated
+        } else {
+            truncated
+                .rfind(char::is_whitespace)
+                .map_or(truncated, |index| &truncated[..index])
+        };
+        Ok(Value::from(format!("{}…", truncated.trim_end())))
+    }
+
     fn split_by_label(_state: &State, v: Value, label: Value) -> Result<Value, minijinja::Error> {
         let label_name = if matches!(label.kind(), ValueKind::String) {
             label.as_str().expect("Should be a string")
@@ -116,9 +152,9 @@ impl<'a> FileTemplate<'a> {
             let labels = epic_or_story.get_attr("labels")?;
             let mut labels_iter = SeqIterator::new(labels)?;
             let has_label = labels_iter.any(|label| {
-                label.get_attr("name").map_or(false, |name| {
-                    name.as_str().map_or(false, |name| name == label_name)
-                })
+                label
+                    .get_attr("name")
+                    .is_ok_and(|name| name.as_str().is_some_and(|name| name == label_name))
             });
             if has_label {
                 matched.push(epic_or_story)
@@ -178,6 +214,35 @@ impl<'a> FileTemplate<'a> {
         Ok(Value::from(vec![matched, unmatched]))
     }
 
+    /// Group a list of commits by author, returning a list of objects with the `name` and `email`
+    /// of the author and their `commits`, in order of first appearance
+    fn group_by_author(_state: &State, v: Value) -> Result<Value, minijinja::Error> {
+        let mut authors: Vec<(Value, Value, Vec<Value>)> = Vec::new();
+        let commits_iter = SeqIterator::new(v)?;
+        for commit in commits_iter {
+            let author = commit.get_attr("author")?;
+            let name = author.get_attr("name")?;
+            let email = author.get_attr("email")?;
+            match authors.iter_mut().find(|(author_name, author_email, _)| {
+                *author_name == name && *author_email == email
+            }) {
+                Some((_, _, commits)) => commits.push(commit),
+                None => authors.push((name, email, vec![commit])),
+            }
+        }
+        let authors = authors
+            .into_iter()
+            .map(|(name, email, commits)| {
+                Value::from_iter([
+                    ("name", name),
+                    ("email", email),
+                    ("commits", Value::from(commits)),
+                ])
+            })
+            .collect::<Vec<_>>();
+        Ok(Value::from(authors))
+    }
+
     fn story_emoji(_state: &State, story: Value) -> Result<Value, minijinja::Error> {
         if !matches!(story.kind(), ValueKind::Map) {
             return Err(minijinja::Error::new(
@@ -225,9 +290,9 @@ impl<'a> FileTemplate<'a> {
         let labels = epic_or_story.get_attr("labels")?;
         let mut labels_iter = SeqIterator::new(labels)?;
         let has_label = labels_iter.any(|label| {
-            label.get_attr("name").map_or(false, |name| {
-                name.as_str().map_or(false, |name| name == label_name)
-            })
+            label
+                .get_attr("name")
+                .is_ok_and(|name| name.as_str().is_some_and(|name| name == label_name))
         });
         Ok(Value::from(has_label))
     }
@@ -247,10 +312,50 @@ impl<'a> FileTemplate<'a> {
         Ok(Value::from_safe_string(EPIC_EMOJI.to_string()))
     }
 
-    pub fn render_to_file(&self, release: &Release, output_file: &Path) -> Result<()> {
+    /// Render the release notes with the template
+    pub fn render(&self, release: &Release) -> Result<String> {
         let template = self.environment.get_template(TEMPLATE_NAME)?;
-        let file_content = template.render(release)?;
-        fs::write(output_file, &file_content)?;
-        Ok(())
+        Ok(template.render(release)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::context;
+
+    use super::*;
+
+    fn render(template: &str, text: &str) -> String {
+        FileTemplate::new(template)
+            .unwrap()
+            .environment
+            .get_template(TEMPLATE_NAME)
+            .unwrap()
+            .render(context! { text })
+            .unwrap()
+    }
+
+    #[test]
+    fn first_paragraph() {
+        let template = "{{ text | first_paragraph }}";
+        assert_eq!(render(template, ""), "");
+        assert_eq!(render(template, "One line"), "One line");
+        assert_eq!(
+            render(template, "\n\nFirst\nparagraph\n  \nSecond paragraph"),
+            "First\nparagraph"
+        );
+    }
+
+    #[test]
+    fn truncate() {
+        let template = "{{ text | truncate(12) }}";
+        assert_eq!(render(template, ""), "");
+        assert_eq!(render(template, "Twelve chars"), "Twelve chars");
+        assert_eq!(render(template, "Thirteen char"), "Thirteen…");
+        assert_eq!(render(template, "Hello world foo"), "Hello world…");
+        assert_eq!(render(template, "Hello worlds foo"), "Hello…");
+        // Cut in the word when there is no whitespace, counting characters rather than bytes
+        assert_eq!(render(template, "Ééééééééééééé"), "Ééééééééééé…");
+        assert_eq!(render("{{ text | truncate(0) }}", "Text"), "…");
     }
 }
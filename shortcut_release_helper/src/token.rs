@@ -0,0 +1,82 @@
+//! This module reads the Shortcut API token of each workspace, from an environment variable or
+//! from the OS keyring, or the one given on the command line as a file or on the standard input
+//!
+//! The keyring is only available when built with the `keyring` feature.
+use std::{
+    env::{var, VarError},
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::types::{ShortcutApiKey, TokenSource, WorkspaceConfiguration, WorkspaceName};
+
+/// Environment variable containing the API token of the default workspace, unless configured
+const DEFAULT_TOKEN_ENV: &str = "SHORTCUT_TOKEN";
+
+pub fn read_api_key(
+    workspace: &WorkspaceName,
+    workspace_config: &WorkspaceConfiguration,
+) -> Result<ShortcutApiKey> {
+    let api_key = match workspace_config.token_source {
+        TokenSource::Env => {
+            let token_env = workspace_config
+                .token_env
+                .as_deref()
+                .unwrap_or(DEFAULT_TOKEN_ENV);
+            var(token_env).map_err(|err| match err {
+                VarError::NotPresent => anyhow!("Missing {token_env} environment variable for the {workspace} workspace. Please provide it in a .env file or set it in your environment."),
+                VarError::NotUnicode(_) => err.into(),
+            })?
+        }
+        TokenSource::Keyring => {
+            let service = workspace_config
+                .keyring_service
+                .as_deref()
+                .unwrap_or(env!("CARGO_PKG_NAME"));
+            let account = workspace_config
+                .keyring_account
+                .as_deref()
+                .unwrap_or(workspace.as_ref());
+            read_keyring(service, account)?
+        }
+    };
+    Ok(ShortcutApiKey::new(api_key))
+}
+
+/// Read the token from the file, ignoring surrounding whitespace like a trailing newline
+pub fn read_token_file(path: &Path) -> Result<ShortcutApiKey> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Could not read the token file {path:?}"))?;
+    parse_token(&content).ok_or_else(|| anyhow!("Empty token file {path:?}"))
+}
+
+/// Read the token from the standard input, ignoring surrounding whitespace
+pub fn read_token_stdin() -> Result<ShortcutApiKey> {
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    parse_token(&content).ok_or_else(|| anyhow!("Empty token on the standard input"))
+}
+
+fn parse_token(content: &str) -> Option<ShortcutApiKey> {
+    let token = content.trim();
+    (!token.is_empty()).then(|| ShortcutApiKey::new(token.to_string()))
+}
+
+#[cfg(feature = "keyring")]
+fn read_keyring(service: &str, account: &str) -> Result<String> {
+    keyring::Entry::new(service, account)
+        .and_then(|entry| entry.get_password())
+        .with_context(|| {
+            format!("Could not read the token of service {service:?} and account {account:?} from the keyring")
+        })
+}
+
+#[cfg(not(feature = "keyring"))]
+fn read_keyring(_service: &str, _account: &str) -> Result<String> {
+    Err(anyhow!(
+        "Reading the token from the keyring requires building with the keyring feature"
+    ))
+}
@@ -1,8 +1,19 @@
-use std::{collections::HashMap, path::PathBuf, string::ToString};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    string::ToString,
+};
 
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, FixedOffset};
 use git2::Oid as GitOid;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize, Serializer};
 
+use crate::conventional_commits::ConventionalCommit;
+
 /// Name of the Shortcut instance
 #[derive(Debug, PartialEq, Eq, Hash, Clone, AsRef, Display)]
 pub struct ShortcutApiKey(String);
@@ -13,42 +24,479 @@ impl ShortcutApiKey {
     }
 }
 
+/// API token for GitHub
+#[derive(Debug, PartialEq, Eq, Hash, Clone, AsRef, Display)]
+pub struct GithubApiKey(String);
+
+impl GithubApiKey {
+    pub fn new(key: String) -> Self {
+        GithubApiKey(key)
+    }
+}
+
+/// Name of a Shortcut workspace, as configured, `default` being the one of the repositories not
+/// assigned to a workspace
+#[derive(
+    Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord, AsRef, Deserialize, Display, Serialize,
+)]
+#[serde(transparent)]
+pub struct WorkspaceName(String);
+
+impl Default for WorkspaceName {
+    fn default() -> Self {
+        WorkspaceName("default".to_string())
+    }
+}
+
+/// Where the API token of a workspace is read from
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenSource {
+    /// An environment variable, possibly set from the `.env` file
+    #[default]
+    Env,
+    /// An entry of the OS keyring
+    Keyring,
+}
+
+/// Section of the release notes gathering the stories with a label
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SectionConfiguration {
+    pub label: String,
+    pub heading: String,
+}
+
+/// Whether the archived stories referenced by commits are part of the release
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArchivedPolicy {
+    /// Include them like the other stories
+    Include,
+    /// Leave them out, listing them among the missing stories
+    #[default]
+    Exclude,
+    /// Include them, logging a warning for each
+    Warn,
+}
+
+/// Configuration of a Shortcut workspace
+#[derive(Debug, PartialEq, Eq, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceConfiguration {
+    #[serde(default)]
+    pub token_source: TokenSource,
+    /// Environment variable containing the API token, with the `env` source. Defaults to
+    /// `SHORTCUT_TOKEN` for the default workspace
+    pub token_env: Option<String>,
+    /// Service of the keyring entry containing the API token, with the `keyring` source.
+    /// Defaults to the name of the tool
+    pub keyring_service: Option<String>,
+    /// Account of the keyring entry containing the API token, with the `keyring` source.
+    /// Defaults to the name of the workspace
+    pub keyring_account: Option<String>,
+    /// Slug of the Shortcut workspace the token should belong to, as in its URL
+    pub url_slug: Option<String>,
+}
+
 /// Name of the repository, must be unique
-#[derive(Debug, PartialEq, Eq, Hash, Clone, AsRef, Deserialize, Display, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, AsRef, Deserialize, Display, FromStr, Serialize)]
 #[serde(transparent)]
 pub struct RepositoryName(String);
 
+impl RepositoryName {
+    pub fn new(name: String) -> Self {
+        RepositoryName(name)
+    }
+
+    /// Name of a submodule of the repository, as `<repository>/<path>`
+    pub fn submodule(&self, path: &Path) -> Self {
+        RepositoryName(format!("{}/{}", self.0, path.display()))
+    }
+}
+
 /// Configuration of the repository
 #[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
 pub struct RepositoryConfiguration {
-    /// Path to the location of the repository on disk
+    /// Path to the location of the repository on disk, or URL of the repository to clone
     pub location: RepositoryLocation,
     /// Branch or commit name which has been released
+    #[serde(alias = "release_ref")]
     pub release_branch: RepositoryReference,
-    /// Branch or commit name which has not been released
-    pub next_branch: RepositoryReference,
+    /// Branch or commit name which has not been released, or a list of them whose unreleased
+    /// commits are merged into the same release
+    pub next_branch: NextBranches,
+    /// Fetch the release and next branches from the remote before looking for commits
+    #[serde(default)]
+    pub fetch: bool,
+    /// Name of the remote to fetch from
+    #[serde(default)]
+    pub remote: RepositoryRemote,
+    /// Only keep commits touching files under these paths, relative to the repository root
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    /// Exclude commits whose change was cherry-picked in the release branch, which requires
+    /// diffing every commit
+    #[serde(default)]
+    pub detect_cherry_picks: bool,
+    /// Only follow the first parent of merge commits, keeping the merge commits themselves
+    #[serde(default)]
+    pub first_parent: bool,
+    /// Regexes of the commit authors to exclude, matched against their name and email. Defaults
+    /// to the top-level `exclude_authors`
+    pub exclude_authors: Option<Vec<String>>,
+    /// GitHub repository, as `<owner>/<name>`, used to retrieve the pull requests referenced by
+    /// commits
+    pub github_repository: Option<GithubRepository>,
+    /// Regexes of the story ids referenced in commit messages, as their first capture group.
+    /// Defaults to the `[sc-1234]`, `[ch1234]` and `story/1234` references
+    pub story_id_patterns: Option<Vec<String>>,
+    /// Template of the link to a commit, where `{sha}` is replaced by the id of the commit
+    pub commit_url_template: Option<String>,
+    /// Authentication method used to fetch or clone the repository. Defaults to trying the SSH
+    /// agent, the git credential helpers and the default credentials in turn
+    pub auth: Option<RepositoryAuth>,
+    /// Also find the unreleased commits of the submodules whose pinned commit changed
+    #[serde(default)]
+    pub submodules: bool,
+    /// Search a mirror of the local repository in the cache directory instead of the working
+    /// copy
+    #[serde(default)]
+    pub isolated: bool,
+    /// Reference deployed to each environment of the `promotion_pipeline`, by environment name
+    #[serde(default)]
+    pub environments: HashMap<String, RepositoryReference>,
+    /// Shortcut workspace of the stories referenced by the commits
+    #[serde(default)]
+    pub workspace: WorkspaceName,
+}
+
+/// Authentication method for remote git operations
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[serde(tag = "method", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum RepositoryAuth {
+    /// Keys of the running SSH agent
+    SshAgent,
+    /// SSH private key file, like a deploy key
+    SshKey {
+        private_key: PathBuf,
+        /// Environment variable containing the passphrase of the key, if any
+        passphrase_env: Option<String>,
+    },
+    /// HTTPS token, like a GitHub personal access token
+    Token {
+        /// Environment variable containing the token
+        token_env: String,
+        /// User name sent along with the token, `x-access-token` per default
+        username: Option<String>,
+    },
 }
 
-/// Newtype for the physical location of the repository
+/// Newtype for the physical location of the repository, either a path on disk or the URL of a
+/// remote repository
 #[derive(Debug, PartialEq, Eq, Clone, Deserialize, AsRef)]
 #[serde(transparent)]
 pub struct RepositoryLocation(PathBuf);
 
-/// Newtype for a branch or commit name
-#[derive(Debug, PartialEq, Eq, Clone, Deserialize, AsRef, Display)]
+impl RepositoryLocation {
+    pub fn new(path: PathBuf) -> Self {
+        RepositoryLocation(path)
+    }
+
+    /// The location resolved against the directory, unless it is an absolute or remote one
+    pub fn relative_to(&self, directory: &Path) -> Self {
+        match self.remote_url() {
+            Some(_) => self.clone(),
+            None => RepositoryLocation(directory.join(&self.0)),
+        }
+    }
+
+    /// The URL of the repository, if the location is a remote one which should be cloned
+    pub fn remote_url(&self) -> Option<&str> {
+        const REMOTE_SCHEMES: [&str; 4] = ["https://", "http://", "ssh://", "git://"];
+        self.0.to_str().filter(|location| {
+            REMOTE_SCHEMES
+                .iter()
+                .any(|scheme| location.starts_with(scheme))
+        })
+    }
+}
+
+/// Newtype for a revision, as understood by `git rev-parse` (eg `master`, `origin/release`,
+/// `v3.4.0^{commit}`, `next~5` or a SHA), or `latest-tag:<glob>` for the tag with the highest
+/// semantic version amongst the ones matching the glob
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, AsRef, Display, FromStr)]
 #[serde(transparent)]
 pub struct RepositoryReference(String);
 
+impl RepositoryReference {
+    pub fn new(reference: String) -> Self {
+        RepositoryReference(reference)
+    }
+
+    /// The branch the revision is based on, ie the revision without suffixes like `~5` or
+    /// `^{commit}`
+    pub fn branch_name(&self) -> &str {
+        self.0
+            .find(['~', '^', ':'])
+            .map_or(self.0.as_str(), |suffix_start| &self.0[..suffix_start])
+    }
+
+    /// The glob of the tags to pick the latest one from, for a `latest-tag:<glob>` reference
+    pub fn latest_tag_pattern(&self) -> Option<&str> {
+        self.0.strip_prefix("latest-tag:")
+    }
+}
+
+/// One or more references which have not been released, configured as a single reference or a
+/// list of references. The first one is the primary next branch, used for the merge base, the
+/// commits missing in next, the submodules and tagging.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[serde(try_from = "NextBranchesConfiguration")]
+pub struct NextBranches(Vec<RepositoryReference>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NextBranchesConfiguration {
+    Single(RepositoryReference),
+    Multiple(Vec<RepositoryReference>),
+}
+
+impl TryFrom<NextBranchesConfiguration> for NextBranches {
+    type Error = Error;
+
+    fn try_from(configuration: NextBranchesConfiguration) -> Result<Self, Self::Error> {
+        match configuration {
+            NextBranchesConfiguration::Single(reference) => Ok(Self::single(reference)),
+            NextBranchesConfiguration::Multiple(references) if references.is_empty() => {
+                Err(anyhow!("next_branch must contain at least one reference"))
+            }
+            NextBranchesConfiguration::Multiple(references) => Ok(Self(references)),
+        }
+    }
+}
+
+impl NextBranches {
+    pub fn single(reference: RepositoryReference) -> Self {
+        NextBranches(vec![reference])
+    }
+
+    /// The primary next branch, ie the first configured one
+    pub fn primary(&self) -> &RepositoryReference {
+        &self.0[0]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RepositoryReference> {
+        self.0.iter()
+    }
+}
+
+impl fmt::Display for NextBranches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.iter().join(", "))
+    }
+}
+
+/// Release and next references of a single repository, overriding its configuration. Parsed
+/// from `<repository>=<release>..<next>`, where either side of the range may be omitted to
+/// keep the configured reference.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RepositoryReferenceOverride {
+    pub repository: RepositoryName,
+    pub release_branch: Option<RepositoryReference>,
+    pub next_branch: Option<RepositoryReference>,
+}
+
+impl FromStr for RepositoryReferenceOverride {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (repository, range) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected <repository>=<release>..<next>, got {s}"))?;
+        let (release_branch, next_branch) = range
+            .split_once("..")
+            .ok_or_else(|| anyhow!("Expected a <release>..<next> range, got {range}"))?;
+        let to_reference = |reference: &str| {
+            (!reference.is_empty()).then(|| RepositoryReference(reference.to_string()))
+        };
+        Ok(Self {
+            repository: RepositoryName(repository.to_string()),
+            release_branch: to_reference(release_branch),
+            next_branch: to_reference(next_branch),
+        })
+    }
+}
+
+/// Kind of the Shortcut entity created to group the stories of a release
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Display)]
+pub enum ReleaseContainer {
+    #[default]
+    #[display(fmt = "epic")]
+    Epic,
+    #[display(fmt = "iteration")]
+    Iteration,
+}
+
+impl FromStr for ReleaseContainer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "epic" => Ok(Self::Epic),
+            "iteration" => Ok(Self::Iteration),
+            _ => Err(anyhow!("Expected epic or iteration, got {s}")),
+        }
+    }
+}
+
+/// Where the publish command publishes the release to Shortcut
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Display)]
+pub enum PublishTarget {
+    /// A label on the stories
+    #[display(fmt = "label")]
+    Label,
+    /// A comment on the stories
+    #[display(fmt = "comment")]
+    Comment,
+    /// The version custom field of the stories
+    #[display(fmt = "version-field")]
+    VersionField,
+    /// The released workflow state of the stories
+    #[display(fmt = "workflow-state")]
+    WorkflowState,
+    /// An epic or iteration grouping the stories
+    #[display(fmt = "shortcut")]
+    Shortcut,
+    /// A Doc with the release notes
+    #[display(fmt = "doc")]
+    Doc,
+}
+
+impl FromStr for PublishTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "label" => Ok(Self::Label),
+            "comment" => Ok(Self::Comment),
+            "version-field" => Ok(Self::VersionField),
+            "workflow-state" => Ok(Self::WorkflowState),
+            "shortcut" => Ok(Self::Shortcut),
+            "doc" => Ok(Self::Doc),
+            _ => Err(anyhow!(
+                "Expected label, comment, version-field, workflow-state, shortcut or doc, got {s}"
+            )),
+        }
+    }
+}
+
+/// Format of the release notes
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Display)]
+pub enum OutputFormat {
+    /// Rendered with the template
+    #[default]
+    #[display(fmt = "markdown")]
+    Markdown,
+    /// The release serialized as JSON, without the template
+    #[display(fmt = "json")]
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("Expected markdown or json, got {s}")),
+        }
+    }
+}
+
+/// Gap of a release which fails the run with `--fail-on`
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Display)]
+pub enum ReleaseGap {
+    /// Commits not referencing any story
+    #[display(fmt = "unparsed-commits")]
+    UnparsedCommits,
+    /// Stories not in a workflow state of the done type
+    #[display(fmt = "not-done-stories")]
+    NotDoneStories,
+    /// Stories referenced by the commits but not in the release notes
+    #[display(fmt = "missing-stories")]
+    MissingStories,
+}
+
+impl ReleaseGap {
+    pub const ALL: [ReleaseGap; 3] = [
+        ReleaseGap::UnparsedCommits,
+        ReleaseGap::NotDoneStories,
+        ReleaseGap::MissingStories,
+    ];
+}
+
+impl FromStr for ReleaseGap {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unparsed-commits" => Ok(Self::UnparsedCommits),
+            "not-done-stories" => Ok(Self::NotDoneStories),
+            "missing-stories" => Ok(Self::MissingStories),
+            _ => Err(anyhow!(
+                "Expected unparsed-commits, not-done-stories or missing-stories, got {s}"
+            )),
+        }
+    }
+}
+
+/// Newtype for a GitHub repository, as `<owner>/<name>`
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, AsRef, Display)]
+#[serde(transparent)]
+pub struct GithubRepository(String);
+
+/// Newtype for the name of a git remote
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, AsRef, Display)]
+#[serde(transparent)]
+pub struct RepositoryRemote(String);
+
+impl Default for RepositoryRemote {
+    fn default() -> Self {
+        RepositoryRemote("origin".to_string())
+    }
+}
+
 fn serialize_oid<S: Serializer>(oid: &GitOid, serializer: S) -> Result<S::Ok, S::Error> {
     serializer.serialize_str(&oid.to_string())
 }
 
+fn serialize_time<S: Serializer>(
+    time: &DateTime<FixedOffset>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&time.to_rfc3339())
+}
+
 /// Head commit of a branch. May or may not have been released
 #[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub struct HeadCommit {
     #[serde(serialize_with = "serialize_oid")]
     pub id: GitOid,
     pub message: Option<String>,
+    /// Reference of the branch, as configured
+    pub branch: String,
+}
+
+/// Merge base of the release and next branches, ie their most recent common commit
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Display, Serialize)]
+#[serde(transparent)]
+pub struct MergeBase(#[serde(serialize_with = "serialize_oid")] GitOid);
+
+impl MergeBase {
+    pub fn new(id: GitOid) -> Self {
+        Self(id)
+    }
 }
 
 /// Commit only present in `next_branch`.
@@ -57,6 +505,145 @@ pub struct UnreleasedCommit {
     #[serde(serialize_with = "serialize_oid")]
     pub id: GitOid,
     pub message: Option<String>,
+    pub author: CommitAuthor,
+    /// Co-authors credited with `Co-authored-by:` trailers
+    pub co_authors: Vec<CommitAuthor>,
+    /// Date the commit was authored
+    #[serde(serialize_with = "serialize_time")]
+    pub authored_at: DateTime<FixedOffset>,
+    /// Date the commit was committed, ie landed in the branch
+    #[serde(serialize_with = "serialize_time")]
+    pub committed_at: DateTime<FixedOffset>,
+    /// Diff statistics, only computed when requested
+    pub diff_stats: Option<DiffStats>,
+    /// Status of the GPG or SSH signature, only verified when requested
+    pub signature: Option<SignatureStatus>,
+    /// Type, scope and breaking status of commit messages following the Conventional Commits
+    /// specification
+    pub conventional: Option<ConventionalCommit>,
+    /// GitHub pull request the commit was merged from, if any
+    pub pull_request: Option<PullRequest>,
+    /// Name of the branch the commit was merged from, if any
+    pub merged_branch: Option<String>,
+    /// Owners of the files touched by the commit according to the `CODEOWNERS` of the
+    /// repository, from the most to the least files owned
+    pub owners: Vec<String>,
+    /// Link to the commit, built from the `commit_url_template` of the repository
+    pub commit_url: Option<String>,
+    /// Stories which may be the one of a commit not referencing any, found by searching Shortcut
+    /// when requested
+    pub suggested_stories: Vec<SuggestedStory>,
+}
+
+/// Commit with an id made of the byte, committed `minute` minutes after midnight, without an
+/// author
+#[cfg(test)]
+pub fn test_commit(id_byte: u8, minute: u32, message: Option<&str>) -> UnreleasedCommit {
+    use chrono::TimeZone;
+
+    let committed_at = FixedOffset::east_opt(0)
+        .expect("UTC should be a valid offset")
+        .with_ymd_and_hms(2024, 1, 1, 0, minute, 0)
+        .unwrap();
+    UnreleasedCommit {
+        id: GitOid::from_bytes(&[id_byte; 20]).unwrap(),
+        message: message.map(str::to_owned),
+        author: CommitAuthor {
+            name: None,
+            email: None,
+        },
+        co_authors: Vec::new(),
+        authored_at: committed_at,
+        committed_at,
+        diff_stats: None,
+        signature: None,
+        conventional: None,
+        pull_request: None,
+        merged_branch: None,
+        owners: Vec::new(),
+        commit_url: None,
+        suggested_stories: Vec::new(),
+    }
+}
+
+/// A story found by searching Shortcut with the subject or branch of a commit
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct SuggestedStory {
+    pub id: i64,
+    pub name: String,
+    pub app_url: String,
+}
+
+/// Statistics of the changes made by one or more commits
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, AddAssign)]
+pub struct DiffStats {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// Status of the signature of a commit
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStatus {
+    /// Signed with a trusted key
+    Signed,
+    Unsigned,
+    /// Signed with a key which isn't trusted, or which couldn't be verified
+    UnknownKey,
+    /// Signature which doesn't match the commit
+    Bad,
+}
+
+/// Number of commits per signature status
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize)]
+pub struct SignatureStats {
+    pub signed: usize,
+    pub unsigned: usize,
+    pub unknown_key: usize,
+    pub bad: usize,
+}
+
+impl SignatureStats {
+    pub fn add(&mut self, status: SignatureStatus) {
+        match status {
+            SignatureStatus::Signed => self.signed += 1,
+            SignatureStatus::Unsigned => self.unsigned += 1,
+            SignatureStatus::UnknownKey => self.unknown_key += 1,
+            SignatureStatus::Bad => self.bad += 1,
+        }
+    }
+}
+
+/// Author of a commit
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize)]
+pub struct CommitAuthor {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// GitHub pull request, as returned by the GitHub API
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub body: Option<String>,
+    pub html_url: String,
+}
+
+/// GitHub release, as returned by the GitHub API
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+pub struct GithubRelease {
+    pub tag_name: String,
+    pub assets: Vec<GithubReleaseAsset>,
+}
+
+/// File attached to a GitHub release. Its `url` is the one of the GitHub API, which also works
+/// for the private repositories.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+pub struct GithubReleaseAsset {
+    pub name: String,
+    pub url: String,
 }
 
 /// A repository name -> unreleased commits mapping
@@ -64,3 +651,39 @@ pub type RepoToCommits = HashMap<RepositoryName, Vec<UnreleasedCommit>>;
 
 /// A repository name -> head of the next branch mapping
 pub type RepoToHeadCommit = HashMap<RepositoryName, HeadCommit>;
+
+/// A repository name -> heads of all the next branches mapping
+pub type RepoToHeadCommits = HashMap<RepositoryName, Vec<HeadCommit>>;
+
+/// A repository name -> merge base of the release and next branches mapping
+pub type RepoToMergeBase = HashMap<RepositoryName, MergeBase>;
+
+/// A repository name -> total diff statistics of the unreleased commits mapping
+pub type RepoToDiffStats = HashMap<RepositoryName, DiffStats>;
+
+/// A repository name -> signature statistics of the unreleased commits mapping
+pub type RepoToSignatureStats = HashMap<RepositoryName, SignatureStats>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_release_gaps() {
+        for gap in ReleaseGap::ALL {
+            assert_eq!(ReleaseGap::from_str(&gap.to_string()).unwrap(), gap);
+        }
+        for s in [
+            "",
+            "Unparsed-Commits",
+            "unparsed_commits",
+            " missing-stories",
+        ] {
+            let err = ReleaseGap::from_str(s).unwrap_err();
+            assert!(
+                err.to_string().starts_with("Expected unparsed-commits"),
+                "{s}"
+            );
+        }
+    }
+}